@@ -0,0 +1,256 @@
+//! Runs a scene file without a window, for batch runs and parameter sweeps on machines without a display.
+//!
+//! ```text
+//! headless <scene.toml|scene.ron> --duration <time> --dt <time> [options]
+//!
+//! options:
+//!     --output <dir>          directory to write the results to (default: .)
+//!     --stride <steps>        record the trajectories and diagnostics every this many steps (default: 1)
+//!     --max-drift <fraction>  fail if the total energy drifts by more than this fraction of the kinetic energy
+//!     --format <json|ron|bin> format of the final state (default: json)
+//!     --trajectories <csv|frames>  format of the trajectories (default: csv)
+//! ```
+//!
+//! The results are written to the output directory:
+//! - `trajectories.csv` (or `.frames`): the id, position, and velocity of every particle at every recorded step, as a
+//!   recording that can be replayed (see `recording`)
+//! - `diagnostics.csv`: the kinetic, potential, and total energy at every recorded step, and the energy drift
+//! - `final.json` (or `.ron`, `.bin`): the final state of the system, which can be loaded with `System::load`
+//!
+//! Exit codes:
+//! - 0: the run finished
+//! - 1: the arguments, scene, or output files were invalid
+//! - 2: a particle's position or velocity became NaN (or infinite)
+//! - 3: the energy drifted by more than `--max-drift`
+//!
+//! A failed run stops at the step where it failed, and still writes its results up to that step. The final state
+//! of a run that failed with exit code 2 holds the NaN (or infinite) values, written as "NaN", "inf", or "-inf" in
+//! JSON, and can still be loaded with `System::load` to inspect what went wrong.
+
+use rusty_particle_physics_2d::import::scene::Scene;
+use rusty_particle_physics_2d::physics::diagnostics::{Diagnostics, Quantity};
+use rusty_particle_physics_2d::physics::system::System;
+use rusty_particle_physics_2d::recording::{Recorder, RecordingFormat};
+use rusty_particle_physics_2d::save::Format;
+
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+const USAGE: &str =
+    "usage: headless <scene.toml|scene.ron> --duration <time> --dt <time> [--output <dir>] \
+    [--stride <steps>] [--max-drift <fraction>] [--format <json|ron|bin>] [--trajectories <csv|frames>]";
+
+/// The command line options.
+struct Options {
+    scene: PathBuf,
+    duration: f64,
+    dt: f64,
+    output: PathBuf,
+    stride: u64,
+    max_drift: Option<f64>,
+    format: Format,
+    trajectories: RecordingFormat,
+}
+
+/// Why a run stopped early.
+enum Failure {
+    NotFinite { step: u64, particle: u32 },
+    Drift { step: u64, drift: f64 },
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let (mut scene, mut duration, mut dt) = (None, None, None);
+    let mut options = Options {
+        scene: PathBuf::new(),
+        duration: 0.0,
+        dt: 0.0,
+        output: PathBuf::from("."),
+        stride: 1,
+        max_drift: None,
+        format: Format::Json,
+        trajectories: RecordingFormat::Csv,
+    };
+    while let Some(arg) = args.next() {
+        if !arg.starts_with("--") {
+            if scene.replace(PathBuf::from(&arg)).is_some() {
+                return Err(format!("unexpected argument {arg}"));
+            }
+            continue;
+        }
+        let value = args.next().ok_or_else(|| format!("{arg} needs a value"))?;
+        let number = |value: &str| {
+            value
+                .parse::<f64>()
+                .map_err(|_| format!("{arg} needs a number, not {value}"))
+        };
+        match arg.as_str() {
+            "--duration" => duration = Some(number(&value)?),
+            "--dt" => dt = Some(number(&value)?),
+            "--output" => options.output = PathBuf::from(value),
+            "--stride" => {
+                options.stride = value
+                    .parse::<u64>()
+                    .ok()
+                    .filter(|&stride| stride > 0)
+                    .ok_or_else(|| format!("--stride needs a positive whole number, not {value}"))?
+            }
+            "--max-drift" => {
+                let drift = number(&value)?;
+                if !(drift.is_finite() && drift >= 0.0) {
+                    return Err(format!(
+                        "--max-drift needs a finite, non-negative number, not {value}"
+                    ));
+                }
+                options.max_drift = Some(drift);
+            }
+            "--format" => {
+                options.format = match value.as_str() {
+                    "json" => Format::Json,
+                    "ron" => Format::Ron,
+                    "bin" | "bincode" => Format::Bincode,
+                    _ => return Err(format!("unknown format {value}")),
+                }
+            }
+            "--trajectories" => {
+                options.trajectories = match value.as_str() {
+                    "csv" => RecordingFormat::Csv,
+                    "frames" => RecordingFormat::Binary,
+                    _ => return Err(format!("unknown trajectory format {value}")),
+                }
+            }
+            _ => return Err(format!("unknown option {arg}")),
+        }
+    }
+    options.scene = scene.ok_or("missing the scene file")?;
+    options.duration = duration
+        .filter(|&duration| duration.is_finite() && duration >= 0.0)
+        .ok_or("missing a finite, non-negative --duration")?;
+    options.dt = dt
+        .filter(|&dt| dt.is_finite() && dt > 0.0)
+        .ok_or("missing a finite, positive --dt")?;
+    Ok(options)
+}
+
+/// The first particle whose position or velocity isn't finite.
+fn not_finite(system: &System) -> Option<u32> {
+    system
+        .particles
+        .iter()
+        .find(|p| {
+            !(p.pos.x.is_finite()
+                && p.pos.y.is_finite()
+                && p.vel.x.is_finite()
+                && p.vel.y.is_finite())
+        })
+        .map(|p| p.id)
+}
+
+fn record(
+    diagnostics: &Diagnostics,
+    step: u64,
+    initial: &Diagnostics,
+    output: &mut impl Write,
+) -> std::io::Result<()> {
+    writeln!(
+        output,
+        "{step},{},{},{},{},{}",
+        diagnostics.time,
+        diagnostics.kinetic_energy,
+        diagnostics.potential_energy,
+        diagnostics.total_energy(),
+        diagnostics.drift(initial, Quantity::Energy)
+    )
+}
+
+fn run(options: &Options) -> Result<Option<Failure>, String> {
+    let scene = Scene::load(&options.scene)
+        .map_err(|error| format!("{}: {error}", options.scene.display()))?;
+    let (mut system, _) = scene
+        .build()
+        .map_err(|error| format!("{}: {error}", options.scene.display()))?;
+
+    std::fs::create_dir_all(&options.output).map_err(|error| error.to_string())?;
+    let create = |name: &str| {
+        let path = options.output.join(name);
+        File::create(&path)
+            .map(BufWriter::new)
+            .map_err(|error| format!("{}: {error}", path.display()))
+    };
+    let mut diagnostics = create("diagnostics.csv")?;
+    let io = |error: std::io::Error| error.to_string();
+    writeln!(diagnostics, "step,time,kinetic,potential,total,drift").map_err(io)?;
+
+    let initial = system.diagnostics();
+    let steps = (options.duration / options.dt).round() as u64;
+    let mut failure = not_finite(&system).map(|particle| Failure::NotFinite { step: 0, particle });
+    let trajectories = options.output.join(match options.trajectories {
+        RecordingFormat::Csv => "trajectories.csv",
+        RecordingFormat::Binary => "trajectories.frames",
+    });
+    let recorder = Recorder::create(&trajectories)
+        .map_err(|error| format!("{}: {error}", trajectories.display()))?
+        .stride(options.stride);
+    system.record(recorder);
+    record(&initial, 0, &initial, &mut diagnostics).map_err(io)?;
+    for step in 1..=steps {
+        if failure.is_some() {
+            break;
+        }
+        system.step_forward(options.dt);
+        let current = system.diagnostics();
+        if let Some(particle) = not_finite(&system) {
+            failure = Some(Failure::NotFinite { step, particle });
+        } else if let Some(max_drift) = options.max_drift {
+            if let Err(violation) = current.check(&initial, Quantity::Energy, max_drift) {
+                failure = Some(Failure::Drift {
+                    step,
+                    drift: violation.drift,
+                });
+            }
+        }
+        if step % options.stride == 0 || step == steps || failure.is_some() {
+            record(&current, step, &initial, &mut diagnostics).map_err(io)?;
+        }
+    }
+    if let Some(recorder) = system.stop_recording() {
+        recorder.finish().map_err(io)?;
+    }
+    diagnostics.flush().map_err(io)?;
+
+    let extension = match options.format {
+        Format::Json => "json",
+        Format::Ron => "ron",
+        Format::Bincode => "bin",
+    };
+    system
+        .save(options.output.join(format!("final.{extension}")))
+        .map_err(|error| error.to_string())?;
+    Ok(failure)
+}
+
+fn main() -> ExitCode {
+    let options = match parse_options(std::env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{message}\n{USAGE}");
+            return ExitCode::from(1);
+        }
+    };
+    match run(&options) {
+        Ok(None) => ExitCode::SUCCESS,
+        Ok(Some(Failure::NotFinite { step, particle })) => {
+            eprintln!("particle {particle} became NaN or infinite at step {step}");
+            ExitCode::from(2)
+        }
+        Ok(Some(Failure::Drift { step, drift })) => {
+            eprintln!("the energy drifted by {drift:e} at step {step}");
+            ExitCode::from(3)
+        }
+        Err(message) => {
+            eprintln!("{message}");
+            ExitCode::from(1)
+        }
+    }
+}
@@ -0,0 +1,189 @@
+//! Provides procedural generators, which build common arrangements of particles instead of placing them by hand.
+//!
+//! Every generator copies a template particle (its mass, radius, color, group, etc) to each position it makes, and
+//! returns the particles to be added with `System::add_particles`. Ropes, chains and rings can then be held
+//! together with `System::add_links`.
+//!
+//! Gases are given velocities from the Maxwell-Boltzmann distribution at a temperature. Like the thermostats, the
+//! Boltzmann constant is 1, so each velocity component is normally distributed with a variance of T/m.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::generators;
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! let mut sim = System::new();
+//! let bead = Particle::new().radius(2.0).mass(1.0);
+//!
+//! // a rope hanging between two points, pinned at both ends
+//! let rope = sim.add_particles(generators::rope(&bead, Vec2::new(-50.0, 0.0), Vec2::new(50.0, 0.0), 21));
+//! sim.add_links(&rope, false, 0.0);
+//! sim.particles[rope[0]].mass = f64::INFINITY;
+//! sim.particles[rope[20]].mass = f64::INFINITY;
+//!
+//! // and a warm gas above it
+//! let gas = generators::gas(&bead, 100, Vec2::new(-50.0, 10.0), Vec2::new(50.0, 60.0), 2.0, 7);
+//! assert_eq!(gas.len(), 100);
+//! sim.add_particles(gas);
+//! ```
+
+use crate::physics::neighbor_grid::NeighborGrid;
+use crate::physics::particle::Particle;
+use crate::random::Rng;
+use crate::vec2::Vec2;
+
+use std::f64::consts::PI;
+
+/// Number of random positions tried for each particle of a gas before giving up on it.
+const GAS_ATTEMPTS: usize = 1000;
+
+/// A copy of the template at a position.
+fn place(template: &Particle, pos: Vec2) -> Particle {
+    template.clone().pos(pos.x, pos.y)
+}
+
+/// A square lattice of particles, row by row from its bottom left corner at the origin.
+pub fn square_lattice(
+    template: &Particle,
+    origin: Vec2,
+    columns: usize,
+    rows: usize,
+    spacing: f64,
+) -> Vec<Particle> {
+    (0..rows)
+        .flat_map(|row| {
+            (0..columns).map(move |column| {
+                place(
+                    template,
+                    origin + Vec2::new(column as f64, row as f64) * spacing,
+                )
+            })
+        })
+        .collect()
+}
+
+/// A hexagonal (close packed) lattice of particles, row by row from its bottom left corner at the origin. Odd rows
+/// are offset by half of the spacing, and rows are √3/2 of the spacing apart, so every particle is the spacing away
+/// from its six neighbors.
+pub fn hexagonal_lattice(
+    template: &Particle,
+    origin: Vec2,
+    columns: usize,
+    rows: usize,
+    spacing: f64,
+) -> Vec<Particle> {
+    let row_height = spacing * 3.0_f64.sqrt() / 2.0;
+    (0..rows)
+        .flat_map(|row| {
+            let offset = if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+            (0..columns).map(move |column| {
+                place(
+                    template,
+                    origin + Vec2::new(column as f64 * spacing + offset, row as f64 * row_height),
+                )
+            })
+        })
+        .collect()
+}
+
+/// A gas of particles at random, non-overlapping positions inside of a box, with velocities from the
+/// Maxwell-Boltzmann distribution at a temperature.
+///
+/// If the box is too crowded to fit every particle, the particles that couldn't be placed are left out, so the
+/// result can have fewer than `count` particles.
+pub fn gas(
+    template: &Particle,
+    count: usize,
+    min: Vec2,
+    max: Vec2,
+    temperature: f64,
+    seed: u64,
+) -> Vec<Particle> {
+    let mut rng = Rng::new(seed);
+    let radius = template.radius;
+    let (low, high) = (
+        min + Vec2::new(radius, radius),
+        max - Vec2::new(radius, radius),
+    );
+    // point particles can't overlap, so they don't need a grid (which couldn't have cells of zero size anyway)
+    let mut grid = if radius > 0.0 {
+        Some(NeighborGrid::new(2.0 * radius))
+    } else {
+        None
+    };
+    let mut positions: Vec<Vec2> = Vec::with_capacity(count);
+    for _ in 0..count {
+        for _ in 0..GAS_ATTEMPTS {
+            let pos = Vec2::new(rng.range(low.x, high.x), rng.range(low.y, high.y));
+            let overlaps = match &grid {
+                Some(grid) => grid
+                    .candidates(pos)
+                    .any(|other| (positions[other] - pos).mag() < 2.0 * radius),
+                None => false,
+            };
+            if !overlaps {
+                if let Some(grid) = &mut grid {
+                    grid.insert(positions.len(), pos);
+                }
+                positions.push(pos);
+                break;
+            }
+        }
+    }
+    let deviation = (temperature / template.mass).sqrt();
+    positions
+        .into_iter()
+        .map(|pos| {
+            let vel = if deviation.is_finite() {
+                rng.normal_vec2() * deviation
+            } else {
+                Vec2::zero()
+            };
+            place(template, pos).vel(vel.x, vel.y)
+        })
+        .collect()
+}
+
+/// A rope of some number of particles, evenly spaced from the start to the end (inclusive).
+pub fn rope(template: &Particle, start: Vec2, end: Vec2, count: usize) -> Vec<Particle> {
+    match count {
+        0 => Vec::new(),
+        1 => vec![place(template, start)],
+        _ => (0..count)
+            .map(|k| {
+                place(
+                    template,
+                    start + (end - start) * (k as f64 / (count - 1) as f64),
+                )
+            })
+            .collect(),
+    }
+}
+
+/// A chain of particles from the start to the end, with just enough particles that neighbors touch (or overlap
+/// slightly).
+///
+/// Panics if the template's radius isn't positive, since no number of particles would then be enough.
+pub fn chain(template: &Particle, start: Vec2, end: Vec2) -> Vec<Particle> {
+    assert!(
+        template.radius > 0.0,
+        "the particles of a chain must have a positive radius, not {}",
+        template.radius
+    );
+    let links = ((end - start).mag() / (2.0 * template.radius)).ceil() as usize;
+    rope(template, start, end, links.max(1) + 1)
+}
+
+/// A ring of some number of particles, evenly spaced counterclockwise around a circle, starting at its rightmost
+/// point.
+pub fn ring(template: &Particle, center: Vec2, radius: f64, count: usize) -> Vec<Particle> {
+    (0..count)
+        .map(|k| {
+            place(
+                template,
+                center + Vec2::new_polar(radius, 2.0 * PI * k as f64 / count as f64),
+            )
+        })
+        .collect()
+}
@@ -0,0 +1,275 @@
+//! A decoder for uncompressed BMP images, with 1, 4, 8, 16, 24 or 32 bits per pixel (and bit field masks).
+
+use crate::import::{Decoded, ImportError, MAX_PIXELS};
+
+fn error(message: impl Into<String>) -> ImportError {
+    ImportError::Format(format!("invalid BMP: {}", message.into()))
+}
+
+/// Whether some bytes are a BMP image.
+pub(crate) fn is_bmp(bytes: &[u8]) -> bool {
+    bytes.starts_with(b"BM")
+}
+
+/// A little endian integer at some offset.
+fn read(bytes: &[u8], offset: usize, size: usize) -> Result<u32, ImportError> {
+    let field = bytes
+        .get(offset..offset + size)
+        .ok_or_else(|| error("unexpected end of file"))?;
+    Ok(field
+        .iter()
+        .rev()
+        .fold(0, |value, &byte| value << 8 | byte as u32))
+}
+
+/// Scale the bits of a pixel under a mask to 8 bits (or None if the mask is empty).
+fn channel(pixel: u32, mask: u32) -> Option<u8> {
+    if mask == 0 {
+        return None;
+    }
+    let value = (pixel & mask) >> mask.trailing_zeros();
+    let maximum = mask >> mask.trailing_zeros();
+    Some((value as u64 * 255 / maximum as u64) as u8)
+}
+
+/// Decode a BMP image into its width, height, and pixels (row by row from the top left).
+pub(crate) fn decode(bytes: &[u8]) -> Result<Decoded, ImportError> {
+    if !is_bmp(bytes) {
+        return Err(error("missing signature"));
+    }
+    let data_offset = read(bytes, 10, 4)? as usize;
+    let header_size = read(bytes, 14, 4)? as usize;
+    // the old OS/2 header has 16 bit sizes and 3 byte palette entries
+    let core = header_size == 12;
+    let (width, height, bits, compression, palette_size) = if core {
+        (
+            read(bytes, 18, 2)? as i32,
+            read(bytes, 20, 2)? as i32,
+            read(bytes, 24, 2)?,
+            0,
+            0,
+        )
+    } else {
+        (
+            read(bytes, 18, 4)? as i32,
+            read(bytes, 22, 4)? as i32,
+            read(bytes, 28, 2)?,
+            read(bytes, 30, 4)?,
+            read(bytes, 46, 4)? as usize,
+        )
+    };
+    // a negative height is stored from the top row down
+    let top_down = height < 0;
+    let (width, height) = (
+        width.unsigned_abs() as usize,
+        height.unsigned_abs() as usize,
+    );
+    if width.saturating_mul(height) > MAX_PIXELS {
+        return Err(error(format!("{width}x{height} is too large")));
+    }
+
+    let masks = match (compression, bits) {
+        (0, 16) => [0x7c00, 0x03e0, 0x001f, 0],
+        (0, 32) => [0x00ff_0000, 0x0000_ff00, 0x0000_00ff, 0xff00_0000],
+        // bit fields, which follow the basic header (or are part of a larger one)
+        (3 | 6, 16 | 32) => [
+            read(bytes, 54, 4)?,
+            read(bytes, 58, 4)?,
+            read(bytes, 62, 4)?,
+            if compression == 6 || header_size >= 56 {
+                read(bytes, 66, 4)?
+            } else {
+                0
+            },
+        ],
+        (0, 1 | 4 | 8 | 24) => [0; 4],
+        _ => {
+            return Err(error(format!(
+                "{bits} bits per pixel with compression {compression} isn't supported"
+            )))
+        }
+    };
+
+    let entry = if core { 3 } else { 4 };
+    let palette: Vec<(u8, u8, u8, u8)> = if bits <= 8 {
+        let count = if palette_size == 0 {
+            1 << bits
+        } else {
+            palette_size
+        };
+        (0..count)
+            .map(|k| {
+                let offset = 14 + header_size + k * entry;
+                let color = bytes
+                    .get(offset..offset + 3)
+                    .ok_or_else(|| error("unexpected end of palette"))?;
+                Ok((color[2], color[1], color[0], 255))
+            })
+            .collect::<Result<_, ImportError>>()?
+    } else {
+        Vec::new()
+    };
+
+    let stride = (bits as usize * width).div_ceil(32) * 4;
+    // checked before the pixels are allocated, so that a short file can't claim to be a huge image
+    if data_offset + stride * height > bytes.len() {
+        return Err(error("not enough image data"));
+    }
+    let mut pixels = Vec::with_capacity(width * height);
+    for y in 0..height {
+        let stored = if top_down { y } else { height - 1 - y };
+        let start = data_offset + stored * stride;
+        let row = bytes
+            .get(start..start + stride)
+            .ok_or_else(|| error("not enough image data"))?;
+        for x in 0..width {
+            pixels.push(match bits {
+                1 | 4 | 8 => {
+                    let bit = x * bits as usize;
+                    let index = (row[bit / 8] >> (8 - bits as usize - bit % 8))
+                        & ((1u16 << bits) - 1) as u8;
+                    palette
+                        .get(index as usize)
+                        .copied()
+                        .unwrap_or((0, 0, 0, 255))
+                }
+                24 => (row[3 * x + 2], row[3 * x + 1], row[3 * x], 255),
+                _ => {
+                    let size = bits as usize / 8;
+                    let pixel = row[size * x..size * (x + 1)]
+                        .iter()
+                        .rev()
+                        .fold(0, |value, &byte| value << 8 | byte as u32);
+                    (
+                        channel(pixel, masks[0]).unwrap_or(0),
+                        channel(pixel, masks[1]).unwrap_or(0),
+                        channel(pixel, masks[2]).unwrap_or(0),
+                        channel(pixel, masks[3]).unwrap_or(255),
+                    )
+                }
+            });
+        }
+    }
+    // plenty of writers leave the alpha byte of a 32 bit image unused, so an image that's entirely transparent
+    // is really opaque
+    if bits == 32 && pixels.iter().all(|pixel| pixel.3 == 0) {
+        for pixel in &mut pixels {
+            pixel.3 = 255;
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A BMP image with a 40 byte header, whose rows (bottom row first, unless the height is negative) are padded
+    /// to 4 bytes.
+    fn bmp(width: i32, height: i32, bits: u16, palette: &[[u8; 4]], rows: &[&[u8]]) -> Vec<u8> {
+        let data_offset = 14 + 40 + 4 * palette.len() as u32;
+        let mut bytes = b"BM".to_vec();
+        bytes.extend_from_slice(&[0; 8]);
+        bytes.extend_from_slice(&data_offset.to_le_bytes());
+        bytes.extend_from_slice(&40u32.to_le_bytes());
+        bytes.extend_from_slice(&width.to_le_bytes());
+        bytes.extend_from_slice(&height.to_le_bytes());
+        bytes.extend_from_slice(&1u16.to_le_bytes());
+        bytes.extend_from_slice(&bits.to_le_bytes());
+        bytes.extend_from_slice(&[0; 16]);
+        bytes.extend_from_slice(&(palette.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(&[0; 4]);
+        for entry in palette {
+            bytes.extend_from_slice(entry);
+        }
+        for row in rows {
+            bytes.extend_from_slice(row);
+            bytes.resize(bytes.len() + (4 - row.len() % 4) % 4, 0);
+        }
+        bytes
+    }
+
+    /// Black and white, stored as blue, green, red, unused.
+    const BLACK_WHITE: [[u8; 4]; 2] = [[0, 0, 0, 0], [255, 255, 255, 0]];
+
+    const BLACK: (u8, u8, u8, u8) = (0, 0, 0, 255);
+    const WHITE: (u8, u8, u8, u8) = (255, 255, 255, 255);
+
+    #[test]
+    fn one_bit() {
+        // stored bottom row first
+        let bytes = bmp(3, 2, 1, &BLACK_WHITE, &[&[0b0100_0000], &[0b1010_0000]]);
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            (3, 2, vec![WHITE, BLACK, WHITE, BLACK, WHITE, BLACK])
+        );
+    }
+
+    #[test]
+    fn four_bit() {
+        let bytes = bmp(3, 1, 4, &BLACK_WHITE, &[&[0x10, 0x10]]);
+        assert_eq!(decode(&bytes).unwrap(), (3, 1, vec![WHITE, BLACK, WHITE]));
+    }
+
+    #[test]
+    fn eight_bit() {
+        // top row first, with an index past the end of the palette
+        let bytes = bmp(2, -2, 8, &BLACK_WHITE, &[&[1, 0], &[0, 7]]);
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            (2, 2, vec![WHITE, BLACK, BLACK, BLACK])
+        );
+    }
+
+    #[test]
+    fn twenty_four_bit() {
+        let bytes = bmp(2, 1, 24, &[], &[&[1, 2, 3, 4, 5, 6]]);
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            (2, 1, vec![(3, 2, 1, 255), (6, 5, 4, 255)])
+        );
+    }
+
+    #[test]
+    fn thirty_two_bit() {
+        let bytes = bmp(2, 1, 32, &[], &[&[1, 2, 3, 128, 4, 5, 6, 255]]);
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            (2, 1, vec![(3, 2, 1, 128), (6, 5, 4, 255)])
+        );
+        // an alpha byte that's never used
+        let bytes = bmp(2, 1, 32, &[], &[&[1, 2, 3, 0, 4, 5, 6, 0]]);
+        assert_eq!(
+            decode(&bytes).unwrap(),
+            (2, 1, vec![(3, 2, 1, 255), (6, 5, 4, 255)])
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = bmp(3, 2, 24, &[], &[&[1; 9], &[2; 9]]);
+        assert!(decode(&bytes).is_ok());
+        for length in 0..bytes.len() {
+            assert!(decode(&bytes[..length]).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        // an unsupported bit depth
+        assert!(decode(&bmp(1, 1, 2, &BLACK_WHITE, &[&[0]])).is_err());
+        // too large to fit in memory, or larger than the file, which are found before anything is allocated
+        assert!(decode(&bmp(1 << 20, 1 << 20, 24, &[], &[&[0]])).is_err());
+        assert!(decode(&bmp(i32::MIN, i32::MIN, 32, &[], &[&[0]])).is_err());
+        assert!(decode(&bmp(8192, 8192, 32, &[], &[&[0]])).is_err());
+        // every single byte change decodes (to something) or fails, without panicking
+        let bytes = bmp(3, 2, 8, &BLACK_WHITE, &[&[0, 1, 0], &[1, 0, 1]]);
+        for index in 0..bytes.len() {
+            for value in [0, 1, 3, 4, 8, 12, 16, 32, 0x80, 0xff] {
+                let mut corrupted = bytes.clone();
+                corrupted[index] = value;
+                let _ = decode(&corrupted);
+            }
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Provides an image importer, which turns a raster image (PNG or BMP) into a grid of colored particles.
+//!
+//! The image is cut into square blocks of pixels (the sampling resolution). Every block whose average alpha reaches
+//! the alpha threshold becomes a particle, colored with the average color of its opaque pixels. Neighboring
+//! particles (including diagonal ones) can be connected with distance constraints, so that a logo holds together
+//! until it's shattered, or they can be left loose, so that it can be poured like sand.
+//!
+//! Images are stored with their top row first, so the grid is flipped to keep the image upright, with its bottom
+//! left block at the origin.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::import::image::Image;
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! // a 4x2 image whose right half is transparent
+//! let red = (255, 0, 0, 255);
+//! let clear = (0, 0, 0, 0);
+//! let image = Image::new(4, 2, vec![red, red, clear, clear, red, red, clear, clear])
+//!     .spacing(2.0)
+//!     .constraints(0.0);
+//!
+//! let mut sim = System::new();
+//! let body = sim.add_image(&image);
+//! assert_eq!(body.particles.len(), 4);
+//! assert_eq!(body.constraints.len(), 6);
+//! let corner = sim.particles[body.particle(1, 1).unwrap()].pos;
+//! assert_eq!((corner.x, corner.y), (2.0, 2.0));
+//! ```
+
+use crate::import::{bmp, png, ImportError};
+use crate::physics::constraint::Constraint;
+use crate::physics::particle::Particle;
+use crate::physics::system::System;
+use crate::vec2::Vec2;
+
+use std::path::Path;
+
+/// A raster image, and how it should be turned into particles.
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    /// 32-bit colors (r, g, b, a), row by row from the top left
+    pub pixels: Vec<(u8, u8, u8, u8)>,
+    /// width and height (in pixels) of the block that each particle samples
+    pub resolution: usize,
+    /// minimum average alpha of a block for it to become a particle
+    pub alpha_threshold: u8,
+    /// distance between neighboring particles
+    pub spacing: f64,
+    /// where the bottom left particle is placed
+    pub origin: Vec2,
+    /// mass of each particle
+    pub particle_mass: f64,
+    /// radius of each particle, where None uses half of the spacing
+    pub radius: Option<f64>,
+    pub group: u32,
+    /// compliance of the distance constraints between neighboring particles, if there should be any
+    pub compliance: Option<f64>,
+}
+
+/// A handle to the particles that an image was turned into.
+pub struct ImageBody {
+    /// indices of the particles in `System::particles`
+    pub particles: Vec<usize>,
+    /// indices of the constraints between neighbors in `System::constraints`
+    pub constraints: Vec<usize>,
+    /// number of blocks across the image
+    pub columns: usize,
+    /// number of blocks down the image
+    pub rows: usize,
+    /// index in `System::particles` of the particle at each block (from the bottom left), if the block was opaque
+    grid: Vec<Option<usize>>,
+}
+
+impl ImageBody {
+    /// The index in `System::particles` of the particle in a column and row (from the bottom left), if that
+    /// block of the image was opaque.
+    pub fn particle(self: &Self, column: usize, row: usize) -> Option<usize> {
+        if column < self.columns && row < self.rows {
+            self.grid[row * self.columns + column]
+        } else {
+            None
+        }
+    }
+}
+
+impl Image {
+    /// Create an image out of its pixels (row by row from the top left), which is sampled at every pixel.
+    pub fn new(width: usize, height: usize, pixels: Vec<(u8, u8, u8, u8)>) -> Image {
+        assert_eq!(
+            pixels.len(),
+            width * height,
+            "an image needs width * height pixels"
+        );
+        Image {
+            width,
+            height,
+            pixels,
+            resolution: 1,
+            alpha_threshold: 128,
+            spacing: 1.0,
+            origin: Vec2::zero(),
+            particle_mass: Particle::new().mass,
+            radius: None,
+            group: 0,
+            compliance: None,
+        }
+    }
+
+    /// Decode a PNG or BMP image. Images with more than 8192x8192 pixels are rejected.
+    pub fn decode(bytes: &[u8]) -> Result<Image, ImportError> {
+        let (width, height, pixels) = if png::is_png(bytes) {
+            png::decode(bytes)?
+        } else if bmp::is_bmp(bytes) {
+            bmp::decode(bytes)?
+        } else {
+            return Err(ImportError::Format(
+                "unsupported image format (expected PNG or BMP)".to_string(),
+            ));
+        };
+        Ok(Image::new(width, height, pixels))
+    }
+
+    /// Read and decode a PNG or BMP image file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Image, ImportError> {
+        Image::decode(&std::fs::read(path)?)
+    }
+
+    /// A builder method to sample blocks of some number of pixels after creating it.
+    pub fn resolution(mut self: Self, resolution: usize) -> Image {
+        self.resolution = resolution.max(1);
+        self
+    }
+
+    /// A builder method to give the image a specific alpha threshold after creating it.
+    pub fn alpha_threshold(mut self: Self, alpha_threshold: u8) -> Image {
+        self.alpha_threshold = alpha_threshold;
+        self
+    }
+
+    /// A builder method to give the particles a specific spacing after creating it.
+    pub fn spacing(mut self: Self, spacing: f64) -> Image {
+        self.spacing = spacing;
+        self
+    }
+
+    /// A builder method to place the image's bottom left particle somewhere specific after creating it.
+    pub fn origin(mut self: Self, x: f64, y: f64) -> Image {
+        self.origin = Vec2::new(x, y);
+        self
+    }
+
+    /// A builder method to give each particle a specific mass after creating it.
+    pub fn particle_mass(mut self: Self, mass: f64) -> Image {
+        self.particle_mass = mass;
+        self
+    }
+
+    /// A builder method to give the particles a specific radius after creating it.
+    pub fn radius(mut self: Self, radius: f64) -> Image {
+        self.radius = Some(radius);
+        self
+    }
+
+    /// A builder method to give the particles a specific group after creating it.
+    pub fn group(mut self: Self, group: u32) -> Image {
+        self.group = group;
+        self
+    }
+
+    /// A builder method to connect neighboring particles with distance constraints of some compliance after
+    /// creating it.
+    pub fn constraints(mut self: Self, compliance: f64) -> Image {
+        self.compliance = Some(compliance);
+        self
+    }
+
+    /// The number of blocks across and down the image.
+    pub fn blocks(self: &Self) -> (usize, usize) {
+        (
+            self.width.div_ceil(self.resolution),
+            self.height.div_ceil(self.resolution),
+        )
+    }
+
+    /// The color of a block (counted from the top left), or None if it isn't opaque enough.
+    pub fn sample(self: &Self, column: usize, row: usize) -> Option<(u8, u8, u8, u8)> {
+        let (mut sum, mut count, mut alpha) = ([0u64; 3], 0u64, 0u64);
+        let mut pixels = 0;
+        for y in row * self.resolution..((row + 1) * self.resolution).min(self.height) {
+            for x in column * self.resolution..((column + 1) * self.resolution).min(self.width) {
+                let (r, g, b, a) = self.pixels[y * self.width + x];
+                pixels += 1;
+                alpha += a as u64;
+                if a > 0 {
+                    // weigh the color by alpha, so that antialiased edges don't darken it
+                    for (total, value) in sum.iter_mut().zip([r, g, b]) {
+                        *total += value as u64 * a as u64;
+                    }
+                    count += a as u64;
+                }
+            }
+        }
+        if pixels == 0 || count == 0 || alpha / pixels < self.alpha_threshold as u64 {
+            return None;
+        }
+        Some((
+            (sum[0] / count) as u8,
+            (sum[1] / count) as u8,
+            (sum[2] / count) as u8,
+            (alpha / pixels) as u8,
+        ))
+    }
+
+    /// Add the image's particles and constraints to a system.
+    pub(crate) fn build(self: &Self, system: &mut System) -> ImageBody {
+        let (columns, rows) = self.blocks();
+        let radius = self.radius.unwrap_or(self.spacing / 2.0);
+        let mut body = ImageBody {
+            particles: Vec::new(),
+            constraints: Vec::new(),
+            columns,
+            rows,
+            grid: vec![None; columns * rows],
+        };
+        for row in 0..rows {
+            for column in 0..columns {
+                // rows are counted from the bottom, but the image is stored from the top
+                if let Some(color) = self.sample(column, rows - 1 - row) {
+                    let pos = self.origin + Vec2::new(column as f64, row as f64) * self.spacing;
+                    let index = system.add_particle(
+                        Particle::new()
+                            .pos(pos.x, pos.y)
+                            .mass(self.particle_mass)
+                            .radius(radius)
+                            .color(color)
+                            .group(self.group),
+                    );
+                    body.grid[row * columns + column] = Some(index);
+                    body.particles.push(index);
+                }
+            }
+        }
+        if let Some(compliance) = self.compliance {
+            for row in 0..rows {
+                for column in 0..columns {
+                    let Some(a) = body.particle(column, row) else {
+                        continue;
+                    };
+                    // right, up, and both diagonals above, so each pair is only connected once
+                    let neighbors = [
+                        body.particle(column + 1, row),
+                        body.particle(column, row + 1),
+                        body.particle(column + 1, row + 1),
+                        column
+                            .checked_sub(1)
+                            .and_then(|left| body.particle(left, row + 1)),
+                    ];
+                    for b in neighbors.into_iter().flatten() {
+                        let constraint = Constraint::distance(
+                            a,
+                            b,
+                            &system.particles,
+                            &system.boundary,
+                            compliance,
+                        );
+                        body.constraints.push(system.add_constraint(constraint));
+                    }
+                }
+            }
+        }
+        body
+    }
+}
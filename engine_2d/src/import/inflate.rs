@@ -0,0 +1,342 @@
+//! A decoder for zlib streams (RFC 1950) of deflate data (RFC 1951), which PNG images are compressed with.
+//!
+//! Huffman codes are decoded one bit at a time from their canonical form, like zlib's `puff`, which is simple
+//! rather than fast.
+
+use crate::import::ImportError;
+
+/// Base lengths and extra bits of the length symbols 257..=285.
+const LENGTH_BASE: [u16; 29] = [
+    3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131,
+    163, 195, 227, 258,
+];
+const LENGTH_EXTRA: [u8; 29] = [
+    0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+];
+/// Base distances and extra bits of the distance symbols 0..=29.
+const DISTANCE_BASE: [u16; 30] = [
+    1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537,
+    2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577,
+];
+const DISTANCE_EXTRA: [u8; 30] = [
+    0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13,
+    13,
+];
+/// The order that the code length code lengths are stored in.
+const CODE_LENGTH_ORDER: [usize; 19] = [
+    16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+];
+
+fn error(message: &str) -> ImportError {
+    ImportError::Format(format!("invalid compressed data: {message}"))
+}
+
+fn too_large() -> ImportError {
+    error("decompresses to more data than expected")
+}
+
+/// A canonical Huffman code, as the number of codes of each length and the symbols in code order.
+struct Huffman {
+    counts: [u16; 16],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Huffman {
+        let mut counts = [0; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        let mut offsets = [0; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, &length) in lengths.iter().enumerate() {
+            if length != 0 {
+                symbols[offsets[length as usize] as usize] = symbol as u16;
+                offsets[length as usize] += 1;
+            }
+        }
+        Huffman { counts, symbols }
+    }
+}
+
+/// Reads the stream's bits, least significant first.
+struct Bits<'a> {
+    data: &'a [u8],
+    position: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn bits(self: &mut Self, count: u32) -> Result<u32, ImportError> {
+        while self.count < count {
+            let byte = *self
+                .data
+                .get(self.position)
+                .ok_or_else(|| error("unexpected end of data"))?;
+            self.position += 1;
+            self.buffer |= (byte as u32) << self.count;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << count) - 1) as u32;
+        self.buffer >>= count;
+        self.count -= count;
+        Ok(value)
+    }
+
+    fn decode(self: &mut Self, huffman: &Huffman) -> Result<u16, ImportError> {
+        let (mut code, mut first, mut index) = (0i32, 0i32, 0i32);
+        for length in 1..16 {
+            code |= self.bits(1)? as i32;
+            let count = huffman.counts[length] as i32;
+            if code - count < first {
+                return Ok(huffman.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(error("invalid Huffman code"))
+    }
+}
+
+/// Decompress a zlib stream, which mustn't decompress to more than `limit` bytes (so that a small malicious file
+/// can't use up all of the memory).
+pub(crate) fn decompress(data: &[u8], limit: usize) -> Result<Vec<u8>, ImportError> {
+    if data.len() < 2
+        || data[0] & 0x0f != 8
+        || !((data[0] as u16) << 8 | data[1] as u16).is_multiple_of(31)
+    {
+        return Err(error("bad zlib header"));
+    }
+    if data[1] & 0x20 != 0 {
+        return Err(error("preset dictionaries aren't supported"));
+    }
+    let mut bits = Bits {
+        data: &data[2..],
+        position: 0,
+        buffer: 0,
+        count: 0,
+    };
+    let mut output = Vec::new();
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => {
+                // stored, starting at the next byte
+                bits.buffer = 0;
+                bits.count = 0;
+                let start = bits.position;
+                let header = bits
+                    .data
+                    .get(start..start + 4)
+                    .ok_or_else(|| error("unexpected end of data"))?;
+                let length = u16::from_le_bytes([header[0], header[1]]) as usize;
+                if length != !u16::from_le_bytes([header[2], header[3]]) as usize {
+                    return Err(error("stored block length doesn't match its complement"));
+                }
+                let block = bits
+                    .data
+                    .get(start + 4..start + 4 + length)
+                    .ok_or_else(|| error("unexpected end of data"))?;
+                if output.len() + block.len() > limit {
+                    return Err(too_large());
+                }
+                output.extend_from_slice(block);
+                bits.position = start + 4 + length;
+            }
+            1 => {
+                let mut lengths = [0; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths);
+                let distances = Huffman::new(&[5; 30]);
+                inflate_block(&mut bits, &mut output, limit, &literals, &distances)?;
+            }
+            2 => {
+                let (literals, distances) = dynamic_codes(&mut bits)?;
+                inflate_block(&mut bits, &mut output, limit, &literals, &distances)?;
+            }
+            _ => return Err(error("invalid block type")),
+        }
+        if last {
+            return Ok(output);
+        }
+    }
+}
+
+/// Read the Huffman codes of a dynamic block.
+fn dynamic_codes(bits: &mut Bits) -> Result<(Huffman, Huffman), ImportError> {
+    let literal_count = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_count = bits.bits(4)? as usize + 4;
+    let mut code_lengths = [0; 19];
+    for &index in &CODE_LENGTH_ORDER[..code_count] {
+        code_lengths[index] = bits.bits(3)? as u8;
+    }
+    let code = Huffman::new(&code_lengths);
+    let mut lengths = Vec::with_capacity(literal_count + distance_count);
+    while lengths.len() < literal_count + distance_count {
+        let symbol = bits.decode(&code)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (
+                *lengths
+                    .last()
+                    .ok_or_else(|| error("repeated length with no previous length"))?,
+                3 + bits.bits(2)?,
+            ),
+            17 => (0, 3 + bits.bits(3)?),
+            _ => (0, 11 + bits.bits(7)?),
+        };
+        for _ in 0..repeat {
+            lengths.push(value);
+        }
+    }
+    if lengths.len() > literal_count + distance_count {
+        return Err(error("too many code lengths"));
+    }
+    Ok((
+        Huffman::new(&lengths[..literal_count]),
+        Huffman::new(&lengths[literal_count..]),
+    ))
+}
+
+/// Decode the symbols of a compressed block until its end.
+fn inflate_block(
+    bits: &mut Bits,
+    output: &mut Vec<u8>,
+    limit: usize,
+    literals: &Huffman,
+    distances: &Huffman,
+) -> Result<(), ImportError> {
+    loop {
+        let symbol = bits.decode(literals)? as usize;
+        match symbol {
+            0..=255 => {
+                if output.len() == limit {
+                    return Err(too_large());
+                }
+                output.push(symbol as u8)
+            }
+            256 => return Ok(()),
+            257..=285 => {
+                let index = symbol - 257;
+                let length =
+                    LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+                let index = bits.decode(distances)? as usize;
+                if index >= 30 {
+                    return Err(error("invalid distance symbol"));
+                }
+                let distance = DISTANCE_BASE[index] as usize
+                    + bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() {
+                    return Err(error("distance is too far back"));
+                }
+                if output.len() + length > limit {
+                    return Err(too_large());
+                }
+                // copied a byte at a time, since the copy can overlap what it's writing
+                let start = output.len() - distance;
+                for k in 0..length {
+                    output.push(output[start + k]);
+                }
+            }
+            _ => return Err(error("invalid literal/length symbol")),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wrap some bytes in a zlib stream of one stored block.
+    fn stored(data: &[u8]) -> Vec<u8> {
+        let length = data.len() as u16;
+        let mut stream = vec![0x78, 0x01, 0x01];
+        stream.extend_from_slice(&length.to_le_bytes());
+        stream.extend_from_slice(&(!length).to_le_bytes());
+        stream.extend_from_slice(data);
+        stream
+    }
+
+    /// "hello hello hello hello", compressed with a fixed Huffman block.
+    const FIXED: [u8; 16] = [
+        0x78, 0xda, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0x57, 0xc8, 0x40, 0x27, 0x01, 0x68, 0x03, 0x08,
+        0xb1,
+    ];
+
+    /// "ahgjdabekjkdakbheeidbdhddfki", compressed with a dynamic Huffman block.
+    const DYNAMIC: [u8; 35] = [
+        0x78, 0xda, 0x05, 0xc1, 0xc1, 0x11, 0x00, 0x30, 0x08, 0x02, 0xb0, 0x59, 0xe1, 0xa0, 0x45,
+        0xd9, 0xff, 0x6f, 0x82, 0xfc, 0x15, 0xe8, 0x6e, 0x85, 0x32, 0xf6, 0x88, 0x8a, 0xf4, 0x3a,
+        0x07, 0xa1, 0x8b, 0x0b, 0x28,
+    ];
+
+    /// 1000 copies of "a", compressed with a fixed Huffman block.
+    const REPEATED: [u8; 17] = [
+        0x78, 0xda, 0x4b, 0x4c, 0x1c, 0x05, 0xa3, 0x60, 0x14, 0x0c, 0x77, 0x00, 0x00, 0xf9, 0xd8,
+        0x7a, 0xf8,
+    ];
+
+    #[test]
+    fn stored_block() {
+        let data = b"stored, not compressed";
+        assert_eq!(decompress(&stored(data), 100).unwrap(), data);
+    }
+
+    #[test]
+    fn fixed_block() {
+        assert_eq!(decompress(&FIXED, 100).unwrap(), b"hello hello hello hello");
+    }
+
+    #[test]
+    fn dynamic_block() {
+        assert_eq!(
+            decompress(&DYNAMIC, 100).unwrap(),
+            b"ahgjdabekjkdakbheeidbdhddfki"
+        );
+    }
+
+    #[test]
+    fn limit() {
+        assert_eq!(decompress(&REPEATED, 1000).unwrap(), vec![b'a'; 1000]);
+        assert!(decompress(&REPEATED, 999).is_err());
+        assert!(decompress(&stored(b"too long"), 7).is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        for stream in [&stored(b"stored")[..], &FIXED, &DYNAMIC, &REPEATED] {
+            // the last 4 bytes are the checksum, which isn't checked
+            for length in 0..stream.len() - 4 {
+                assert!(decompress(&stream[..length], 1000).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        assert!(decompress(&[0x78, 0x02], 100).is_err());
+        assert!(decompress(&[0x78, 0xbb, 0x01], 100).is_err());
+        // a block type of 3
+        assert!(decompress(&[0x78, 0x01, 0x07], 100).is_err());
+        // a stored block whose length doesn't match its complement
+        assert!(decompress(&[0x78, 0x01, 0x01, 0x01, 0x00, 0x00, 0x00, 0x61], 100).is_err());
+        // every single bit flip decodes (to something) or fails, without panicking
+        for stream in [&FIXED[..], &DYNAMIC, &REPEATED] {
+            for bit in 16..8 * stream.len() {
+                let mut corrupted = stream.to_vec();
+                corrupted[bit / 8] ^= 1 << (bit % 8);
+                let _ = decompress(&corrupted, 1000);
+            }
+        }
+    }
+}
@@ -0,0 +1,48 @@
+//! Importers, which build particles, bodies and colliders out of files made in other tools, and whole systems
+//! out of scene files.
+
+mod bmp;
+pub mod image;
+mod inflate;
+mod png;
+#[cfg(feature = "serde")]
+pub mod scene;
+pub mod svg;
+
+use std::fmt;
+
+/// The most pixels that a decoded image can have (8192 by 8192), so that a small malicious file can't claim to be
+/// large enough to use up all of the memory.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// A decoded image's width, height, and pixels (row by row from the top left).
+type Decoded = (usize, usize, Vec<(u8, u8, u8, u8)>);
+
+/// An error from reading or parsing an imported file.
+#[derive(Debug)]
+pub enum ImportError {
+    /// The file couldn't be read.
+    Io(std::io::Error),
+    /// The file couldn't be parsed, with the (1-based) line that the problem is on.
+    Parse { line: usize, message: String },
+    /// A binary file isn't in a format that can be decoded.
+    Format(String),
+}
+
+impl fmt::Display for ImportError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ImportError::Io(error) => write!(f, "{error}"),
+            ImportError::Parse { line, message } => write!(f, "line {line}: {message}"),
+            ImportError::Format(message) => write!(f, "{message}"),
+        }
+    }
+}
+
+impl std::error::Error for ImportError {}
+
+impl From<std::io::Error> for ImportError {
+    fn from(error: std::io::Error) -> ImportError {
+        ImportError::Io(error)
+    }
+}
@@ -0,0 +1,494 @@
+//! A decoder for PNG images, which supports every color type and bit depth, transparency (`tRNS`), and Adam7
+//! interlacing. Gamma and color profiles are ignored.
+
+use crate::import::inflate;
+use crate::import::{Decoded, ImportError, MAX_PIXELS};
+
+const SIGNATURE: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
+
+/// The pixel offsets and steps of the seven Adam7 passes: (x, y, dx, dy).
+const ADAM7: [(usize, usize, usize, usize); 7] = [
+    (0, 0, 8, 8),
+    (4, 0, 8, 8),
+    (0, 4, 4, 8),
+    (2, 0, 4, 4),
+    (0, 2, 2, 4),
+    (1, 0, 2, 2),
+    (0, 1, 1, 2),
+];
+
+fn error(message: impl Into<String>) -> ImportError {
+    ImportError::Format(format!("invalid PNG: {}", message.into()))
+}
+
+/// Whether some bytes are a PNG image.
+pub(crate) fn is_png(bytes: &[u8]) -> bool {
+    bytes.starts_with(&SIGNATURE)
+}
+
+/// The image's header, and what's needed to turn its samples into colors.
+struct Header {
+    width: usize,
+    height: usize,
+    bit_depth: u8,
+    color_type: u8,
+    palette: Vec<(u8, u8, u8, u8)>,
+    /// the color (as raw samples) that is fully transparent, for grayscale and truecolor images
+    transparent: Option<[u16; 3]>,
+}
+
+impl Header {
+    fn channels(self: &Self) -> usize {
+        match self.color_type {
+            0 | 3 => 1,
+            4 => 2,
+            2 => 3,
+            _ => 4,
+        }
+    }
+
+    /// Bytes per pixel, rounded up, as the filters use it.
+    fn filter_step(self: &Self) -> usize {
+        (self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    fn row_bytes(self: &Self, width: usize) -> usize {
+        (width * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// The width and height of the part of the image in an interlacing pass, if it has any pixels.
+    fn pass_size(
+        self: &Self,
+        (x0, y0, dx, dy): (usize, usize, usize, usize),
+    ) -> Option<(usize, usize)> {
+        // small images leave some passes empty
+        if x0 >= self.width || y0 >= self.height {
+            return None;
+        }
+        Some((
+            (self.width - x0).div_ceil(dx),
+            (self.height - y0).div_ceil(dy),
+        ))
+    }
+
+    /// The colors of a row of unfiltered bytes.
+    fn colors(self: &Self, row: &[u8], width: usize) -> Vec<(u8, u8, u8, u8)> {
+        let depth = self.bit_depth as usize;
+        let sample = |index: usize| -> u16 {
+            match depth {
+                16 => u16::from_be_bytes([row[2 * index], row[2 * index + 1]]),
+                8 => row[index] as u16,
+                _ => {
+                    let bit = index * depth;
+                    ((row[bit / 8] >> (8 - depth - bit % 8)) & ((1 << depth) - 1) as u8) as u16
+                }
+            }
+        };
+        // scale a sample to 8 bits
+        let scale = |value: u16| -> u8 {
+            match depth {
+                16 => (value >> 8) as u8,
+                _ => (value as u32 * 255 / ((1 << depth) - 1)) as u8,
+            }
+        };
+        let channels = self.channels();
+        (0..width)
+            .map(|x| {
+                let samples: Vec<u16> = (0..channels).map(|c| sample(x * channels + c)).collect();
+                match self.color_type {
+                    0 => {
+                        let gray = scale(samples[0]);
+                        let alpha = match self.transparent {
+                            Some([value, ..]) if value == samples[0] => 0,
+                            _ => 255,
+                        };
+                        (gray, gray, gray, alpha)
+                    }
+                    2 => {
+                        let alpha = match self.transparent {
+                            Some(value) if value[..] == samples[..] => 0,
+                            _ => 255,
+                        };
+                        (
+                            scale(samples[0]),
+                            scale(samples[1]),
+                            scale(samples[2]),
+                            alpha,
+                        )
+                    }
+                    3 => self
+                        .palette
+                        .get(samples[0] as usize)
+                        .copied()
+                        .unwrap_or((0, 0, 0, 255)),
+                    4 => {
+                        let gray = scale(samples[0]);
+                        (gray, gray, gray, scale(samples[1]))
+                    }
+                    _ => (
+                        scale(samples[0]),
+                        scale(samples[1]),
+                        scale(samples[2]),
+                        scale(samples[3]),
+                    ),
+                }
+            })
+            .collect()
+    }
+}
+
+/// Decode a PNG image into its width, height, and pixels (row by row from the top left).
+pub(crate) fn decode(bytes: &[u8]) -> Result<Decoded, ImportError> {
+    if !is_png(bytes) {
+        return Err(error("missing signature"));
+    }
+    let mut header: Option<Header> = None;
+    let mut compressed = Vec::new();
+    let mut interlaced = false;
+    let mut position = SIGNATURE.len();
+    loop {
+        let length = bytes
+            .get(position..position + 4)
+            .map(|length| u32::from_be_bytes(length.try_into().unwrap()) as usize)
+            .ok_or_else(|| error("unexpected end of file"))?;
+        let kind = &bytes
+            .get(position + 4..position + 8)
+            .ok_or_else(|| error("unexpected end of file"))?;
+        let data = bytes
+            .get(position + 8..position + 8 + length)
+            .ok_or_else(|| error("unexpected end of file"))?;
+        position += 12 + length;
+        match *kind {
+            b"IHDR" => {
+                if data.len() < 13 {
+                    return Err(error("short IHDR chunk"));
+                }
+                let (bit_depth, color_type) = (data[8], data[9]);
+                let valid = match color_type {
+                    0 => matches!(bit_depth, 1 | 2 | 4 | 8 | 16),
+                    3 => matches!(bit_depth, 1 | 2 | 4 | 8),
+                    2 | 4 | 6 => matches!(bit_depth, 8 | 16),
+                    _ => false,
+                };
+                if !valid {
+                    return Err(error(format!(
+                        "color type {color_type} with bit depth {bit_depth}"
+                    )));
+                }
+                interlaced = data[12] == 1;
+                header = Some(Header {
+                    width: u32::from_be_bytes(data[0..4].try_into().unwrap()) as usize,
+                    height: u32::from_be_bytes(data[4..8].try_into().unwrap()) as usize,
+                    bit_depth,
+                    color_type,
+                    palette: Vec::new(),
+                    transparent: None,
+                });
+            }
+            b"PLTE" => {
+                let header = header.as_mut().ok_or_else(|| error("PLTE before IHDR"))?;
+                header.palette = data
+                    .chunks_exact(3)
+                    .map(|color| (color[0], color[1], color[2], 255))
+                    .collect();
+            }
+            b"tRNS" => {
+                let header = header.as_mut().ok_or_else(|| error("tRNS before IHDR"))?;
+                let sample = |k: usize| {
+                    data.get(2 * k..2 * k + 2)
+                        .map(|value| u16::from_be_bytes([value[0], value[1]]))
+                        .unwrap_or(0)
+                };
+                match header.color_type {
+                    0 => header.transparent = Some([sample(0), 0, 0]),
+                    2 => header.transparent = Some([sample(0), sample(1), sample(2)]),
+                    3 => {
+                        for (color, &alpha) in header.palette.iter_mut().zip(data) {
+                            color.3 = alpha;
+                        }
+                    }
+                    _ => {}
+                }
+            }
+            b"IDAT" => compressed.extend_from_slice(data),
+            b"IEND" => break,
+            _ => {}
+        }
+    }
+    let header = header.ok_or_else(|| error("missing IHDR chunk"))?;
+    let (width, height) = (header.width, header.height);
+    if width == 0 || height == 0 {
+        return Err(error("the image is empty"));
+    }
+    if width.saturating_mul(height) > MAX_PIXELS {
+        return Err(error(format!("{width}x{height} is too large")));
+    }
+    let passes: &[(usize, usize, usize, usize)] = if interlaced { &ADAM7 } else { &[(0, 0, 1, 1)] };
+    // each row of each pass starts with its filter type
+    let expected = passes
+        .iter()
+        .filter_map(|&pass| header.pass_size(pass))
+        .map(|(pass_width, pass_height)| (header.row_bytes(pass_width) + 1) * pass_height)
+        .sum();
+    let data = inflate::decompress(&compressed, expected)?;
+    if data.len() < expected {
+        return Err(error("not enough image data"));
+    }
+
+    let mut pixels = vec![(0, 0, 0, 0); width * height];
+    let mut offset = 0;
+    for &(x0, y0, dx, dy) in passes {
+        let (pass_width, pass_height) = match header.pass_size((x0, y0, dx, dy)) {
+            Some(size) => size,
+            None => continue,
+        };
+        let row_bytes = header.row_bytes(pass_width);
+        let rows = unfilter(
+            data.get(offset..offset + (row_bytes + 1) * pass_height)
+                .ok_or_else(|| error("not enough image data"))?,
+            row_bytes,
+            header.filter_step(),
+        )?;
+        offset += (row_bytes + 1) * pass_height;
+        for (y, row) in rows.chunks_exact(row_bytes).enumerate() {
+            for (x, color) in header.colors(row, pass_width).into_iter().enumerate() {
+                pixels[(y0 + y * dy) * width + x0 + x * dx] = color;
+            }
+        }
+    }
+    Ok((width, height, pixels))
+}
+
+/// Undo the filter on each row (which starts with its filter type), returning the raw rows back to back.
+fn unfilter(data: &[u8], row_bytes: usize, step: usize) -> Result<Vec<u8>, ImportError> {
+    let mut rows = Vec::with_capacity(data.len());
+    let mut previous = vec![0u8; row_bytes];
+    for line in data.chunks_exact(row_bytes + 1) {
+        let (filter, line) = (line[0], &line[1..]);
+        let mut row = line.to_vec();
+        for k in 0..row_bytes {
+            let left = if k >= step { row[k - step] } else { 0 };
+            let up = previous[k];
+            let up_left = if k >= step { previous[k - step] } else { 0 };
+            let prediction = match filter {
+                0 => 0,
+                1 => left,
+                2 => up,
+                3 => ((left as u16 + up as u16) / 2) as u8,
+                4 => paeth(left, up, up_left),
+                _ => return Err(error(format!("unknown filter type {filter}"))),
+            };
+            row[k] = row[k].wrapping_add(prediction);
+        }
+        rows.extend_from_slice(&row);
+        previous = row;
+    }
+    Ok(rows)
+}
+
+/// The Paeth predictor, which picks whichever neighbor is closest to left + up - up_left.
+fn paeth(left: u8, up: u8, up_left: u8) -> u8 {
+    let estimate = left as i16 + up as i16 - up_left as i16;
+    let (a, b, c) = (
+        (estimate - left as i16).abs(),
+        (estimate - up as i16).abs(),
+        (estimate - up_left as i16).abs(),
+    );
+    if a <= b && a <= c {
+        left
+    } else if b <= c {
+        up
+    } else {
+        up_left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A chunk, with a CRC of 0 (which isn't checked).
+    fn chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = (data.len() as u32).to_be_bytes().to_vec();
+        chunk.extend_from_slice(kind);
+        chunk.extend_from_slice(data);
+        chunk.extend_from_slice(&[0; 4]);
+        chunk
+    }
+
+    /// A PNG image, whose scanlines (each starting with its filter type) are stored uncompressed.
+    fn png(
+        (width, height): (u32, u32),
+        bit_depth: u8,
+        color_type: u8,
+        interlaced: bool,
+        chunks: &[Vec<u8>],
+        scanlines: &[u8],
+    ) -> Vec<u8> {
+        let mut header = width.to_be_bytes().to_vec();
+        header.extend_from_slice(&height.to_be_bytes());
+        header.extend_from_slice(&[bit_depth, color_type, 0, 0, interlaced as u8]);
+        let length = scanlines.len() as u16;
+        let mut compressed = vec![0x78, 0x01, 0x01];
+        compressed.extend_from_slice(&length.to_le_bytes());
+        compressed.extend_from_slice(&(!length).to_le_bytes());
+        compressed.extend_from_slice(scanlines);
+
+        let mut bytes = SIGNATURE.to_vec();
+        bytes.extend(chunk(b"IHDR", &header));
+        for extra in chunks {
+            bytes.extend_from_slice(extra);
+        }
+        bytes.extend(chunk(b"IDAT", &compressed));
+        bytes.extend(chunk(b"IEND", &[]));
+        bytes
+    }
+
+    fn gray(values: &[u8]) -> Vec<(u8, u8, u8, u8)> {
+        values
+            .iter()
+            .map(|&value| (value, value, value, 255))
+            .collect()
+    }
+
+    #[test]
+    fn filters() {
+        // the second row is [15, 25, 40] under each filter, after a first row of [10, 20, 30]
+        let second_rows: [[u8; 4]; 5] = [
+            [0, 15, 25, 40],
+            [1, 15, 10, 15],
+            [2, 5, 5, 10],
+            [3, 10, 8, 13],
+            [4, 5, 5, 10],
+        ];
+        for second_row in second_rows {
+            let scanlines = [&[0, 10, 20, 30][..], &second_row].concat();
+            let (width, height, pixels) =
+                decode(&png((3, 2), 8, 0, false, &[], &scanlines)).unwrap();
+            assert_eq!((width, height), (3, 2));
+            assert_eq!(
+                pixels,
+                gray(&[10, 20, 30, 15, 25, 40]),
+                "filter {}",
+                second_row[0]
+            );
+        }
+        assert!(decode(&png((3, 1), 8, 0, false, &[], &[5, 10, 20, 30])).is_err());
+    }
+
+    #[test]
+    fn adam7() {
+        // each pixel of the 3x3 image is its position, counting from 1, spread over the passes that it's in
+        let scanlines = [
+            &[0, 1][..],
+            &[0, 3],
+            &[0, 7, 9],
+            &[0, 2, 0, 8],
+            &[0, 4, 5, 6],
+        ]
+        .concat();
+        let (_, _, pixels) = decode(&png((3, 3), 8, 0, true, &[], &scanlines)).unwrap();
+        assert_eq!(pixels, gray(&[1, 2, 3, 4, 5, 6, 7, 8, 9]));
+    }
+
+    #[test]
+    fn bit_depths() {
+        // 1 bit grayscale, padded to a whole byte
+        let (_, _, pixels) = decode(&png((3, 1), 1, 0, false, &[], &[0, 0b1010_0000])).unwrap();
+        assert_eq!(pixels, gray(&[255, 0, 255]));
+        // 16 bit grayscale and alpha
+        let (_, _, pixels) = decode(&png(
+            (1, 1),
+            16,
+            4,
+            false,
+            &[],
+            &[0, 0x80, 0x00, 0x40, 0x00],
+        ))
+        .unwrap();
+        assert_eq!(pixels, vec![(128, 128, 128, 64)]);
+        // 8 bit truecolor and alpha
+        let (_, _, pixels) = decode(&png((1, 1), 8, 6, false, &[], &[0, 1, 2, 3, 4])).unwrap();
+        assert_eq!(pixels, vec![(1, 2, 3, 4)]);
+    }
+
+    #[test]
+    fn transparency() {
+        // a grayscale color
+        let transparent = chunk(b"tRNS", &[0, 20]);
+        let (_, _, pixels) =
+            decode(&png((2, 1), 8, 0, false, &[transparent], &[0, 10, 20])).unwrap();
+        assert_eq!(pixels, vec![(10, 10, 10, 255), (20, 20, 20, 0)]);
+        // a truecolor color
+        let transparent = chunk(b"tRNS", &[0, 1, 0, 2, 0, 3]);
+        let (_, _, pixels) = decode(&png(
+            (2, 1),
+            8,
+            2,
+            false,
+            &[transparent],
+            &[0, 1, 2, 3, 1, 2, 4],
+        ))
+        .unwrap();
+        assert_eq!(pixels, vec![(1, 2, 3, 0), (1, 2, 4, 255)]);
+        // the alpha of each palette entry, where entries after the tRNS chunk's are opaque
+        let palette = chunk(b"PLTE", &[255, 0, 0, 0, 255, 0, 0, 0, 255]);
+        let transparent = chunk(b"tRNS", &[0, 128]);
+        let (_, _, pixels) = decode(&png(
+            (3, 1),
+            2,
+            3,
+            false,
+            &[palette, transparent],
+            &[0, 0b0001_1000],
+        ))
+        .unwrap();
+        assert_eq!(
+            pixels,
+            vec![(255, 0, 0, 0), (0, 255, 0, 128), (0, 0, 255, 255)]
+        );
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = png((3, 2), 8, 0, false, &[], &[0, 10, 20, 30, 1, 15, 10, 15]);
+        assert!(decode(&bytes).is_ok());
+        // cut anywhere before the IEND chunk's type
+        for length in 0..bytes.len() - 4 {
+            assert!(decode(&bytes[..length]).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        // too little image data
+        assert!(decode(&png((3, 2), 8, 0, false, &[], &[0, 10, 20, 30])).is_err());
+        // an invalid bit depth
+        assert!(decode(&png((1, 1), 3, 0, false, &[], &[0, 0])).is_err());
+        // empty
+        assert!(decode(&png((0, 1), 8, 0, false, &[], &[])).is_err());
+        // too large to fit in memory, which is found before anything is allocated
+        assert!(decode(&png((1 << 20, 1 << 20), 8, 6, false, &[], &[0])).is_err());
+        assert!(decode(&png((u32::MAX, u32::MAX), 16, 6, true, &[], &[0])).is_err());
+        // a chunk whose length runs past the end of the file
+        let mut bytes = png((1, 1), 8, 0, false, &[], &[0, 0]);
+        bytes[8..12].copy_from_slice(&u32::MAX.to_be_bytes());
+        assert!(decode(&bytes).is_err());
+        // every single byte change decodes (to something) or fails, without panicking
+        let bytes = png(
+            (3, 3),
+            8,
+            0,
+            true,
+            &[],
+            &[0, 1, 0, 3, 0, 7, 9, 0, 2, 0, 8, 0, 4, 5, 6],
+        );
+        for index in 0..bytes.len() {
+            for value in [0, 1, 2, 7, 8, 0x80, 0xff] {
+                let mut corrupted = bytes.clone();
+                corrupted[index] = value;
+                let _ = decode(&corrupted);
+            }
+        }
+    }
+}
@@ -0,0 +1,751 @@
+//! Provides a declarative scene format, which describes a whole simulation in a TOML or RON file (with the `serde`
+//! feature), so that it can be changed without recompiling.
+//!
+//! A scene lists its particles (one by one, or made by the procedural generators), its built-in forces and
+//! constraints, the boundary, the solver settings, and the view. Particles are numbered in the order that they're
+//! made: first the `particles`, then the particles of each generator in turn. Constraints refer to particles by
+//! these numbers.
+//!
+//! Mistakes in the file are reported with the line that they're on, including the ones that are only found when the
+//! scene is built (like a constraint on a particle that doesn't exist), as long as the file is TOML.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::import::scene::Scene;
+//!
+//! let scene = Scene::from_toml(r#"
+//!     [solver]
+//!     substeps = 4
+//!     restitution = 0.5
+//!
+//!     [boundary]
+//!     kind = "periodic"
+//!     min = [-50, -50]
+//!     max = [50, 50]
+//!
+//!     [view]
+//!     zoom = 4.0
+//!
+//!     [[particles]]
+//!     pos = [0, 20]
+//!     mass = inf
+//!
+//!     [[generators]]
+//!     kind = "rope"
+//!     particle = { radius = 1.0, mass = 1.0 }
+//!     start = [1, 20]
+//!     end = [20, 20]
+//!     count = 10
+//!     links = 0.0
+//!
+//!     [[constraints]]
+//!     kind = "distance"
+//!     particles = [0, 1]
+//!
+//!     [[forces]]
+//!     kind = "gravity"
+//!     acceleration = [0, -9.8]
+//! "#).unwrap();
+//!
+//! let (sim, view) = scene.build().unwrap();
+//! assert_eq!(sim.particles.len(), 11);
+//! assert_eq!(sim.constraints.len(), 10);
+//! assert_eq!(view.zoom, 4.0);
+//!
+//! let error = Scene::from_toml("[solver]\nsubsteps = 4\nintegrator = \"rk4\"\n").err().unwrap();
+//! assert!(error.to_string().starts_with("line 3:"));
+//!
+//! let scene = Scene::from_toml("[[constraints]]\nkind = \"distance\"\nparticles = [0, 1]\n").unwrap();
+//! assert!(scene.build().err().unwrap().to_string().starts_with("line 3:"));
+//! ```
+
+use crate::generators;
+use crate::import::ImportError;
+use crate::physics::boundary::Boundary;
+use crate::physics::collision::Collisions;
+use crate::physics::constraint::Constraint;
+use crate::physics::force::Force;
+use crate::physics::pair_table::PairTable;
+use crate::physics::particle::Particle;
+use crate::physics::potential::Potential;
+use crate::physics::system::System;
+use crate::rendering::View2D;
+use crate::vec2::Vec2;
+
+use serde::Deserialize;
+use std::ops::Range;
+use std::path::Path;
+
+/// A whole simulation, as it's described in a scene file.
+#[derive(Deserialize, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scene {
+    pub solver: Solver,
+    pub boundary: SceneBoundary,
+    pub view: SceneView,
+    pub particles: Vec<SceneParticle>,
+    pub generators: Vec<Generator>,
+    pub forces: Vec<SceneForce>,
+    pub constraints: Vec<SceneConstraint>,
+    /// where the parts of the scene are in the file that it was parsed from
+    #[serde(skip)]
+    pub lines: SceneLines,
+}
+
+/// The lines (counted from 1) that parts of a scene are on in the file that it was parsed from, so that mistakes
+/// found while building the scene can be reported with their line too. A line of 0 is unknown, which it always is
+/// for RON files and for scenes that weren't parsed.
+#[derive(Default, Clone)]
+pub struct SceneLines {
+    /// the line of the boundary's `min`
+    pub boundary: usize,
+    /// the line of each constraint's `particles`
+    pub constraints: Vec<usize>,
+}
+
+/// The spans of the parts of a TOML scene that `SceneLines` needs.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+struct TomlSpans {
+    boundary: Option<toml::Spanned<toml::Table>>,
+    constraints: Vec<toml::Spanned<toml::Table>>,
+}
+
+/// Settings of the solver.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Solver {
+    pub substeps: u32,
+    pub integrator: Integrator,
+    /// coefficient of restitution of collisions between particles, if particles should collide
+    pub restitution: Option<f64>,
+}
+
+impl Default for Solver {
+    fn default() -> Solver {
+        Solver {
+            substeps: 1,
+            integrator: Integrator::default(),
+            restitution: None,
+        }
+    }
+}
+
+/// The integrator that moves particles between constraint projections.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// Semi-implicit (symplectic) Euler, which is currently the only integrator of the engine.
+    #[default]
+    SymplecticEuler,
+}
+
+/// The boundary conditions of the scene.
+#[derive(Deserialize, Default)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SceneBoundary {
+    #[default]
+    Open,
+    Periodic {
+        min: [f64; 2],
+        max: [f64; 2],
+    },
+}
+
+/// Where the scene is initially viewed from.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneView {
+    pub offset: [f64; 2],
+    pub zoom: f64,
+}
+
+impl Default for SceneView {
+    fn default() -> SceneView {
+        let view = View2D::new();
+        SceneView {
+            offset: [view.view_offset.x, view.view_offset.y],
+            zoom: view.zoom,
+        }
+    }
+}
+
+/// A particle, where every property that's left out is the same as `Particle::new`.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SceneParticle {
+    pub pos: [f64; 2],
+    pub vel: [f64; 2],
+    pub mass: f64,
+    pub radius: f64,
+    pub color: [u8; 4],
+    pub group: u32,
+    pub charge: f64,
+    pub temperature: f64,
+    pub specific_heat: f64,
+}
+
+impl Default for SceneParticle {
+    fn default() -> SceneParticle {
+        let particle = Particle::new();
+        let (r, g, b, a) = particle.color;
+        SceneParticle {
+            pos: [particle.pos.x, particle.pos.y],
+            vel: [particle.vel.x, particle.vel.y],
+            mass: particle.mass,
+            radius: particle.radius,
+            color: [r, g, b, a],
+            group: particle.group,
+            charge: particle.charge,
+            temperature: particle.temperature,
+            specific_heat: particle.specific_heat,
+        }
+    }
+}
+
+impl SceneParticle {
+    fn particle(self: &Self) -> Particle {
+        let [r, g, b, a] = self.color;
+        let mut particle = Particle::new()
+            .pos(self.pos[0], self.pos[1])
+            .vel(self.vel[0], self.vel[1])
+            .mass(self.mass)
+            .radius(self.radius)
+            .color((r, g, b, a))
+            .group(self.group);
+        particle.charge = self.charge;
+        particle.temperature = self.temperature;
+        particle.specific_heat = self.specific_heat;
+        particle
+    }
+}
+
+/// A procedural generator (see `generators`), which copies its particle template to every position it makes.
+///
+/// Ropes, chains and rings can be held together by distance constraints of some compliance (`links`).
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Generator {
+    SquareLattice {
+        #[serde(default)]
+        particle: SceneParticle,
+        #[serde(default)]
+        origin: [f64; 2],
+        columns: usize,
+        rows: usize,
+        spacing: f64,
+    },
+    HexagonalLattice {
+        #[serde(default)]
+        particle: SceneParticle,
+        #[serde(default)]
+        origin: [f64; 2],
+        columns: usize,
+        rows: usize,
+        spacing: f64,
+    },
+    Gas {
+        #[serde(default)]
+        particle: SceneParticle,
+        count: usize,
+        min: [f64; 2],
+        max: [f64; 2],
+        temperature: f64,
+        #[serde(default)]
+        seed: u64,
+    },
+    Rope {
+        #[serde(default)]
+        particle: SceneParticle,
+        start: [f64; 2],
+        end: [f64; 2],
+        count: usize,
+        links: Option<f64>,
+    },
+    Chain {
+        #[serde(default)]
+        particle: SceneParticle,
+        start: [f64; 2],
+        end: [f64; 2],
+        links: Option<f64>,
+    },
+    Ring {
+        #[serde(default)]
+        particle: SceneParticle,
+        center: [f64; 2],
+        radius: f64,
+        count: usize,
+        links: Option<f64>,
+    },
+}
+
+/// A built-in force.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SceneForce {
+    /// A uniform gravitational acceleration, given to every particle with a finite mass as a constant force.
+    Gravity { acceleration: [f64; 2] },
+    /// See `Force::PairPotential`, where a missing cutoff means no cutoff.
+    PairPotential {
+        potentials: Vec<ScenePotential>,
+        cutoff: Option<f64>,
+    },
+    /// See `Force::Ewald`.
+    Ewald { constant: f64, accuracy: f64 },
+    /// See `Force::Wcsph`.
+    Wcsph {
+        groups: Vec<u32>,
+        rest_density: f64,
+        kernel_radius: f64,
+        stiffness: f64,
+        exponent: i32,
+        viscosity: f64,
+    },
+    /// See `Force::SurfaceTension`, where the strengths are listed as (group a, group b, strength).
+    SurfaceTension {
+        kernel_radius: f64,
+        rest_density: f64,
+        #[serde(default)]
+        cohesion: Vec<(u32, u32, f64)>,
+        #[serde(default)]
+        adhesion: Vec<(u32, u32, f64)>,
+    },
+}
+
+/// The pair potential between two groups of particles.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ScenePotential {
+    LennardJones {
+        groups: [u32; 2],
+        epsilon: f64,
+        sigma: f64,
+    },
+    Morse {
+        groups: [u32; 2],
+        depth: f64,
+        width: f64,
+        equilibrium: f64,
+    },
+    Coulomb {
+        groups: [u32; 2],
+        constant: f64,
+    },
+}
+
+/// A built-in constraint between particles, which are referred to by the order that they're made in.
+#[derive(Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum SceneConstraint {
+    /// Keeps two particles apart at a length, which is their initial distance if it's left out.
+    Distance {
+        particles: [usize; 2],
+        length: Option<f64>,
+        #[serde(default)]
+        compliance: f64,
+    },
+    /// Keeps a triangle of particles at its initial area.
+    TriangleArea {
+        particles: [usize; 3],
+        #[serde(default)]
+        compliance: f64,
+    },
+    /// Holds a cluster of particles in its initial shape, as a rigid body.
+    RigidBody {
+        particles: Vec<usize>,
+        stiffness: f64,
+    },
+}
+
+impl Scene {
+    /// Parse a scene written in TOML.
+    pub fn from_toml(text: &str) -> Result<Scene, ImportError> {
+        let mut scene: Scene = toml::from_str(text).map_err(|error| ImportError::Parse {
+            line: error
+                .span()
+                .map_or(0, |span| toml_line(text, span, error.message())),
+            message: error.message().to_string(),
+        })?;
+        // the text is already known to be valid, so this only fails if something is very wrong
+        let spans: TomlSpans = toml::from_str(text).unwrap_or_default();
+        scene.lines = SceneLines {
+            boundary: spans
+                .boundary
+                .map_or(0, |boundary| field_line(text, boundary.span(), "min")),
+            constraints: spans
+                .constraints
+                .iter()
+                .map(|constraint| field_line(text, constraint.span(), "particles"))
+                .collect(),
+        };
+        Ok(scene)
+    }
+
+    /// Parse a scene written in RON.
+    pub fn from_ron(text: &str) -> Result<Scene, ImportError> {
+        ron::from_str(text).map_err(|error| ImportError::Parse {
+            line: error.position.line,
+            message: error.code.to_string(),
+        })
+    }
+
+    /// Read and parse a scene file, in the format given by its extension (.toml or .ron).
+    pub fn load(path: impl AsRef<Path>) -> Result<Scene, ImportError> {
+        let path = path.as_ref();
+        let extension = path.extension().and_then(|extension| extension.to_str());
+        match extension
+            .map(|extension| extension.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("toml") => Scene::from_toml(&std::fs::read_to_string(path)?),
+            Some("ron") => Scene::from_ron(&std::fs::read_to_string(path)?),
+            _ => Err(ImportError::Format(format!(
+                "unknown scene format for {} (expected .toml or .ron)",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Build the system that the scene describes, and the view of it.
+    pub fn build(self: &Self) -> Result<(System, View2D), ImportError> {
+        let mut system = System::new();
+        system.substeps = self.solver.substeps.max(1);
+        system.collisions = self.solver.restitution.map(Collisions::new);
+        system.boundary = match self.boundary {
+            SceneBoundary::Open => Boundary::Open,
+            SceneBoundary::Periodic { min, max } => {
+                if min[0] >= max[0] || min[1] >= max[1] {
+                    return Err(build_error(
+                        self.lines.boundary,
+                        "a periodic boundary's min needs to be below and left of its max"
+                            .to_string(),
+                    ));
+                }
+                Boundary::Periodic {
+                    min: vec2(min),
+                    max: vec2(max),
+                }
+            }
+        };
+
+        for particle in &self.particles {
+            system.add_particle(particle.particle());
+        }
+        for generator in &self.generators {
+            generator.build(&mut system);
+        }
+
+        for (index, constraint) in self.constraints.iter().enumerate() {
+            let count = system.particles.len();
+            let particles: &[usize] = match constraint {
+                SceneConstraint::Distance { particles, .. } => particles,
+                SceneConstraint::TriangleArea { particles, .. } => particles,
+                SceneConstraint::RigidBody { particles, .. } => particles,
+            };
+            let line = self.lines.constraints.get(index).copied().unwrap_or(0);
+            if particles.is_empty() {
+                return Err(build_error(
+                    line,
+                    format!("constraint {index} doesn't refer to any particles"),
+                ));
+            }
+            if let Some(missing) = particles.iter().find(|&&particle| particle >= count) {
+                return Err(build_error(
+                    line,
+                    format!(
+                        "constraint {index} refers to particle {missing}, but the scene only has {count} particles"
+                    ),
+                ));
+            }
+            if let Some(repeated) = particles
+                .iter()
+                .enumerate()
+                .find_map(|(k, particle)| particles[..k].contains(particle).then_some(particle))
+            {
+                return Err(build_error(
+                    line,
+                    format!("constraint {index} refers to particle {repeated} more than once"),
+                ));
+            }
+            match constraint {
+                SceneConstraint::Distance {
+                    particles: [a, b],
+                    length,
+                    compliance,
+                } => {
+                    let mut distance = Constraint::distance(
+                        *a,
+                        *b,
+                        &system.particles,
+                        &system.boundary,
+                        *compliance,
+                    );
+                    if let (Constraint::Distance { length: rest, .. }, Some(length)) =
+                        (&mut distance, length)
+                    {
+                        *rest = *length;
+                    }
+                    system.add_constraint(distance);
+                }
+                SceneConstraint::TriangleArea {
+                    particles,
+                    compliance,
+                } => {
+                    let triangle = Constraint::triangle_area(
+                        *particles,
+                        &system.particles,
+                        &system.boundary,
+                        *compliance,
+                    );
+                    system.add_constraint(triangle);
+                }
+                SceneConstraint::RigidBody {
+                    particles,
+                    stiffness,
+                } => {
+                    system.add_rigid_body(particles.clone(), *stiffness);
+                }
+            }
+        }
+
+        for force in &self.forces {
+            match force {
+                SceneForce::Gravity { acceleration } => {
+                    for particle in &mut system.particles {
+                        if particle.mass.is_finite() {
+                            let weight = vec2(*acceleration) * particle.mass;
+                            particle.forces.push(weight);
+                        }
+                    }
+                }
+                SceneForce::PairPotential { potentials, cutoff } => {
+                    let potentials =
+                        potentials
+                            .iter()
+                            .fold(PairTable::new(), |table, potential| {
+                                let (groups, potential) = potential.potential();
+                                table.with(groups[0], groups[1], potential)
+                            });
+                    system.forces.push(Force::PairPotential {
+                        potentials,
+                        cutoff: cutoff.unwrap_or(f64::INFINITY),
+                    });
+                }
+                SceneForce::Ewald { constant, accuracy } => system.forces.push(Force::Ewald {
+                    constant: *constant,
+                    accuracy: *accuracy,
+                }),
+                SceneForce::Wcsph {
+                    groups,
+                    rest_density,
+                    kernel_radius,
+                    stiffness,
+                    exponent,
+                    viscosity,
+                } => system.forces.push(Force::Wcsph {
+                    groups: groups.clone(),
+                    rest_density: *rest_density,
+                    kernel_radius: *kernel_radius,
+                    stiffness: *stiffness,
+                    exponent: *exponent,
+                    viscosity: *viscosity,
+                }),
+                SceneForce::SurfaceTension {
+                    kernel_radius,
+                    rest_density,
+                    cohesion,
+                    adhesion,
+                } => system.forces.push(Force::SurfaceTension {
+                    kernel_radius: *kernel_radius,
+                    rest_density: *rest_density,
+                    cohesion: pair_table(cohesion),
+                    adhesion: pair_table(adhesion),
+                }),
+            }
+        }
+
+        let mut view = View2D::new();
+        view.view_offset = vec2(self.view.offset);
+        view.zoom = self.view.zoom;
+        Ok((system, view))
+    }
+}
+
+impl Generator {
+    /// Add the generator's particles (and links) to a system.
+    fn build(self: &Self, system: &mut System) {
+        let (particles, links, closed) = match self {
+            Generator::SquareLattice {
+                particle,
+                origin,
+                columns,
+                rows,
+                spacing,
+            } => (
+                generators::square_lattice(
+                    &particle.particle(),
+                    vec2(*origin),
+                    *columns,
+                    *rows,
+                    *spacing,
+                ),
+                None,
+                false,
+            ),
+            Generator::HexagonalLattice {
+                particle,
+                origin,
+                columns,
+                rows,
+                spacing,
+            } => (
+                generators::hexagonal_lattice(
+                    &particle.particle(),
+                    vec2(*origin),
+                    *columns,
+                    *rows,
+                    *spacing,
+                ),
+                None,
+                false,
+            ),
+            Generator::Gas {
+                particle,
+                count,
+                min,
+                max,
+                temperature,
+                seed,
+            } => (
+                generators::gas(
+                    &particle.particle(),
+                    *count,
+                    vec2(*min),
+                    vec2(*max),
+                    *temperature,
+                    *seed,
+                ),
+                None,
+                false,
+            ),
+            Generator::Rope {
+                particle,
+                start,
+                end,
+                count,
+                links,
+            } => (
+                generators::rope(&particle.particle(), vec2(*start), vec2(*end), *count),
+                *links,
+                false,
+            ),
+            Generator::Chain {
+                particle,
+                start,
+                end,
+                links,
+            } => (
+                generators::chain(&particle.particle(), vec2(*start), vec2(*end)),
+                *links,
+                false,
+            ),
+            Generator::Ring {
+                particle,
+                center,
+                radius,
+                count,
+                links,
+            } => (
+                generators::ring(&particle.particle(), vec2(*center), *radius, *count),
+                *links,
+                true,
+            ),
+        };
+        let indices = system.add_particles(particles);
+        if let Some(compliance) = links {
+            system.add_links(&indices, closed, compliance);
+        }
+    }
+}
+
+impl ScenePotential {
+    /// The pair of groups, and the potential between them.
+    fn potential(self: &Self) -> ([u32; 2], Potential) {
+        match *self {
+            ScenePotential::LennardJones {
+                groups,
+                epsilon,
+                sigma,
+            } => (groups, Potential::LennardJones { epsilon, sigma }),
+            ScenePotential::Morse {
+                groups,
+                depth,
+                width,
+                equilibrium,
+            } => (
+                groups,
+                Potential::Morse {
+                    depth,
+                    width,
+                    equilibrium,
+                },
+            ),
+            ScenePotential::Coulomb { groups, constant } => {
+                (groups, Potential::Coulomb { constant })
+            }
+        }
+    }
+}
+
+/// The line of a TOML error. Errors inside of a tagged entry (a generator, force, etc) only span the whole entry,
+/// so when the error names a field, the line of that field is found within the entry.
+fn toml_line(text: &str, span: Range<usize>, message: &str) -> usize {
+    let field = message
+        .strip_prefix("unknown field `")
+        .or_else(|| message.strip_prefix("duplicate field `"))
+        .and_then(|rest| rest.split('`').next());
+    match field {
+        Some(field) => field_line(text, span, field),
+        None => line(text, span.start),
+    }
+}
+
+/// The line of a key inside of a span of TOML, or of the start of the span if the key isn't on a line of its own.
+fn field_line(text: &str, span: Range<usize>, field: &str) -> usize {
+    let mut offset = span.start;
+    for entry_line in text[span.clone()].split_inclusive('\n') {
+        let key = entry_line.trim_start().strip_prefix(field);
+        if key.is_some_and(|rest| rest.trim_start().starts_with('=')) {
+            return line(text, offset);
+        }
+        offset += entry_line.len();
+    }
+    line(text, span.start)
+}
+
+/// The line (counted from 1) of an offset into some text.
+fn line(text: &str, offset: usize) -> usize {
+    text[..offset].matches('\n').count() + 1
+}
+
+/// A mistake found while building a scene, with its line if it's known.
+fn build_error(line: usize, message: String) -> ImportError {
+    if line == 0 {
+        ImportError::Format(message)
+    } else {
+        ImportError::Parse { line, message }
+    }
+}
+
+fn vec2([x, y]: [f64; 2]) -> Vec2 {
+    Vec2::new(x, y)
+}
+
+fn pair_table(entries: &[(u32, u32, f64)]) -> PairTable<f64> {
+    entries
+        .iter()
+        .fold(PairTable::new(), |table, &(a, b, value)| {
+            table.with(a, b, value)
+        })
+}
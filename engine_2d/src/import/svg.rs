@@ -0,0 +1,1217 @@
+//! Provides an SVG importer, which turns the shapes of a vector drawing into particle bodies and static colliders.
+//!
+//! Paths, rects, circles, ellipses, polygons, polylines and lines are read, along with the transforms of their
+//! groups. Curves and arcs are flattened into line segments. Every closed subpath of a path is its own shape
+//! (holes aren't cut out of the shapes around them). Shapes that aren't drawn by themselves, inside of `<defs>`,
+//! `<clipPath>`, `<mask>`, `<symbol>` and `<marker>`, are left out.
+//!
+//! Each shape becomes one of:
+//!
+//! - A body: filled shapes are meshed into a hexagonal lattice of particles, and become soft bodies (or rigid
+//!   bodies). The fill color becomes `Particle::color`.
+//! - A static collider: shapes without a fill, and shapes in a static layer (or with a static id), become static
+//!   colliders. Filled shapes become solid circles and polygons, and unfilled shapes become the segments of their
+//!   outline (ex: an unfilled rect is a container that particles can sit inside of).
+//!
+//! Each shape's layer or id becomes `Particle::group` (and `StaticCollider::group`), see `Grouping`.
+//!
+//! SVG's y axis points down, so it's flipped to point up, and a scale and origin map the drawing's units onto the
+//! simulation's.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::import::svg::Svg;
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! let svg = Svg::parse(
+//!     r##"<svg xmlns="http://www.w3.org/2000/svg">
+//!         <g id="static"><rect x="0" y="90" width="200" height="10" fill="#444"/></g>
+//!         <g id="bodies"><circle cx="100" cy="40" r="20" fill="#dc143c"/></g>
+//!     </svg>"##,
+//! )
+//! .unwrap()
+//! .spacing(5.0)
+//! .static_layer("static");
+//!
+//! let mut sim = System::new();
+//! let scene = sim.add_svg(&svg);
+//! assert_eq!(scene.colliders.len(), 1);
+//! assert_eq!(scene.soft_bodies.len(), 1);
+//! assert_eq!(sim.particles[0].color, (220, 20, 60, 255));
+//! assert_eq!(sim.particles[0].group, 2);
+//! ```
+
+use crate::import::ImportError;
+use crate::physics::collision::{Shape, StaticCollider};
+use crate::physics::mesh::{Meshing, PolygonMesh};
+use crate::physics::particle::Particle;
+use crate::physics::system::System;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+use std::f64::consts::PI;
+use std::path::Path;
+
+/// Number of line segments that each curve of a path is flattened into.
+const CURVE_SEGMENTS: usize = 16;
+
+/// Elements whose contents are only drawn when something else refers to them, so their shapes aren't imported.
+const NOT_DRAWN: [&str; 5] = ["defs", "clipPath", "mask", "symbol", "marker"];
+
+/// How each shape's group is chosen.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Grouping {
+    /// The shape's layer (a group directly inside of the `<svg>`), numbered from 1 in the order they appear. Shapes
+    /// outside of every layer are group 0.
+    Layer,
+    /// The number at the end of the shape's id, or of its closest ancestor with an id (ex: "ball3" is group 3).
+    /// Shapes without a number are group 0.
+    Id,
+}
+
+/// A shape read from an SVG, in the drawing's coordinates.
+#[derive(Clone)]
+pub struct SvgShape {
+    /// the outline's points (a circle's outline is left empty)
+    pub outline: Vec<Vec2>,
+    /// whether the outline is closed
+    pub closed: bool,
+    /// the center and radius, if the shape is a circle (that wasn't skewed or stretched)
+    pub circle: Option<(Vec2, f64)>,
+    /// the fill color, or None if the shape isn't filled
+    pub fill: Option<(u8, u8, u8, u8)>,
+    /// the shape's own id, or that of its closest ancestor with an id
+    pub id: Option<String>,
+    /// the shape's layer (numbered from 1, or 0 if it isn't in a layer)
+    pub layer: usize,
+    /// the layer's label (`inkscape:label`) or id
+    pub layer_name: Option<String>,
+}
+
+/// Handles to everything that was built from an SVG.
+#[derive(Default)]
+pub struct SvgScene {
+    /// indices in `System::soft_bodies`
+    pub soft_bodies: Vec<usize>,
+    /// indices in `System::rigid_bodies`
+    pub rigid_bodies: Vec<usize>,
+    /// indices in `System::colliders`
+    pub colliders: Vec<usize>,
+}
+
+/// A parsed SVG drawing, and how its shapes should be built.
+pub struct Svg {
+    pub shapes: Vec<SvgShape>,
+    /// simulation units per SVG unit
+    pub scale: f64,
+    /// where the drawing's (0, 0) is placed in the simulation
+    pub origin: Vec2,
+    /// spacing of the particles that bodies are meshed into (in simulation units)
+    pub spacing: f64,
+    /// mass of each particle
+    pub particle_mass: f64,
+    /// compliance of the bodies' edge constraints
+    pub edge_compliance: f64,
+    /// compliance of the bodies' area constraints
+    pub area_compliance: f64,
+    /// stiffness of the bodies, if they should be rigid instead of soft
+    pub rigid: Option<f64>,
+    pub grouping: Grouping,
+    /// layer labels, layer ids, or shape ids whose shapes become static colliders
+    pub static_layers: Vec<String>,
+}
+
+impl Svg {
+    /// Parse the text of an SVG file.
+    pub fn parse(text: &str) -> Result<Svg, ImportError> {
+        Ok(Svg {
+            shapes: Parser::new(text).shapes()?,
+            scale: 1.0,
+            origin: Vec2::zero(),
+            spacing: 10.0,
+            particle_mass: Particle::new().mass,
+            edge_compliance: 0.0,
+            area_compliance: f64::INFINITY,
+            rigid: None,
+            grouping: Grouping::Layer,
+            static_layers: Vec::new(),
+        })
+    }
+
+    /// Read and parse an SVG file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Svg, ImportError> {
+        Svg::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// A builder method to give the drawing a specific scale after creating it.
+    pub fn scale(mut self: Self, scale: f64) -> Svg {
+        self.scale = scale;
+        self
+    }
+
+    /// A builder method to place the drawing's (0, 0) somewhere specific after creating it.
+    pub fn origin(mut self: Self, x: f64, y: f64) -> Svg {
+        self.origin = Vec2::new(x, y);
+        self
+    }
+
+    /// A builder method to give the bodies' particles a specific spacing after creating it.
+    ///
+    /// Panics if the spacing isn't positive and finite.
+    pub fn spacing(mut self: Self, spacing: f64) -> Svg {
+        assert!(
+            spacing > 0.0 && spacing.is_finite(),
+            "the spacing of an svg's bodies must be positive and finite, not {spacing}"
+        );
+        self.spacing = spacing;
+        self
+    }
+
+    /// A builder method to give each of the bodies' particles a specific mass after creating it.
+    pub fn particle_mass(mut self: Self, mass: f64) -> Svg {
+        self.particle_mass = mass;
+        self
+    }
+
+    /// A builder method to give the bodies' edge and area constraints specific compliances after creating it.
+    pub fn compliance(mut self: Self, edge: f64, area: f64) -> Svg {
+        self.edge_compliance = edge;
+        self.area_compliance = area;
+        self
+    }
+
+    /// A builder method to make the bodies rigid, with some stiffness, after creating it.
+    pub fn rigid(mut self: Self, stiffness: f64) -> Svg {
+        self.rigid = Some(stiffness);
+        self
+    }
+
+    /// A builder method to choose how groups are assigned after creating it.
+    pub fn grouping(mut self: Self, grouping: Grouping) -> Svg {
+        self.grouping = grouping;
+        self
+    }
+
+    /// A builder method to make the shapes of a layer (by label or id), or a shape (by id), static colliders after
+    /// creating it.
+    pub fn static_layer(mut self: Self, name: &str) -> Svg {
+        self.static_layers.push(name.to_string());
+        self
+    }
+
+    /// The group of a shape.
+    pub fn group(self: &Self, shape: &SvgShape) -> u32 {
+        match self.grouping {
+            Grouping::Layer => shape.layer as u32,
+            Grouping::Id => shape
+                .id
+                .as_deref()
+                .map(|id| {
+                    let digits = id.len() - id.trim_end_matches(|c: char| c.is_ascii_digit()).len();
+                    id[id.len() - digits..].parse().unwrap_or(0)
+                })
+                .unwrap_or(0),
+        }
+    }
+
+    /// Whether a shape becomes a static collider instead of a body.
+    pub fn is_static(self: &Self, shape: &SvgShape) -> bool {
+        let named = |name: &Option<String>| {
+            name.as_ref()
+                .is_some_and(|name| self.static_layers.contains(name))
+        };
+        shape.fill.is_none() || named(&shape.layer_name) || named(&shape.id)
+    }
+
+    /// Map a point of the drawing onto the simulation.
+    fn map(self: &Self, point: Vec2) -> Vec2 {
+        self.origin + Vec2::new(point.x, -point.y) * self.scale
+    }
+
+    /// Build every shape into a system.
+    pub(crate) fn build(self: &Self, system: &mut System) -> SvgScene {
+        assert!(
+            self.spacing > 0.0 && self.spacing.is_finite(),
+            "the spacing of an svg's bodies must be positive and finite, not {}",
+            self.spacing
+        );
+        let mut scene = SvgScene::default();
+        for shape in &self.shapes {
+            let group = self.group(shape);
+            // a circle's outline, for when it has to be a polygon
+            let outline: Vec<Vec2> = match shape.circle {
+                Some((center, radius)) => {
+                    let count =
+                        ((2.0 * PI * radius * self.scale / self.spacing).ceil() as usize).max(8);
+                    (0..count)
+                        .map(|k| {
+                            center + Vec2::new_polar(radius, 2.0 * PI * k as f64 / count as f64)
+                        })
+                        .collect()
+                }
+                None => shape.outline.clone(),
+            }
+            .into_iter()
+            .map(|point| self.map(point))
+            .collect();
+
+            if self.is_static(shape) {
+                let filled = shape.fill.is_some();
+                let colliders: Vec<Shape> = match shape.circle {
+                    Some((center, radius)) if filled => vec![Shape::Circle {
+                        center: self.map(center),
+                        radius: radius * self.scale,
+                    }],
+                    _ if filled && outline.len() >= 3 => vec![Shape::Polygon { vertices: outline }],
+                    _ => {
+                        let count = outline.len();
+                        let edges = if shape.closed || shape.circle.is_some() {
+                            count
+                        } else {
+                            count.saturating_sub(1)
+                        };
+                        (0..edges)
+                            .map(|k| Shape::Segment {
+                                start: outline[k],
+                                end: outline[(k + 1) % count],
+                            })
+                            .collect()
+                    }
+                };
+                for collider in colliders {
+                    scene
+                        .colliders
+                        .push(system.add_collider(StaticCollider::new(collider).group(group)));
+                }
+            } else if outline.len() >= 3 {
+                let mesh = PolygonMesh::new(
+                    outline,
+                    Meshing::Hexagonal {
+                        spacing: self.spacing,
+                    },
+                )
+                .particle_mass(self.particle_mass)
+                .color(shape.fill.unwrap_or(Particle::new().color))
+                .group(group)
+                .compliance(self.edge_compliance, self.area_compliance);
+                match self.rigid {
+                    Some(stiffness) => scene
+                        .rigid_bodies
+                        .push(system.add_polygon_rigid_body(mesh, stiffness)),
+                    None => scene.soft_bodies.push(system.add_polygon_soft_body(mesh)),
+                }
+            }
+        }
+        scene
+    }
+}
+
+/// An affine transform [a, b, c, d, e, f], which maps (x, y) to (ax + cy + e, bx + dy + f).
+type Transform = [f64; 6];
+
+const IDENTITY: Transform = [1.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+
+/// The transform that applies `second` after `first`.
+fn compose(first: Transform, second: Transform) -> Transform {
+    let [a, b, c, d, e, f] = first;
+    let [a2, b2, c2, d2, e2, f2] = second;
+    [
+        a2 * a + c2 * b,
+        b2 * a + d2 * b,
+        a2 * c + c2 * d,
+        b2 * c + d2 * d,
+        a2 * e + c2 * f + e2,
+        b2 * e + d2 * f + f2,
+    ]
+}
+
+fn apply(transform: &Transform, point: Vec2) -> Vec2 {
+    let [a, b, c, d, e, f] = *transform;
+    Vec2::new(a * point.x + c * point.y + e, b * point.x + d * point.y + f)
+}
+
+/// What an element inherits from the groups around it.
+#[derive(Clone)]
+struct Context {
+    transform: Transform,
+    fill: Option<(u8, u8, u8)>,
+    fill_opacity: f64,
+    opacity: f64,
+    id: Option<String>,
+    layer: usize,
+    layer_name: Option<String>,
+}
+
+/// A tag of the document.
+struct Tag {
+    name: String,
+    attributes: HashMap<String, String>,
+    closing: bool,
+    self_closing: bool,
+    line: usize,
+}
+
+/// Reads the tags of the document, and the shapes inside of them.
+struct Parser<'a> {
+    text: &'a str,
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str) -> Parser<'a> {
+        Parser {
+            text,
+            position: 0,
+            line: 1,
+        }
+    }
+
+    fn error(line: usize, message: impl Into<String>) -> ImportError {
+        ImportError::Parse {
+            line,
+            message: message.into(),
+        }
+    }
+
+    /// Move forward by some number of bytes, counting lines.
+    fn advance(self: &mut Self, count: usize) {
+        let end = (self.position + count).min(self.text.len());
+        self.line += self.text[self.position..end].matches('\n').count();
+        self.position = end;
+    }
+
+    /// Move past the next occurrence of `pattern`.
+    fn skip_past(self: &mut Self, pattern: &str, what: &str) -> Result<(), ImportError> {
+        match self.text[self.position..].find(pattern) {
+            Some(offset) => {
+                self.advance(offset + pattern.len());
+                Ok(())
+            }
+            None => Err(Self::error(self.line, format!("unterminated {what}"))),
+        }
+    }
+
+    /// The next element tag, skipping text, comments, declarations and CDATA.
+    fn next_tag(self: &mut Self) -> Result<Option<Tag>, ImportError> {
+        loop {
+            match self.text[self.position..].find('<') {
+                Some(offset) => self.advance(offset),
+                None => return Ok(None),
+            }
+            let rest = &self.text[self.position..];
+            if rest.starts_with("<!--") {
+                self.skip_past("-->", "comment")?;
+            } else if rest.starts_with("<![CDATA[") {
+                self.skip_past("]]>", "CDATA section")?;
+            } else if rest.starts_with("<?") {
+                self.skip_past("?>", "processing instruction")?;
+            } else if rest.starts_with("<!") {
+                self.skip_past(">", "declaration")?;
+            } else {
+                return self.tag().map(Some);
+            }
+        }
+    }
+
+    /// Read the tag at the current position.
+    fn tag(self: &mut Self) -> Result<Tag, ImportError> {
+        let line = self.line;
+        self.advance(1);
+        let closing = self.text[self.position..].starts_with('/');
+        if closing {
+            self.advance(1);
+        }
+        let name = self.word();
+        if name.is_empty() {
+            return Err(Self::error(line, "expected a tag name after '<'"));
+        }
+        let mut attributes = HashMap::new();
+        loop {
+            self.skip_whitespace();
+            let rest = &self.text[self.position..];
+            if rest.starts_with("/>") {
+                self.advance(2);
+                return Ok(Tag {
+                    name,
+                    attributes,
+                    closing,
+                    self_closing: true,
+                    line,
+                });
+            } else if rest.starts_with('>') {
+                self.advance(1);
+                return Ok(Tag {
+                    name,
+                    attributes,
+                    closing,
+                    self_closing: false,
+                    line,
+                });
+            } else if rest.is_empty() {
+                return Err(Self::error(line, format!("unterminated <{name}> tag")));
+            }
+            let attribute = self.word();
+            if attribute.is_empty() {
+                return Err(Self::error(
+                    self.line,
+                    format!(
+                        "unexpected '{}' in <{name}> tag",
+                        rest.chars().next().unwrap()
+                    ),
+                ));
+            }
+            self.skip_whitespace();
+            if !self.text[self.position..].starts_with('=') {
+                // an attribute without a value
+                attributes.insert(attribute, String::new());
+                continue;
+            }
+            self.advance(1);
+            self.skip_whitespace();
+            let quote = match self.text[self.position..].chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => {
+                    return Err(Self::error(
+                        self.line,
+                        format!("expected a quoted value for '{attribute}'"),
+                    ))
+                }
+            };
+            self.advance(1);
+            let value_line = self.line;
+            let length = self.text[self.position..].find(quote).ok_or_else(|| {
+                Self::error(value_line, format!("unterminated value for '{attribute}'"))
+            })?;
+            let value = unescape(&self.text[self.position..self.position + length]);
+            self.advance(length + 1);
+            attributes.insert(attribute, value);
+        }
+    }
+
+    /// Read a tag or attribute name.
+    fn word(self: &mut Self) -> String {
+        let length = self.text[self.position..]
+            .find(|c: char| !(c.is_alphanumeric() || matches!(c, ':' | '-' | '_' | '.')))
+            .unwrap_or(self.text.len() - self.position);
+        let word = self.text[self.position..self.position + length].to_string();
+        self.advance(length);
+        word
+    }
+
+    fn skip_whitespace(self: &mut Self) {
+        let length = self.text[self.position..]
+            .find(|c: char| !c.is_whitespace())
+            .unwrap_or(self.text.len() - self.position);
+        self.advance(length);
+    }
+
+    /// Read every shape in the document.
+    fn shapes(mut self: Self) -> Result<Vec<SvgShape>, ImportError> {
+        let mut shapes = Vec::new();
+        let mut stack: Vec<(String, Context)> = Vec::new();
+        let mut layers = 0;
+        let root = Context {
+            transform: IDENTITY,
+            fill: Some((0, 0, 0)),
+            fill_opacity: 1.0,
+            opacity: 1.0,
+            id: None,
+            layer: 0,
+            layer_name: None,
+        };
+        while let Some(tag) = self.next_tag()? {
+            if tag.closing {
+                match stack.pop() {
+                    Some((name, _)) if name == tag.name => continue,
+                    Some((name, _)) => {
+                        return Err(Self::error(
+                            tag.line,
+                            format!("expected </{name}>, found </{}>", tag.name),
+                        ))
+                    }
+                    None => {
+                        return Err(Self::error(tag.line, format!("unexpected </{}>", tag.name)))
+                    }
+                }
+            }
+            let parent = stack.last().map(|(_, context)| context).unwrap_or(&root);
+            let hidden = NOT_DRAWN.contains(&tag.name.as_str())
+                || stack
+                    .iter()
+                    .any(|(name, _)| NOT_DRAWN.contains(&name.as_str()));
+            if hidden {
+                // nothing inside is imported, so its attributes don't matter
+                if !tag.self_closing {
+                    let context = parent.clone();
+                    stack.push((tag.name, context));
+                }
+                continue;
+            }
+            let mut context = inherit(parent, &tag)?;
+            // a group directly inside of the root <svg> is a layer
+            if tag.name == "g" && stack.len() == 1 && stack[0].0 == "svg" {
+                layers += 1;
+                context.layer = layers;
+                context.layer_name = tag
+                    .attributes
+                    .get("inkscape:label")
+                    .or(tag.attributes.get("id"))
+                    .cloned();
+            }
+            shapes.extend(shape(&tag, &context)?);
+            if !tag.self_closing {
+                stack.push((tag.name, context));
+            }
+        }
+        if let Some((name, _)) = stack.last() {
+            return Err(Self::error(self.line, format!("<{name}> is never closed")));
+        }
+        Ok(shapes)
+    }
+}
+
+/// Replace the predefined XML entities.
+fn unescape(value: &str) -> String {
+    value
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// A presentation attribute, where the `style` attribute takes precedence.
+fn property<'t>(tag: &'t Tag, name: &str) -> Option<&'t str> {
+    let styled = tag.attributes.get("style").and_then(|style| {
+        style.split(';').rev().find_map(|declaration| {
+            let (key, value) = declaration.split_once(':')?;
+            (key.trim() == name).then(|| value.trim())
+        })
+    });
+    styled.or(tag.attributes.get(name).map(|value| value.trim()))
+}
+
+/// The context of an element, given its parent's.
+fn inherit(parent: &Context, tag: &Tag) -> Result<Context, ImportError> {
+    let mut context = parent.clone();
+    if let Some(transform) = tag.attributes.get("transform") {
+        context.transform = compose(parse_transform(transform, tag.line)?, parent.transform);
+    }
+    if let Some(fill) = property(tag, "fill") {
+        context.fill = parse_color(fill, tag.line)?;
+    }
+    if let Some(opacity) = property(tag, "fill-opacity") {
+        context.fill_opacity = parse_number(opacity, tag.line)?;
+    }
+    if let Some(opacity) = property(tag, "opacity") {
+        context.opacity *= parse_number(opacity, tag.line)?;
+    }
+    if let Some(id) = tag.attributes.get("id") {
+        context.id = Some(id.clone());
+    }
+    Ok(context)
+}
+
+/// The shapes that an element draws.
+fn shape(tag: &Tag, context: &Context) -> Result<Vec<SvgShape>, ImportError> {
+    let number = |name: &str| -> Result<f64, ImportError> {
+        match tag.attributes.get(name) {
+            Some(value) => parse_number(value.trim_end_matches("px"), tag.line),
+            None => Ok(0.0),
+        }
+    };
+    let mut outlines: Vec<(Vec<Vec2>, bool)> = Vec::new();
+    let mut circle = None;
+    match tag.name.as_str() {
+        "rect" => {
+            let (x, y, width, height) = (
+                number("x")?,
+                number("y")?,
+                number("width")?,
+                number("height")?,
+            );
+            outlines.push((
+                vec![
+                    Vec2::new(x, y),
+                    Vec2::new(x + width, y),
+                    Vec2::new(x + width, y + height),
+                    Vec2::new(x, y + height),
+                ],
+                true,
+            ));
+        }
+        "circle" | "ellipse" => {
+            let center = Vec2::new(number("cx")?, number("cy")?);
+            let (rx, ry) = if tag.name == "circle" {
+                (number("r")?, number("r")?)
+            } else {
+                (number("rx")?, number("ry")?)
+            };
+            let [a, b, c, d, _, _] = context.transform;
+            // a circle stays a circle through rotations, reflections and uniform scales
+            let similar = ((a - d).abs() < 1.0e-9 && (b + c).abs() < 1.0e-9)
+                || ((a + d).abs() < 1.0e-9 && (b - c).abs() < 1.0e-9);
+            if rx == ry && similar {
+                circle = Some((
+                    apply(&context.transform, center),
+                    rx * (a * d - b * c).abs().sqrt(),
+                ));
+                outlines.push((Vec::new(), true));
+            } else {
+                let count = 4 * CURVE_SEGMENTS;
+                outlines.push((
+                    (0..count)
+                        .map(|k| {
+                            let angle = 2.0 * PI * k as f64 / count as f64;
+                            center + Vec2::new(rx * angle.cos(), ry * angle.sin())
+                        })
+                        .collect(),
+                    true,
+                ));
+            }
+        }
+        "line" => outlines.push((
+            vec![
+                Vec2::new(number("x1")?, number("y1")?),
+                Vec2::new(number("x2")?, number("y2")?),
+            ],
+            false,
+        )),
+        "polygon" | "polyline" => {
+            let numbers = parse_numbers(
+                tag.attributes
+                    .get("points")
+                    .map(String::as_str)
+                    .unwrap_or(""),
+                tag.line,
+            )?;
+            outlines.push((
+                numbers
+                    .chunks_exact(2)
+                    .map(|pair| Vec2::new(pair[0], pair[1]))
+                    .collect(),
+                tag.name == "polygon",
+            ));
+        }
+        "path" => {
+            let data = tag.attributes.get("d").map(String::as_str).unwrap_or("");
+            outlines.extend(parse_path(data, tag.line)?);
+        }
+        _ => return Ok(Vec::new()),
+    }
+    let fill = context.fill.map(|(r, g, b)| {
+        let alpha = (context.fill_opacity * context.opacity).clamp(0.0, 1.0);
+        (r, g, b, (alpha * 255.0).round() as u8)
+    });
+    Ok(outlines
+        .into_iter()
+        .filter(|(outline, _)| circle.is_some() || outline.len() >= 2)
+        .map(|(outline, closed)| SvgShape {
+            outline: outline
+                .into_iter()
+                .map(|point| apply(&context.transform, point))
+                .collect(),
+            closed,
+            circle,
+            fill,
+            id: context.id.clone(),
+            layer: context.layer,
+            layer_name: context.layer_name.clone(),
+        })
+        .collect())
+}
+
+fn parse_number(text: &str, line: usize) -> Result<f64, ImportError> {
+    text.trim()
+        .parse()
+        .map_err(|_| Parser::error(line, format!("invalid number '{text}'")))
+}
+
+/// Reads the numbers and commands of a list of numbers, a transform or path data.
+struct Scanner<'a> {
+    bytes: &'a [u8],
+    position: usize,
+    line: usize,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(text: &'a str, line: usize) -> Scanner<'a> {
+        Scanner {
+            bytes: text.as_bytes(),
+            position: 0,
+            line,
+        }
+    }
+
+    /// Skip whitespace and commas.
+    fn skip_separators(self: &mut Self) {
+        while self.position < self.bytes.len()
+            && (self.bytes[self.position].is_ascii_whitespace()
+                || self.bytes[self.position] == b',')
+        {
+            self.position += 1;
+        }
+    }
+
+    /// The next non-separator byte, without moving past it.
+    fn peek(self: &mut Self) -> Option<u8> {
+        self.skip_separators();
+        self.bytes.get(self.position).copied()
+    }
+
+    /// Whether a number comes next.
+    fn at_number(self: &mut Self) -> bool {
+        matches!(self.peek(), Some(b'0'..=b'9' | b'-' | b'+' | b'.'))
+    }
+
+    fn number(self: &mut Self) -> Result<f64, ImportError> {
+        self.skip_separators();
+        let start = self.position;
+        let digits = |scanner: &mut Scanner| {
+            while scanner.position < scanner.bytes.len()
+                && scanner.bytes[scanner.position].is_ascii_digit()
+            {
+                scanner.position += 1;
+            }
+        };
+        if matches!(self.bytes.get(self.position), Some(b'-' | b'+')) {
+            self.position += 1;
+        }
+        digits(self);
+        if self.bytes.get(self.position) == Some(&b'.') {
+            self.position += 1;
+            digits(self);
+        }
+        if matches!(self.bytes.get(self.position), Some(b'e' | b'E'))
+            && matches!(
+                self.bytes.get(self.position + 1),
+                Some(b'0'..=b'9' | b'-' | b'+')
+            )
+        {
+            self.position += 2;
+            digits(self);
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position]).unwrap_or("");
+        text.parse().map_err(|_| {
+            let found = self
+                .bytes
+                .get(start)
+                .map(|&byte| byte as char)
+                .unwrap_or(' ');
+            Parser::error(self.line, format!("expected a number, found '{found}'"))
+        })
+    }
+
+    /// An arc's flag, which can be written without a separator after it.
+    fn flag(self: &mut Self) -> Result<bool, ImportError> {
+        match self.peek() {
+            Some(b'0') => {
+                self.position += 1;
+                Ok(false)
+            }
+            Some(b'1') => {
+                self.position += 1;
+                Ok(true)
+            }
+            _ => Err(Parser::error(self.line, "expected an arc flag (0 or 1)")),
+        }
+    }
+}
+
+fn parse_numbers(text: &str, line: usize) -> Result<Vec<f64>, ImportError> {
+    let mut scanner = Scanner::new(text, line);
+    let mut numbers = Vec::new();
+    while scanner.peek().is_some() {
+        numbers.push(scanner.number()?);
+    }
+    Ok(numbers)
+}
+
+/// Parse a transform list (ex: "translate(10 20) rotate(45)").
+fn parse_transform(text: &str, line: usize) -> Result<Transform, ImportError> {
+    let mut transform = IDENTITY;
+    let mut rest = text.trim();
+    while !rest.is_empty() {
+        let open = rest
+            .find('(')
+            .ok_or_else(|| Parser::error(line, format!("invalid transform '{text}'")))?;
+        let close = rest[open..]
+            .find(')')
+            .map(|close| open + close)
+            .ok_or_else(|| Parser::error(line, format!("unterminated transform '{text}'")))?;
+        let name = rest[..open].trim().trim_start_matches(',').trim();
+        let values = parse_numbers(&rest[open + 1..close], line)?;
+        let value = |k: usize| values.get(k).copied();
+        let next = match (name, values.len()) {
+            ("matrix", 6) => [
+                values[0], values[1], values[2], values[3], values[4], values[5],
+            ],
+            ("translate", 1 | 2) => [1.0, 0.0, 0.0, 1.0, values[0], value(1).unwrap_or(0.0)],
+            ("scale", 1 | 2) => {
+                let x = values[0];
+                [x, 0.0, 0.0, value(1).unwrap_or(x), 0.0, 0.0]
+            }
+            ("rotate", 1 | 3) => {
+                let (sin, cos) = values[0].to_radians().sin_cos();
+                let (x, y) = (value(1).unwrap_or(0.0), value(2).unwrap_or(0.0));
+                // rotate about (x, y)
+                [
+                    cos,
+                    sin,
+                    -sin,
+                    cos,
+                    x - cos * x + sin * y,
+                    y - sin * x - cos * y,
+                ]
+            }
+            ("skewX", 1) => [1.0, 0.0, values[0].to_radians().tan(), 1.0, 0.0, 0.0],
+            ("skewY", 1) => [1.0, values[0].to_radians().tan(), 0.0, 1.0, 0.0, 0.0],
+            _ => {
+                return Err(Parser::error(
+                    line,
+                    format!("invalid transform '{}'", &rest[..=close]),
+                ))
+            }
+        };
+        // the list applies from right to left
+        transform = compose(next, transform);
+        rest = rest[close + 1..].trim();
+    }
+    Ok(transform)
+}
+
+/// Parse a paint (ex: "#ff0000", "rgb(255, 0, 0)", "red", or "none").
+fn parse_color(text: &str, line: usize) -> Result<Option<(u8, u8, u8)>, ImportError> {
+    let text = text.trim().to_ascii_lowercase();
+    let hex = |digits: &str| u8::from_str_radix(digits, 16).ok();
+    let color = if text == "none" || text == "transparent" {
+        return Ok(None);
+    } else if let Some(digits) = text.strip_prefix('#') {
+        match digits.len() {
+            // any other characters would split the digits in the middle of a character
+            _ if !digits.is_ascii() => None,
+            3 => {
+                let channel = |k: usize| hex(&digits[k..=k]).map(|value| value * 17);
+                channel(0)
+                    .zip(channel(1))
+                    .zip(channel(2))
+                    .map(|((r, g), b)| (r, g, b))
+            }
+            6 => hex(&digits[0..2])
+                .zip(hex(&digits[2..4]))
+                .zip(hex(&digits[4..6]))
+                .map(|((r, g), b)| (r, g, b)),
+            _ => None,
+        }
+    } else if let Some(channels) = text
+        .strip_prefix("rgb(")
+        .and_then(|rest| rest.strip_suffix(')'))
+    {
+        let channels: Vec<Option<u8>> = channels
+            .split(',')
+            .map(|channel| {
+                let channel = channel.trim();
+                let value = match channel.strip_suffix('%') {
+                    Some(percent) => percent.parse::<f64>().ok()? * 2.55,
+                    None => channel.parse::<f64>().ok()?,
+                };
+                Some(value.round().clamp(0.0, 255.0) as u8)
+            })
+            .collect();
+        match channels[..] {
+            [Some(r), Some(g), Some(b)] => Some((r, g, b)),
+            _ => None,
+        }
+    } else if text.starts_with("url(") || text == "currentcolor" {
+        // gradients, patterns and inherited colors can't be a single color, so use the default particle color
+        let (r, g, b, _) = Particle::new().color;
+        Some((r, g, b))
+    } else {
+        match text.as_str() {
+            "black" => Some((0, 0, 0)),
+            "white" => Some((255, 255, 255)),
+            "red" => Some((255, 0, 0)),
+            "lime" => Some((0, 255, 0)),
+            "green" => Some((0, 128, 0)),
+            "blue" => Some((0, 0, 255)),
+            "yellow" => Some((255, 255, 0)),
+            "cyan" | "aqua" => Some((0, 255, 255)),
+            "magenta" | "fuchsia" => Some((255, 0, 255)),
+            "gray" | "grey" => Some((128, 128, 128)),
+            "silver" => Some((192, 192, 192)),
+            "maroon" => Some((128, 0, 0)),
+            "olive" => Some((128, 128, 0)),
+            "purple" => Some((128, 0, 128)),
+            "teal" => Some((0, 128, 128)),
+            "navy" => Some((0, 0, 128)),
+            "orange" => Some((255, 165, 0)),
+            "brown" => Some((165, 42, 42)),
+            "pink" => Some((255, 192, 203)),
+            "crimson" => Some((220, 20, 60)),
+            _ => None,
+        }
+    };
+    color
+        .map(Some)
+        .ok_or_else(|| Parser::error(line, format!("invalid color '{text}'")))
+}
+
+/// Parse path data into its subpaths, as (points, closed).
+fn parse_path(data: &str, line: usize) -> Result<Vec<(Vec<Vec2>, bool)>, ImportError> {
+    let mut scanner = Scanner::new(data, line);
+    let mut subpaths = Vec::new();
+    let mut points: Vec<Vec2> = Vec::new();
+    let mut current = Vec2::zero();
+    let mut start = Vec2::zero();
+    // the last control point, for the smooth curve commands
+    let mut control: Option<Vec2> = None;
+    let mut command = b' ';
+
+    let finish = |points: &mut Vec<Vec2>, subpaths: &mut Vec<(Vec<Vec2>, bool)>, closed: bool| {
+        if closed && points.len() > 1 && (points[0] - points[points.len() - 1]).mag() < 1.0e-9 {
+            points.pop();
+        }
+        if points.len() >= 2 {
+            subpaths.push((std::mem::take(points), closed));
+        }
+        points.clear();
+    };
+
+    while let Some(next) = scanner.peek() {
+        if next.is_ascii_alphabetic() {
+            command = next;
+            scanner.position += 1;
+        } else if command == b' ' {
+            return Err(Parser::error(line, "path data must start with a command"));
+        }
+        let relative = command.is_ascii_lowercase();
+        let offset = if relative { current } else { Vec2::zero() };
+        let point = |scanner: &mut Scanner| -> Result<Vec2, ImportError> {
+            Ok(offset + Vec2::new(scanner.number()?, scanner.number()?))
+        };
+        let previous_control = control.take();
+        match command.to_ascii_uppercase() {
+            b'M' => {
+                finish(&mut points, &mut subpaths, false);
+                current = point(&mut scanner)?;
+                start = current;
+                points.push(current);
+                // further pairs are implicit line-tos
+                command = if relative { b'l' } else { b'L' };
+            }
+            b'L' => {
+                current = point(&mut scanner)?;
+                points.push(current);
+            }
+            b'H' => {
+                current.x = scanner.number()? + offset.x;
+                points.push(current);
+            }
+            b'V' => {
+                current.y = scanner.number()? + offset.y;
+                points.push(current);
+            }
+            b'C' | b'S' => {
+                let first = if command.eq_ignore_ascii_case(&b'C') {
+                    point(&mut scanner)?
+                } else {
+                    previous_control.map_or(current, |control| current * 2.0 - control)
+                };
+                let second = point(&mut scanner)?;
+                let end = point(&mut scanner)?;
+                for k in 1..=CURVE_SEGMENTS {
+                    let t = k as f64 / CURVE_SEGMENTS as f64;
+                    let u = 1.0 - t;
+                    points.push(
+                        current * u.powi(3)
+                            + first * (3.0 * u * u * t)
+                            + second * (3.0 * u * t * t)
+                            + end * t.powi(3),
+                    );
+                }
+                control = Some(second);
+                current = end;
+            }
+            b'Q' | b'T' => {
+                let middle = if command.eq_ignore_ascii_case(&b'Q') {
+                    point(&mut scanner)?
+                } else {
+                    previous_control.map_or(current, |control| current * 2.0 - control)
+                };
+                let end = point(&mut scanner)?;
+                for k in 1..=CURVE_SEGMENTS {
+                    let t = k as f64 / CURVE_SEGMENTS as f64;
+                    let u = 1.0 - t;
+                    points.push(current * (u * u) + middle * (2.0 * u * t) + end * (t * t));
+                }
+                control = Some(middle);
+                current = end;
+            }
+            b'A' => {
+                let (rx, ry, rotation) = (scanner.number()?, scanner.number()?, scanner.number()?);
+                let (large, sweep) = (scanner.flag()?, scanner.flag()?);
+                let end = point(&mut scanner)?;
+                points.extend(arc(current, end, rx, ry, rotation, large, sweep));
+                current = end;
+            }
+            b'Z' => {
+                current = start;
+                finish(&mut points, &mut subpaths, true);
+                // a command after a closepath starts from the subpath's start
+                if scanner.at_number() {
+                    return Err(Parser::error(line, "unexpected number after a closepath"));
+                }
+                if !matches!(scanner.peek(), Some(b'M' | b'm') | None) {
+                    points.push(current);
+                }
+            }
+            _ => {
+                return Err(Parser::error(
+                    line,
+                    format!("unknown path command '{}'", command as char),
+                ))
+            }
+        }
+    }
+    finish(&mut points, &mut subpaths, false);
+    Ok(subpaths)
+}
+
+/// Flatten an elliptical arc (after the starting point), from the SVG specification's endpoint to center
+/// conversion.
+fn arc(
+    start: Vec2,
+    end: Vec2,
+    rx: f64,
+    ry: f64,
+    rotation: f64,
+    large: bool,
+    sweep: bool,
+) -> Vec<Vec2> {
+    let (mut rx, mut ry) = (rx.abs(), ry.abs());
+    if rx == 0.0 || ry == 0.0 || (end - start).mag() == 0.0 {
+        return vec![end];
+    }
+    let (sin, cos) = rotation.to_radians().sin_cos();
+    let half = (start - end) / 2.0;
+    let x1 = cos * half.x + sin * half.y;
+    let y1 = -sin * half.x + cos * half.y;
+    // scale up radii that are too small to reach the end
+    let lambda = (x1 / rx).powi(2) + (y1 / ry).powi(2);
+    if lambda > 1.0 {
+        rx *= lambda.sqrt();
+        ry *= lambda.sqrt();
+    }
+    let numerator = (rx * ry).powi(2) - (rx * y1).powi(2) - (ry * x1).powi(2);
+    let denominator = (rx * y1).powi(2) + (ry * x1).powi(2);
+    let mut coefficient = (numerator / denominator).max(0.0).sqrt();
+    if large == sweep {
+        coefficient = -coefficient;
+    }
+    let (cx1, cy1) = (coefficient * rx * y1 / ry, -coefficient * ry * x1 / rx);
+    let center = Vec2::new(cos * cx1 - sin * cy1, sin * cx1 + cos * cy1) + (start + end) / 2.0;
+
+    let angle = |x: f64, y: f64| y.atan2(x);
+    let theta = angle((x1 - cx1) / rx, (y1 - cy1) / ry);
+    let mut delta = angle((-x1 - cx1) / rx, (-y1 - cy1) / ry) - theta;
+    if sweep && delta < 0.0 {
+        delta += 2.0 * PI;
+    } else if !sweep && delta > 0.0 {
+        delta -= 2.0 * PI;
+    }
+    let count = ((delta.abs() / (PI / 2.0) * CURVE_SEGMENTS as f64 / 2.0).ceil() as usize).max(1);
+    (1..=count)
+        .map(|k| {
+            if k == count {
+                return end;
+            }
+            let (sin_t, cos_t) = (theta + delta * k as f64 / count as f64).sin_cos();
+            center
+                + Vec2::new(
+                    rx * cos * cos_t - ry * sin * sin_t,
+                    rx * sin * cos_t + ry * cos * sin_t,
+                )
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DRAWING: &str = r##"<?xml version="1.0"?>
+<svg xmlns="http://www.w3.org/2000/svg" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape">
+  <defs><g id="hidden"><rect width="5" height="5"/></g></defs>
+  <g inkscape:label="walls" transform="translate(10 0)">
+    <rect x="0" y="90" width="200" height="10" fill="#444"/>
+    <path d="M 0 0 L 10 0 Q 15 5 10 10 Z" style="fill:none"/>
+  </g>
+  <g id="bodies">
+    <circle cx="100" cy="40" r="20" fill="rgb(220, 20, 60)"/>
+    <polygon points="0,0 10,0 5,8" fill="red" opacity="0.5"/>
+  </g>
+</svg>"##;
+
+    #[test]
+    fn shapes() {
+        let svg = Svg::parse(DRAWING).unwrap();
+        assert_eq!(svg.shapes.len(), 4);
+        let layers: Vec<_> = svg.shapes.iter().map(|shape| shape.layer).collect();
+        assert_eq!(layers, vec![1, 1, 2, 2]);
+        assert_eq!(svg.shapes[0].layer_name.as_deref(), Some("walls"));
+        let corner = svg.shapes[0].outline[0];
+        assert_eq!((corner.x, corner.y), (10.0, 90.0));
+        assert_eq!(svg.shapes[1].fill, None);
+        let (center, radius) = svg.shapes[2].circle.unwrap();
+        assert_eq!((center.x, center.y, radius), (100.0, 40.0, 20.0));
+        assert_eq!(svg.shapes[2].fill, Some((220, 20, 60, 255)));
+    }
+
+    #[test]
+    fn not_drawn() {
+        let svg = Svg::parse(
+            r##"<svg>
+                <clipPath id="clip"><circle r="5"/></clipPath>
+                <mask><g><rect width="1" height="1"/></g></mask>
+                <symbol id="icon"><rect width="1" height="1"/></symbol>
+                <g><marker><path d="M 0 0 L 1 1 L 0 1 Z"/></marker><rect width="2" height="2"/></g>
+            </svg>"##,
+        )
+        .unwrap();
+        assert_eq!(svg.shapes.len(), 1);
+        assert_eq!(svg.shapes[0].layer, 1);
+    }
+
+    #[test]
+    fn truncated() {
+        // cut anywhere between the opening <svg and the closing </svg>
+        let start = DRAWING.find("<svg").unwrap() + 1;
+        for length in start..DRAWING.len() - "</svg>".len() {
+            assert!(Svg::parse(&DRAWING[..length]).is_err());
+        }
+    }
+
+    #[test]
+    fn corrupt() {
+        assert!(Svg::parse("<svg><g></svg>").is_err());
+        assert!(Svg::parse("<svg></g></svg>").is_err());
+        assert!(Svg::parse(r#"<svg><rect width="wide" height="1"/></svg>"#).is_err());
+        assert!(Svg::parse(r##"<svg><rect width="1" height="1" fill="#aé"/></svg>"##).is_err());
+        assert!(Svg::parse(r##"<svg><rect width="1" height="1" fill="#aaaaé"/></svg>"##).is_err());
+        assert!(
+            Svg::parse(r#"<svg><g transform=")("><rect width="1" height="1"/></g></svg>"#).is_err()
+        );
+        // every single character change parses (to something) or fails, without panicking
+        for index in 0..DRAWING.len() {
+            for replacement in [
+                "<", ">", "/", "\"", "=", " ", "-", "e", "1", ".", "(", ")", "é", "€",
+            ] {
+                let mut corrupted = DRAWING.to_string();
+                corrupted.replace_range(index..index + 1, replacement);
+                let _ = Svg::parse(&corrupted);
+            }
+        }
+    }
+}
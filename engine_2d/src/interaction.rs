@@ -10,6 +10,7 @@ pub fn step(sim: &mut System, dt: f64) {
     sim.step_forward(dt);
 }
 
-pub fn restart(_sim: &mut System) {
-    todo!();
+/// Restore the simulation to its state when it first stepped forward.
+pub fn restart(sim: &mut System) {
+    sim.restart();
 }
@@ -1,6 +1,12 @@
+pub mod generators;
+pub mod import;
 pub mod interaction;
 pub mod physics;
+pub mod random;
+pub mod recording;
 pub mod rendering;
+#[cfg(feature = "serde")]
+pub mod save;
 pub mod vec2;
 
 pub mod prelude {
@@ -10,10 +16,23 @@ pub mod prelude {
     //! use rusty_particle_physics_2d::prelude::*;
     //! ```
     pub use crate::{
-        physics::constraint::{Constraint, ConstraintKind},
+        physics::boundary::Boundary,
+        physics::collision::{Collisions, Shape, StaticCollider},
+        physics::constraint::{Constraint, ConstraintKind, Material, StrainModel},
+        physics::fluid::{Fluid, Viscoelasticity},
         physics::force::Force,
+        physics::joint::{Joint, JointKind},
+        physics::mesh::{Meshing, PolygonMesh},
+        physics::pair_table::PairTable,
         physics::particle::*,
-        physics::system::System,
+        physics::pneumatic::{Inflation, PneumaticBody},
+        physics::potential::Potential,
+        physics::rigid_body::RigidBody,
+        physics::soft_body::{Edge, Lattice, LatticeKind, SoftBody},
+        physics::system::{Snapshot, System},
+        physics::thermal::HeatTransfer,
+        physics::thermostat::{Thermostat, ThermostatKind},
+        random::Rng,
         vec2::Vec2,
     };
 }
@@ -0,0 +1,97 @@
+//! Provides the boundary conditions of the simulation space.
+//!
+//! By default space is open and unbounded. A periodic boundary instead turns a rectangular box into a torus:
+//! particles that leave through one side re-enter through the opposite side, which lets a small number of
+//! particles stand in for a bulk gas or fluid without any walls.
+//!
+//! Interactions in a periodic box use the minimum image convention. Every particle has infinitely many periodic
+//! images, and each pair of particles only interacts through the closest pair of images. This requires that
+//! interaction ranges (cutoffs, kernel radii, etc) are less than half of the box's size.
+//!
+//! Everything that measures an offset between particles follows the boundary: forces, particle collisions,
+//! constraints, soft and rigid bodies, joints, position based fluids, and heat conduction. Every particle is wrapped
+//! back into the box on its own, so bodies can straddle the boundary, as long as each one is smaller than half of
+//! the box. Rest shapes are measured with the boundary too, so it should be set before adding bodies and
+//! constraints.
+
+use crate::vec2::Vec2;
+
+/// The boundary conditions of the simulation space.
+#[derive(Clone, Copy, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Boundary {
+    /// Unbounded space.
+    #[default]
+    Open,
+    /// A periodic box spanning from `min` to `max`.
+    Periodic { min: Vec2, max: Vec2 },
+}
+
+impl Boundary {
+    /// A periodic box centered on the origin with the given width and height.
+    pub fn periodic(width: f64, height: f64) -> Boundary {
+        Boundary::Periodic {
+            min: Vec2::new(-width / 2.0, -height / 2.0),
+            max: Vec2::new(width / 2.0, height / 2.0),
+        }
+    }
+
+    /// Whether the boundary is periodic.
+    pub fn is_periodic(self: &Self) -> bool {
+        matches!(self, Boundary::Periodic { .. })
+    }
+
+    /// Map a position back into the box, if the boundary is periodic.
+    pub fn wrap(self: &Self, pos: Vec2) -> Vec2 {
+        match *self {
+            Boundary::Open => pos,
+            Boundary::Periodic { min, max } => {
+                let size = max - min;
+                Vec2::new(
+                    min.x + (pos.x - min.x).rem_euclid(size.x),
+                    min.y + (pos.y - min.y).rem_euclid(size.y),
+                )
+            }
+        }
+    }
+
+    /// The displacement a - b, using the minimum image convention if the boundary is periodic.
+    pub fn displacement(self: &Self, a: Vec2, b: Vec2) -> Vec2 {
+        let offset = a - b;
+        match *self {
+            Boundary::Open => offset,
+            Boundary::Periodic { min, max } => {
+                let size = max - min;
+                Vec2::new(
+                    offset.x - size.x * (offset.x / size.x).round(),
+                    offset.y - size.y * (offset.y / size.y).round(),
+                )
+            }
+        }
+    }
+
+    /// The position itself, followed by all of its periodic images that are within `margin` of the box
+    /// (ex: to draw a particle that is partially across the boundary on both sides of the box).
+    pub fn images(self: &Self, pos: Vec2, margin: f64) -> Vec<Vec2> {
+        let mut images = vec![pos];
+        if let Boundary::Periodic { min, max } = *self {
+            let size = max - min;
+            for dx in [-1.0, 0.0, 1.0] {
+                for dy in [-1.0, 0.0, 1.0] {
+                    if dx == 0.0 && dy == 0.0 {
+                        continue;
+                    }
+                    let image = pos + Vec2::new(dx * size.x, dy * size.y);
+                    if image.x > min.x - margin
+                        && image.x < max.x + margin
+                        && image.y > min.y - margin
+                        && image.y < max.y + margin
+                    {
+                        images.push(image);
+                    }
+                }
+            }
+        }
+        images
+    }
+}
@@ -0,0 +1,250 @@
+//! Provides collisions between particles, and static colliders, which are immovable shapes that particles
+//! can collide with.
+//!
+//! Static colliders aren't made of particles, so they don't take part in the dynamics at all. They are
+//! simply boundaries of the world (floors, walls, obstacles, containers, etc). Collisions are handled in a
+//! position based manner: any particle that overlaps a collider (or another particle) is moved back out along
+//! the contact normal, and its velocity is later updated from that displacement.
+//!
+//! Particle collisions also get a velocity pass afterwards, which gives the bounce its coefficient of
+//! restitution. The kinetic energy that an inelastic collision removes isn't lost, it is turned into heat that
+//! is shared between the two particles.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::neighbor_grid::NeighborGrid;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+/// The shape of a static collider.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Shape {
+    /// A solid circle.
+    Circle {
+        center: Vec2,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        radius: f64,
+    },
+    /// An infinitely thin line segment, which can be collided with from either side.
+    Segment { start: Vec2, end: Vec2 },
+    /// A solid, simple polygon. The vertices can be in either winding order.
+    Polygon { vertices: Vec<Vec2> },
+}
+
+/// An immovable shape that particles collide with.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct StaticCollider {
+    pub shape: Shape,
+    /// like `Particle::group`, for when colliders should be told apart (ex: adhesion strengths)
+    pub group: u32,
+}
+
+impl StaticCollider {
+    /// Create a new static collider.
+    pub fn new(shape: Shape) -> StaticCollider {
+        StaticCollider { shape, group: 0 }
+    }
+
+    /// A builder method to give the collider a specific group after creating it.
+    pub fn group(mut self: Self, group: u32) -> StaticCollider {
+        self.group = group;
+        self
+    }
+
+    /// Find the closest point on the collider's surface to `pos`.
+    ///
+    /// Returns (closest point, outward surface normal, signed distance), where the distance is negative
+    /// if `pos` is inside of the collider.
+    pub fn closest(self: &Self, pos: Vec2) -> (Vec2, Vec2, f64) {
+        match &self.shape {
+            Shape::Circle { center, radius } => {
+                let offset = pos - *center;
+                let distance = offset.mag();
+                let normal = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    Vec2::new(0.0, 1.0)
+                };
+                (*center + normal * *radius, normal, distance - radius)
+            }
+            Shape::Segment { start, end } => {
+                let point = closest_on_segment(pos, *start, *end);
+                let offset = pos - point;
+                let distance = offset.mag();
+                let normal = if distance > 0.0 {
+                    offset / distance
+                } else {
+                    let along = *end - *start;
+                    Vec2::new(-along.y, along.x) / along.mag()
+                };
+                (point, normal, distance)
+            }
+            Shape::Polygon { vertices } => {
+                let mut closest = (Vec2::zero(), f64::INFINITY, 0);
+                for k in 0..vertices.len() {
+                    let (a, b) = (vertices[k], vertices[(k + 1) % vertices.len()]);
+                    let point = closest_on_segment(pos, a, b);
+                    let distance = (pos - point).mag();
+                    if distance < closest.1 {
+                        closest = (point, distance, k);
+                    }
+                }
+                let (point, distance, edge) = closest;
+                let inside = contains(vertices, pos);
+                let normal = if distance > 0.0 {
+                    (pos - point) / distance * if inside { -1.0 } else { 1.0 }
+                } else {
+                    // on the surface, so use the edge normal pointing away from the polygon's interior
+                    let (a, b) = (vertices[edge], vertices[(edge + 1) % vertices.len()]);
+                    let along = (b - a) / (b - a).mag();
+                    let normal = Vec2::new(along.y, -along.x);
+                    if contains(vertices, point - normal * 1.0e-6) {
+                        normal
+                    } else {
+                        normal * -1.0
+                    }
+                };
+                (point, normal, if inside { -distance } else { distance })
+            }
+        }
+    }
+
+    /// Move a particle out of the collider, if it's overlapping it.
+    pub fn collide(self: &Self, particle: &mut Particle) {
+        let (_, normal, distance) = self.closest(particle.pos);
+        if distance < particle.radius {
+            particle.pos += normal * (particle.radius - distance);
+        }
+    }
+}
+
+/// Collisions between the particles themselves.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Collisions {
+    /// coefficient of restitution, from 0.0 (perfectly inelastic) to 1.0 (perfectly elastic)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub restitution: f64,
+    /// the contacts found during the last projection
+    contacts: Vec<Contact>,
+}
+
+/// A pair of particles that were found overlapping.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+struct Contact {
+    particles: (usize, usize),
+    /// unit normal pointing from the second particle to the first
+    normal: Vec2,
+    /// relative normal velocity before the collision (negative when approaching)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    normal_speed: f64,
+}
+
+impl Collisions {
+    /// Turn on collisions between particles, with a coefficient of restitution.
+    pub fn new(restitution: f64) -> Collisions {
+        Collisions {
+            restitution,
+            contacts: Vec::new(),
+        }
+    }
+
+    /// Push apart every pair of overlapping particles, weighted by their masses.
+    pub fn project(self: &mut Self, particles: &mut [Particle], boundary: &Boundary) {
+        self.contacts.clear();
+        let max_radius = particles.iter().map(|p| p.radius).fold(0.0, f64::max);
+        if max_radius <= 0.0 {
+            return;
+        }
+        let grid = NeighborGrid::from_particles_in(
+            particles,
+            0..particles.len(),
+            2.0 * max_radius,
+            boundary,
+        );
+        for i in 0..particles.len() {
+            for j in grid.candidates(particles[i].pos) {
+                if j <= i {
+                    continue;
+                }
+                let offset = boundary.displacement(particles[i].pos, particles[j].pos);
+                let distance = offset.mag();
+                let overlap = particles[i].radius + particles[j].radius - distance;
+                if overlap <= 0.0 || distance == 0.0 {
+                    continue;
+                }
+                let normal = offset / distance;
+                let (w_i, w_j) = (particles[i].inverse_mass(), particles[j].inverse_mass());
+                if w_i + w_j == 0.0 {
+                    continue;
+                }
+                self.contacts.push(Contact {
+                    particles: (i, j),
+                    normal,
+                    normal_speed: (particles[i].vel - particles[j].vel).dot(&normal),
+                });
+                particles[i].pos += normal * (overlap * w_i / (w_i + w_j));
+                particles[j].pos -= normal * (overlap * w_j / (w_i + w_j));
+            }
+        }
+    }
+
+    /// Give the contacts found during the last projection their bounce, and turn the kinetic energy
+    /// that the collision removed into heat.
+    pub fn restitute(self: &Self, particles: &mut [Particle]) {
+        for contact in &self.contacts {
+            let (i, j) = contact.particles;
+            if contact.normal_speed >= 0.0 {
+                continue;
+            }
+            let (w_i, w_j) = (particles[i].inverse_mass(), particles[j].inverse_mass());
+            let current = (particles[i].vel - particles[j].vel).dot(&contact.normal);
+            let target = -self.restitution * contact.normal_speed;
+            let impulse = contact.normal * ((target - current) / (w_i + w_j));
+            particles[i].vel += impulse * w_i;
+            particles[j].vel -= impulse * w_j;
+
+            // ½μv² of the approach is lost, except for the part that comes back as the bounce
+            let reduced_mass = 1.0 / (w_i + w_j);
+            let heat = 0.5
+                * reduced_mass
+                * contact.normal_speed.powi(2)
+                * (1.0 - self.restitution.powi(2));
+            // a particle with infinite mass can't be heated, so the other one takes all of the heat
+            let share_i = if w_i == 0.0 {
+                0.0
+            } else if w_j == 0.0 {
+                1.0
+            } else {
+                0.5
+            };
+            particles[i].add_heat(heat * share_i);
+            particles[j].add_heat(heat * (1.0 - share_i));
+        }
+    }
+}
+
+/// The closest point on the segment ab to p.
+fn closest_on_segment(p: Vec2, a: Vec2, b: Vec2) -> Vec2 {
+    let ab = b - a;
+    let length_squared = ab.mag_squared();
+    if length_squared == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(&ab) / length_squared).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+/// Whether a point is inside of a simple polygon (even-odd rule).
+pub fn contains(vertices: &[Vec2], p: Vec2) -> bool {
+    let mut inside = false;
+    for k in 0..vertices.len() {
+        let (a, b) = (vertices[k], vertices[(k + 1) % vertices.len()]);
+        if (a.y > p.y) != (b.y > p.y) && p.x < a.x + (p.y - a.y) / (b.y - a.y) * (b.x - a.x) {
+            inside = !inside;
+        }
+    }
+    inside
+}
@@ -1,10 +1,28 @@
+//! Provides position based constraints between particles.
+//!
+//! Constraints are solved with extended position based dynamics (XPBD), from Macklin, Müller, Chentanez. 2016.
+//! [Link](https://doi.org/10.1145/2994258.2994272). A compliance of 0.0 makes a constraint perfectly stiff,
+//! and a positive compliance (the inverse of stiffness) makes it behave like a spring, independently of the
+//! timestep and the number of substeps.
+//!
+//! Triangles of three particles can be given continuum strain constraints, which make a meshed body behave like an
+//! elastic material with a Young's modulus and Poisson ratio, instead of like a network of springs. The strain
+//! energy of a St. Venant-Kirchhoff material is solved as a single energy based constraint, C = √(2AΨ), from
+//! Bender, Koschier, Charrier, Weber. 2014. [Link](https://doi.org/10.1016/j.cag.2014.07.004). A Neo-Hookean
+//! material is solved as a hydrostatic and a deviatoric constraint, which stays stable even when triangles are
+//! inverted, from Macklin, Müller. 2021. [Link](https://doi.org/10.1111/cgf.14328).
+
+use crate::physics::boundary::Boundary;
 use crate::physics::particle::Particle;
+use crate::physics::registry::{ConstraintFunction, Named};
 use crate::vec2::Vec2;
 
 /// The type of constraint.
 ///
 /// The `Equality` type means that the constraint is satisfied if function = 0 (a Holonomic constraint).
 /// The `Inequality` type means that the constraint is satisfied if function ≥ 0.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ConstraintKind {
     /// The constraint is satisfied if function = 0 (a Holonomic constraint).
     Equality,
@@ -12,26 +30,342 @@ pub enum ConstraintKind {
     Inequality,
 }
 
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Constraint {
     /// A generalized, position based constraint.
     Constraint {
         particles: Vec<Particle>,
-        function: Box<dyn Fn(Vec<Vec2>) -> f64>,
+        /// a named closure, so that the constraint can be saved and loaded
+        function: Named<ConstraintFunction>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
         compliance: f64,
         kind: ConstraintKind,
         broken: bool,
     },
 
     BoundingRect {},
+
+    /// Keeps two particles (indices into `System::particles`) a set distance apart.
+    Distance {
+        particles: (usize, usize),
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        length: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        compliance: f64,
+    },
+
+    /// Keeps the signed area of a triangle of particles at its rest area.
+    TriangleArea {
+        particles: [usize; 3],
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        rest_area: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        compliance: f64,
+    },
+
+    /// Gives a triangle of particles the elastic response of a continuous material.
+    Strain {
+        particles: [usize; 3],
+        /// inverse of the matrix whose columns are the triangle's two edges (from its first particle) at rest
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        rest_inverse: [[f64; 2]; 2],
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        rest_area: f64,
+        material: Material,
+    },
+}
+
+/// The constitutive model that relates a material's strain to its energy.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum StrainModel {
+    /// Ψ = μ|E|² + ½λtr(E)², with the Green strain E = ½(FᵀF - I). Good for large rotations, but only small
+    /// strains, as it softens under compression.
+    StVenantKirchhoff,
+    /// Ψ = ½μ(tr(FᵀF) - 2) + ½λ'(det(F) - 1 - μ/λ')², with λ' = λ + μ, which stays well behaved under large
+    /// strains.
+    NeoHookean,
+}
+
+/// The elastic properties of a material, for `Constraint::Strain`.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Material {
+    pub model: StrainModel,
+    /// stiffness of the material (E), per unit of thickness
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub youngs_modulus: f64,
+    /// how much the material contracts sideways when stretched (ν), less than 0.5
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub poisson_ratio: f64,
+}
+
+impl Material {
+    /// A material with a constitutive model, a Young's modulus, and a Poisson ratio.
+    pub fn new(model: StrainModel, youngs_modulus: f64, poisson_ratio: f64) -> Material {
+        Material {
+            model,
+            youngs_modulus,
+            poisson_ratio,
+        }
+    }
+
+    /// The Lamé parameters (μ, λ) of the material, in plane strain.
+    pub fn lame(self: &Self) -> (f64, f64) {
+        let (e, nu) = (self.youngs_modulus, self.poisson_ratio);
+        (
+            e / (2.0 * (1.0 + nu)),
+            e * nu / ((1.0 + nu) * (1.0 - 2.0 * nu)),
+        )
+    }
 }
 
 impl Constraint {
-    pub fn project(self: &Self, dt: f64) {
+    /// A distance constraint that keeps two particles at their current distance apart.
+    pub fn distance(
+        a: usize,
+        b: usize,
+        particles: &[Particle],
+        boundary: &Boundary,
+        compliance: f64,
+    ) -> Constraint {
+        Constraint::Distance {
+            particles: (a, b),
+            length: boundary
+                .displacement(particles[a].pos, particles[b].pos)
+                .mag(),
+            compliance,
+        }
+    }
+
+    /// A triangle area constraint that keeps a triangle at its current area.
+    pub fn triangle_area(
+        triangle: [usize; 3],
+        particles: &[Particle],
+        boundary: &Boundary,
+        compliance: f64,
+    ) -> Constraint {
+        let (e1, e2) = edges(triangle, particles, boundary);
+        Constraint::TriangleArea {
+            particles: triangle,
+            rest_area: e1.cross(&e2) / 2.0,
+            compliance,
+        }
+    }
+
+    /// A strain constraint that uses a triangle's current shape as its rest shape.
+    pub fn strain(
+        triangle: [usize; 3],
+        particles: &[Particle],
+        boundary: &Boundary,
+        material: Material,
+    ) -> Constraint {
+        let (e1, e2) = edges(triangle, particles, boundary);
+        let determinant = e1.cross(&e2);
+        Constraint::Strain {
+            particles: triangle,
+            rest_inverse: [
+                [e2.y / determinant, -e2.x / determinant],
+                [-e1.y / determinant, e1.x / determinant],
+            ],
+            rest_area: determinant.abs() / 2.0,
+            material,
+        }
+    }
+
+    pub fn project(self: &Self, particles: &mut [Particle], boundary: &Boundary, dt: f64) {
         match self {
             Constraint::Constraint { .. } => Constraint::solver(self, dt),
+            Constraint::Distance {
+                particles: (a, b),
+                length,
+                compliance,
+            } => {
+                let offset = boundary.displacement(particles[*a].pos, particles[*b].pos);
+                let distance = offset.mag();
+                let (w_a, w_b) = (particles[*a].inverse_mass(), particles[*b].inverse_mass());
+                let denominator = w_a + w_b + compliance / dt.powi(2);
+                if distance == 0.0 || denominator == 0.0 {
+                    return;
+                }
+                let normal = offset / distance;
+                let lambda = -(distance - length) / denominator;
+                particles[*a].pos += normal * (lambda * w_a);
+                particles[*b].pos -= normal * (lambda * w_b);
+            }
+            Constraint::TriangleArea {
+                particles: triangle,
+                rest_area,
+                compliance,
+            } => {
+                let (e1, e2) = edges(*triangle, particles, boundary);
+                let area = e1.cross(&e2) / 2.0;
+                // the gradient of a triangle's area with respect to a vertex is half of the opposite edge
+                // (going counterclockwise), rotated a quarter turn towards the vertex
+                let opposite = [e2 - e1, e2 * -1.0, e1];
+                let gradients = opposite.map(|edge| Vec2::new(-edge.y, edge.x) / 2.0);
+                solve(
+                    particles,
+                    triangle,
+                    gradients,
+                    area - rest_area,
+                    *compliance,
+                    dt,
+                );
+            }
+            Constraint::Strain {
+                particles: triangle,
+                rest_inverse,
+                rest_area,
+                material,
+            } => {
+                let (e1, e2) = edges(*triangle, particles, boundary);
+                let f = multiply([[e1.x, e2.x], [e1.y, e2.y]], *rest_inverse);
+                let (mu, lambda) = material.lame();
+                match material.model {
+                    StrainModel::StVenantKirchhoff => {
+                        // Green strain E = ½(FᵀF - I), and the first Piola-Kirchhoff stress P = F(2μE + λtr(E)I)
+                        let c = multiply(transpose(f), f);
+                        let strain = [
+                            [(c[0][0] - 1.0) / 2.0, c[0][1] / 2.0],
+                            [c[1][0] / 2.0, (c[1][1] - 1.0) / 2.0],
+                        ];
+                        let trace = strain[0][0] + strain[1][1];
+                        let energy = mu
+                            * (strain[0][0].powi(2)
+                                + strain[0][1].powi(2)
+                                + strain[1][0].powi(2)
+                                + strain[1][1].powi(2))
+                            + lambda / 2.0 * trace.powi(2);
+                        let constraint = (2.0 * rest_area * energy).sqrt();
+                        if constraint < 1.0e-12 {
+                            return;
+                        }
+                        let stress = multiply(
+                            f,
+                            [
+                                [
+                                    2.0 * mu * strain[0][0] + lambda * trace,
+                                    2.0 * mu * strain[0][1],
+                                ],
+                                [
+                                    2.0 * mu * strain[1][0],
+                                    2.0 * mu * strain[1][1] + lambda * trace,
+                                ],
+                            ],
+                        );
+                        let gradients =
+                            vertex_gradients(stress, *rest_inverse, rest_area / constraint);
+                        // C²/2 with a compliance of 1 is the strain energy AΨ
+                        solve(particles, triangle, gradients, constraint, 1.0, dt);
+                    }
+                    StrainModel::NeoHookean => {
+                        // the deviatoric part, ½μA·tr(FᵀF), as C = |F| with a compliance of 1/(μA)
+                        let norm =
+                            (f[0][0].powi(2) + f[0][1].powi(2) + f[1][0].powi(2) + f[1][1].powi(2))
+                                .sqrt();
+                        if norm > 0.0 && mu > 0.0 {
+                            let gradients = vertex_gradients(f, *rest_inverse, 1.0 / norm);
+                            solve(
+                                particles,
+                                triangle,
+                                gradients,
+                                norm,
+                                1.0 / (mu * rest_area),
+                                dt,
+                            );
+                        }
+                        // the hydrostatic part, ½λ'A(det(F) - γ)², where γ = 1 + μ/λ' makes the rest shape
+                        // stress free, and λ' = λ + μ makes small strains match linear elasticity
+                        let (e1, e2) = edges(*triangle, particles, boundary);
+                        let f = multiply([[e1.x, e2.x], [e1.y, e2.y]], *rest_inverse);
+                        let lambda = lambda + mu;
+                        if lambda > 0.0 {
+                            let determinant = f[0][0] * f[1][1] - f[0][1] * f[1][0];
+                            let cofactor = [[f[1][1], -f[1][0]], [-f[0][1], f[0][0]]];
+                            let gradients = vertex_gradients(cofactor, *rest_inverse, 1.0);
+                            solve(
+                                particles,
+                                triangle,
+                                gradients,
+                                determinant - 1.0 - mu / lambda,
+                                1.0 / (lambda * rest_area),
+                                dt,
+                            );
+                        }
+                    }
+                }
+            }
             _ => (),
         }
     }
 
     pub fn solver(_constraint: &Constraint, _dt: f64) {}
 }
+
+/// The two edges of a triangle, from its first particle to its second and third.
+fn edges(triangle: [usize; 3], particles: &[Particle], boundary: &Boundary) -> (Vec2, Vec2) {
+    let [a, b, c] = triangle;
+    (
+        boundary.displacement(particles[b].pos, particles[a].pos),
+        boundary.displacement(particles[c].pos, particles[a].pos),
+    )
+}
+
+/// Turn the derivative of a function of the deformation gradient, ∂f/∂F, into its gradient with respect to each
+/// of a triangle's vertices (scaled by `scale`).
+fn vertex_gradients(
+    derivative: [[f64; 2]; 2],
+    rest_inverse: [[f64; 2]; 2],
+    scale: f64,
+) -> [Vec2; 3] {
+    let h = multiply(derivative, transpose(rest_inverse));
+    let (g1, g2) = (
+        Vec2::new(h[0][0], h[1][0]) * scale,
+        Vec2::new(h[0][1], h[1][1]) * scale,
+    );
+    [(g1 + g2) * -1.0, g1, g2]
+}
+
+/// Take a single XPBD step for a constraint with a value and gradients.
+fn solve(
+    particles: &mut [Particle],
+    indices: &[usize],
+    gradients: impl IntoIterator<Item = Vec2> + Clone,
+    constraint: f64,
+    compliance: f64,
+    dt: f64,
+) {
+    let denominator = indices
+        .iter()
+        .zip(gradients.clone())
+        .map(|(&index, gradient)| particles[index].inverse_mass() * gradient.mag_squared())
+        .sum::<f64>()
+        + compliance / dt.powi(2);
+    if denominator == 0.0 {
+        return;
+    }
+    let lambda = -constraint / denominator;
+    for (&index, gradient) in indices.iter().zip(gradients) {
+        let inverse_mass = particles[index].inverse_mass();
+        particles[index].pos += gradient * (lambda * inverse_mass);
+    }
+}
+
+fn multiply(a: [[f64; 2]; 2], b: [[f64; 2]; 2]) -> [[f64; 2]; 2] {
+    [
+        [
+            a[0][0] * b[0][0] + a[0][1] * b[1][0],
+            a[0][0] * b[0][1] + a[0][1] * b[1][1],
+        ],
+        [
+            a[1][0] * b[0][0] + a[1][1] * b[1][0],
+            a[1][0] * b[0][1] + a[1][1] * b[1][1],
+        ],
+    ]
+}
+
+fn transpose(a: [[f64; 2]; 2]) -> [[f64; 2]; 2] {
+    [[a[0][0], a[1][0]], [a[0][1], a[1][1]]]
+}
@@ -0,0 +1,255 @@
+//! Provides conservation diagnostics, which measure how well a simulation conserves mass, momentum, and energy.
+//!
+//! `System::diagnostics` measures every conserved quantity of a system at once, as `Diagnostics`. A
+//! `DiagnosticsLog` keeps a time series of these, can write it out as CSV, and can check how far each quantity has
+//! drifted from its initial value, either returning the first violation or panicking (for use in tests).
+//!
+//! Drift is measured relative to the initial value, |Q - Q₀| / |Q₀|, or absolutely if the quantity started at zero
+//! (like the momentum of a system at rest). Energy is the exception: potential energy (like m·g·h) depends on where
+//! the origin is, so the same error in the total energy would look larger or smaller depending on where the system
+//! is placed. Its drift is instead measured relative to the kinetic energy, |E - E₀| / max(KE₀, KE), which doesn't
+//! depend on the origin, or absolutely if the system has been at rest the whole time.
+//!
+//! Not every quantity is conserved by every system: walls and static colliders exert external forces (so momentum
+//! isn't conserved), thermostats add and remove energy, and angular momentum (which is measured about the origin)
+//! isn't conserved in a periodic box. Particles with infinite mass are left out of the mass and momentum, since
+//! they're pinned in place.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::physics::diagnostics::{DiagnosticsLog, Quantity};
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! let mut sim = System::new();
+//! sim.add_particle(Particle::new().pos(-1.0, 0.0).vel(0.0, 1.0));
+//! sim.add_particle(Particle::new().pos(1.0, 0.0).vel(0.0, -1.0));
+//!
+//! let mut log = DiagnosticsLog::new();
+//! log.sample(&sim);
+//! for _ in 0..100 {
+//!     sim.step_forward(0.01);
+//!     log.sample(&sim);
+//! }
+//!
+//! log.assert_conserved(Quantity::Mass, 1e-12);
+//! log.assert_conserved(Quantity::Momentum, 1e-12);
+//! log.assert_conserved(Quantity::AngularMomentum, 1e-9);
+//! assert!(log.check(Quantity::Energy, 1e-9).is_ok());
+//! ```
+
+use crate::physics::system::System;
+use crate::vec2::Vec2;
+
+use std::fmt;
+use std::io::{self, Write};
+use std::path::Path;
+
+/// The conserved quantities of a system, measured at some moment.
+#[derive(Clone, Copy)]
+pub struct Diagnostics {
+    /// the simulation time that the diagnostics were measured at
+    pub time: f64,
+    /// total mass of the particles
+    pub mass: f64,
+    /// total linear momentum, Σmv
+    pub momentum: Vec2,
+    /// total angular momentum about the origin, Σr×mv
+    pub angular_momentum: f64,
+    /// total kinetic energy, Σ½mv²
+    pub kinetic_energy: f64,
+    /// total potential energy of the forces that define one
+    pub potential_energy: f64,
+    /// total thermal energy, Σm·c·T
+    pub thermal_energy: f64,
+    /// center of mass of the particles
+    pub center_of_mass: Vec2,
+}
+
+impl Diagnostics {
+    /// The total mechanical energy, kinetic plus potential.
+    pub fn total_energy(self: &Self) -> f64 {
+        self.kinetic_energy + self.potential_energy
+    }
+
+    /// How far a quantity has drifted from its value in earlier diagnostics, relative to the scale of the quantity
+    /// (see the module documentation).
+    pub fn drift(self: &Self, initial: &Diagnostics, quantity: Quantity) -> f64 {
+        let change = (self.value(quantity) - initial.value(quantity)).mag();
+        let scale = match quantity {
+            Quantity::Energy => initial.kinetic_energy.max(self.kinetic_energy),
+            _ => initial.value(quantity).mag(),
+        };
+        if scale == 0.0 {
+            change
+        } else {
+            change / scale
+        }
+    }
+
+    /// Check that a quantity hasn't drifted from its value in earlier diagnostics by more than a tolerance.
+    pub fn check(
+        self: &Self,
+        initial: &Diagnostics,
+        quantity: Quantity,
+        tolerance: f64,
+    ) -> Result<(), Violation> {
+        let drift = self.drift(initial, quantity);
+        // written so that a NaN drift fails too
+        if drift <= tolerance {
+            Ok(())
+        } else {
+            Err(Violation {
+                quantity,
+                time: self.time,
+                drift,
+                tolerance,
+            })
+        }
+    }
+
+    /// The value of a quantity, as a vector so that scalars and vectors can be compared the same way.
+    fn value(self: &Self, quantity: Quantity) -> Vec2 {
+        match quantity {
+            Quantity::Mass => Vec2::new(self.mass, 0.0),
+            Quantity::Momentum => self.momentum,
+            Quantity::AngularMomentum => Vec2::new(self.angular_momentum, 0.0),
+            Quantity::Energy => Vec2::new(self.total_energy(), 0.0),
+        }
+    }
+}
+
+/// A quantity that a system should conserve.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Quantity {
+    Mass,
+    Momentum,
+    AngularMomentum,
+    /// the total mechanical energy, kinetic plus potential
+    Energy,
+}
+
+impl fmt::Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Quantity::Mass => write!(f, "mass"),
+            Quantity::Momentum => write!(f, "linear momentum"),
+            Quantity::AngularMomentum => write!(f, "angular momentum"),
+            Quantity::Energy => write!(f, "energy"),
+        }
+    }
+}
+
+/// A quantity that drifted by more than its tolerance.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Violation {
+    pub quantity: Quantity,
+    /// the simulation time that the drift was measured at
+    pub time: f64,
+    pub drift: f64,
+    pub tolerance: f64,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "the {} drifted by {:e} at time {} (tolerance {:e})",
+            self.quantity, self.drift, self.time, self.tolerance
+        )
+    }
+}
+
+impl std::error::Error for Violation {}
+
+/// A time series of diagnostics, sampled from a system as it steps forward.
+#[derive(Clone, Default)]
+pub struct DiagnosticsLog {
+    /// the diagnostics, in the order that they were sampled
+    pub samples: Vec<Diagnostics>,
+}
+
+impl DiagnosticsLog {
+    /// An empty log.
+    pub fn new() -> DiagnosticsLog {
+        DiagnosticsLog::default()
+    }
+
+    /// Measure a system's diagnostics and add them to the log. The first sample is the one that drift is measured
+    /// from.
+    pub fn sample(self: &mut Self, system: &System) -> &Diagnostics {
+        self.samples.push(system.diagnostics());
+        self.samples.last().unwrap()
+    }
+
+    /// The largest drift of a quantity from its initial value over the whole log.
+    pub fn max_drift(self: &Self, quantity: Quantity) -> f64 {
+        let initial = match self.samples.first() {
+            Some(initial) => initial,
+            None => return 0.0,
+        };
+        self.samples
+            .iter()
+            .map(|sample| sample.drift(initial, quantity))
+            .fold(0.0, |max, drift| {
+                if drift > max || drift.is_nan() {
+                    drift
+                } else {
+                    max
+                }
+            })
+    }
+
+    /// Check that a quantity never drifted from its initial value by more than a tolerance, returning the first
+    /// sample where it did.
+    pub fn check(self: &Self, quantity: Quantity, tolerance: f64) -> Result<(), Violation> {
+        let initial = match self.samples.first() {
+            Some(initial) => initial,
+            None => return Ok(()),
+        };
+        self.samples
+            .iter()
+            .try_for_each(|sample| sample.check(initial, quantity, tolerance))
+    }
+
+    /// Panic if a quantity ever drifted from its initial value by more than a tolerance.
+    #[track_caller]
+    pub fn assert_conserved(self: &Self, quantity: Quantity, tolerance: f64) {
+        if let Err(violation) = self.check(quantity, tolerance) {
+            panic!("{violation}");
+        }
+    }
+
+    /// Write the log as CSV, with one row per sample and the drift of the total energy in the last column.
+    pub fn write_csv(self: &Self, mut writer: impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "time,mass,momentum_x,momentum_y,angular_momentum,kinetic,potential,thermal,total,\
+            center_x,center_y,drift"
+        )?;
+        for sample in &self.samples {
+            writeln!(
+                writer,
+                "{},{},{},{},{},{},{},{},{},{},{},{}",
+                sample.time,
+                sample.mass,
+                sample.momentum.x,
+                sample.momentum.y,
+                sample.angular_momentum,
+                sample.kinetic_energy,
+                sample.potential_energy,
+                sample.thermal_energy,
+                sample.total_energy(),
+                sample.center_of_mass.x,
+                sample.center_of_mass.y,
+                sample.drift(&self.samples[0], Quantity::Energy)
+            )?;
+        }
+        writer.flush()
+    }
+
+    /// Write the log to a CSV file.
+    pub fn save_csv(self: &Self, path: impl AsRef<Path>) -> io::Result<()> {
+        self.write_csv(io::BufWriter::new(std::fs::File::create(path)?))
+    }
+}
@@ -0,0 +1,272 @@
+//! Provides Ewald summation, for Coulomb interactions between charged particles in a periodic box.
+//!
+//! In a periodic box every charge interacts with every periodic image of every other charge. The Coulomb
+//! potential decays too slowly for that sum to be cut off, and it only converges conditionally. Ewald summation
+//! splits 1/r = erfc(αr)/r + erf(αr)/r. The first part is short ranged, so it's summed directly in real space
+//! (with the minimum image convention). The second part is smooth, so it's summed in reciprocal space over the
+//! box's wave vectors.
+//!
+//! The particles live in a plane but interact through the three dimensional 1/r potential, so this is the
+//! two dimensionally periodic Ewald sum from Parry. 1975. [Link](https://doi.org/10.1016/0039-6028(75)90102-7).
+//! For a charge neutral box with area A, the energy is
+//!
+//! E = ½ΣΣ' qᵢqⱼ erfc(αr)/r + (π/A) Σ_{k≠0} erfc(k/2α)/k |S(k)|² - (α/√π) Σqᵢ², where S(k) = Σqⱼe^(ik·rⱼ)
+//!
+//! The splitting parameter α, and the real and reciprocal space cutoffs, are all chosen from an accuracy, which is
+//! roughly the relative error of the truncated sums. The real space cutoff is always half of the box's size.
+//!
+//! # Example
+//!
+//! The Madelung constant of a square lattice of alternating charges is 1.6155426...
+//! ```rust
+//! use rusty_particle_physics_2d::physics::ewald::Ewald;
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! let mut particles = Vec::new();
+//! for i in 0..4 {
+//!     for j in 0..4 {
+//!         let charge = if (i + j) % 2 == 0 { 1.0 } else { -1.0 };
+//!         particles.push(Particle::new().pos(i as f64, j as f64).charge(charge));
+//!     }
+//! }
+//! let boundary = Boundary::Periodic {
+//!     min: Vec2::new(-0.5, -0.5),
+//!     max: Vec2::new(3.5, 3.5),
+//! };
+//! let energy = Ewald::new(1.0).accuracy(1.0e-12).energy(&particles, &boundary);
+//! let madelung = -2.0 * energy / particles.len() as f64;
+//! assert!((madelung - 1.615_542_6).abs() < 1.0e-7);
+//! ```
+
+use crate::physics::boundary::Boundary;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+use std::f64::consts::PI;
+
+/// Ewald summation of the Coulomb interactions between every charged particle.
+#[derive(Clone, Copy)]
+pub struct Ewald {
+    /// Coulomb's constant (k)
+    pub constant: f64,
+    /// roughly the relative error that the real and reciprocal space sums are truncated at
+    pub accuracy: f64,
+}
+
+/// The splitting parameter and cutoffs for a particular box.
+struct Split {
+    alpha: f64,
+    real_cutoff: f64,
+    wave_vectors: Vec<Vec2>,
+    area: f64,
+}
+
+impl Ewald {
+    /// Ewald summation with a Coulomb's constant, and an accuracy of 1e-6.
+    pub fn new(constant: f64) -> Ewald {
+        Ewald {
+            constant,
+            accuracy: 1.0e-6,
+        }
+    }
+
+    /// A builder method to give the summation a specific accuracy after creating it.
+    pub fn accuracy(mut self: Self, accuracy: f64) -> Ewald {
+        self.accuracy = accuracy;
+        self
+    }
+
+    /// The electrostatic energy of the particles. In an open boundary this is just the direct Coulomb sum.
+    pub fn energy(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        let charged = charged(particles);
+        let split = match self.split(boundary) {
+            Some(split) => split,
+            None => {
+                let mut energy = 0.0;
+                for (k, &i) in charged.iter().enumerate() {
+                    for &j in &charged[k + 1..] {
+                        let r = (particles[i].pos - particles[j].pos).mag();
+                        energy += particles[i].charge * particles[j].charge / r;
+                    }
+                }
+                return self.constant * energy;
+            }
+        };
+
+        let mut real = 0.0;
+        for (k, &i) in charged.iter().enumerate() {
+            for &j in &charged[k + 1..] {
+                let r = boundary
+                    .displacement(particles[i].pos, particles[j].pos)
+                    .mag();
+                if r < split.real_cutoff && r > 0.0 {
+                    real += particles[i].charge * particles[j].charge * erfc(split.alpha * r) / r;
+                }
+            }
+        }
+
+        let mut reciprocal = 0.0;
+        for k in &split.wave_vectors {
+            let (cos, sin) = structure_factor(particles, &charged, *k);
+            let magnitude = k.mag();
+            reciprocal +=
+                erfc(magnitude / (2.0 * split.alpha)) / magnitude * (cos.powi(2) + sin.powi(2));
+        }
+        reciprocal *= PI / split.area;
+
+        let self_energy = split.alpha / PI.sqrt()
+            * charged
+                .iter()
+                .map(|&i| particles[i].charge.powi(2))
+                .sum::<f64>();
+
+        self.constant * (real + reciprocal - self_energy)
+    }
+
+    /// The electrostatic force on every particle (zero for the uncharged ones).
+    pub fn forces(self: &Self, particles: &[Particle], boundary: &Boundary) -> Vec<Vec2> {
+        let charged = charged(particles);
+        let mut forces = vec![Vec2::zero(); particles.len()];
+        let split = match self.split(boundary) {
+            Some(split) => split,
+            None => {
+                for (k, &i) in charged.iter().enumerate() {
+                    for &j in &charged[k + 1..] {
+                        let r = particles[i].pos - particles[j].pos;
+                        let force = r
+                            * (self.constant * particles[i].charge * particles[j].charge
+                                / r.mag().powi(3));
+                        forces[i] += force;
+                        forces[j] -= force;
+                    }
+                }
+                return forces;
+            }
+        };
+
+        for (k, &i) in charged.iter().enumerate() {
+            for &j in &charged[k + 1..] {
+                let offset = boundary.displacement(particles[i].pos, particles[j].pos);
+                let r = offset.mag();
+                if r >= split.real_cutoff || r == 0.0 {
+                    continue;
+                }
+                // -d/dr of erfc(αr)/r
+                let magnitude = erfc(split.alpha * r) / r.powi(2)
+                    + 2.0 * split.alpha / PI.sqrt() * (-(split.alpha * r).powi(2)).exp() / r;
+                let force = offset
+                    * (self.constant * particles[i].charge * particles[j].charge * magnitude / r);
+                forces[i] += force;
+                forces[j] -= force;
+            }
+        }
+
+        for k in &split.wave_vectors {
+            let (cos, sin) = structure_factor(particles, &charged, *k);
+            let magnitude = k.mag();
+            let weight = 2.0 * PI / split.area * erfc(magnitude / (2.0 * split.alpha)) / magnitude;
+            for &i in &charged {
+                let phase = k.dot(&particles[i].pos);
+                // Im(S(k)* e^(ik·rᵢ))
+                let imaginary = cos * phase.sin() - sin * phase.cos();
+                forces[i] += *k * (self.constant * weight * particles[i].charge * imaginary);
+            }
+        }
+        forces
+    }
+
+    /// Choose the splitting parameter and cutoffs for a periodic box, or None for an open boundary.
+    fn split(self: &Self, boundary: &Boundary) -> Option<Split> {
+        let (min, max) = match *boundary {
+            Boundary::Open => return None,
+            Boundary::Periodic { min, max } => (min, max),
+        };
+        let size = max - min;
+        let real_cutoff = size.x.min(size.y) / 2.0;
+        // erfc(αr_c) ≈ exp(-(αr_c)²) and erfc(k_max/2α) ≈ exp(-(k_max/2α)²), set both to the accuracy
+        let decay = (-self.accuracy.clamp(f64::MIN_POSITIVE, 0.5).ln()).sqrt();
+        let alpha = decay / real_cutoff;
+        let k_max = 2.0 * alpha * decay;
+
+        let unit = Vec2::new(2.0 * PI / size.x, 2.0 * PI / size.y);
+        let (m_max, n_max) = (
+            (k_max / unit.x).ceil() as i64,
+            (k_max / unit.y).ceil() as i64,
+        );
+        let mut wave_vectors = Vec::new();
+        for m in -m_max..=m_max {
+            for n in -n_max..=n_max {
+                let k = Vec2::new(m as f64 * unit.x, n as f64 * unit.y);
+                if (m, n) != (0, 0) && k.mag() <= k_max {
+                    wave_vectors.push(k);
+                }
+            }
+        }
+        Some(Split {
+            alpha,
+            real_cutoff,
+            wave_vectors,
+            area: size.x * size.y,
+        })
+    }
+}
+
+/// The indices of every particle with a non-zero charge.
+fn charged(particles: &[Particle]) -> Vec<usize> {
+    (0..particles.len())
+        .filter(|&index| particles[index].charge != 0.0)
+        .collect()
+}
+
+/// The real and imaginary parts of the structure factor S(k) = Σqⱼe^(ik·rⱼ).
+fn structure_factor(particles: &[Particle], charged: &[usize], k: Vec2) -> (f64, f64) {
+    charged.iter().fold((0.0, 0.0), |(cos, sin), &j| {
+        let phase = k.dot(&particles[j].pos);
+        (
+            cos + particles[j].charge * phase.cos(),
+            sin + particles[j].charge * phase.sin(),
+        )
+    })
+}
+
+/// The complementary error function, erfc(x) = 1 - erf(x), accurate to double precision.
+///
+/// A series is used for small x, and a continued fraction for large x, where the series would lose precision.
+pub fn erfc(x: f64) -> f64 {
+    if x < 0.0 {
+        return 2.0 - erfc(-x);
+    }
+    if x < 3.0 {
+        // erf(x) = 2/√π e^(-x²) Σ 2ⁿx^(2n+1)/(1·3·...·(2n+1)), which only has positive terms
+        let mut term = x;
+        let mut sum = x;
+        let mut n = 0.0;
+        while term > sum * 1.0e-17 {
+            n += 1.0;
+            term *= 2.0 * x.powi(2) / (2.0 * n + 1.0);
+            sum += term;
+        }
+        1.0 - 2.0 / PI.sqrt() * (-x.powi(2)).exp() * sum
+    } else {
+        // erfc(x) = e^(-x²)/√π · 1/(x + (1/2)/(x + 1/(x + (3/2)/(x + ...)))), evaluated with Lentz's method
+        let tiny = 1.0e-300;
+        let mut fraction = x;
+        let mut c = x;
+        let mut d = 0.0;
+        for n in 1..200 {
+            let a = n as f64 / 2.0;
+            d = x + a * d;
+            d = if d == 0.0 { 1.0 / tiny } else { 1.0 / d };
+            c = x + a / c;
+            if c == 0.0 {
+                c = tiny;
+            }
+            let delta = c * d;
+            fraction *= delta;
+            if (delta - 1.0).abs() < 1.0e-16 {
+                break;
+            }
+        }
+        (-x.powi(2)).exp() / PI.sqrt() / fraction
+    }
+}
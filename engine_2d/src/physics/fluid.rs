@@ -0,0 +1,551 @@
+//! Provides position based fluids (PBF), from Macklin, Müller. 2013. [Link](https://doi.org/10.1145/2461912.2461984).
+//!
+//! Incompressibility is enforced by a density constraint on every fluid particle,
+//! $$C_i = \frac{\rho_i}{\rho_0} - 1, \qquad \rho_i = \Sigma_j m_j W(\vec{x}_i - \vec{x}_j, h)$$
+//! which is solved in the same position based manner as every other constraint in the engine. Density is
+//! estimated with the poly6 kernel, and gradients use the spiky kernel.
+//!
+//! Only compression is corrected (C > 0), so the fluid can freely separate and splash. An artificial pressure
+//! term is added to the position corrections to counter the tensile instability, which would otherwise make
+//! particles clump together at the fluid's surface.
+//!
+//! After the velocities have been updated from the particle displacements, XSPH viscosity smooths the velocity
+//! field, and vorticity confinement puts back some of the swirling motion that the damping of the position
+//! based solver removes. In 2d the vorticity is a scalar, ω = ∇ × v.
+//!
+//! A fluid can also be given `Viscoelasticity`, which turns it into a material like wax or lava, following
+//! Takahashi, Nishita, Fujishiro. 2014. [Link](https://doi.org/10.1016/j.cag.2014.06.002). Each particle
+//! keeps a rest shape of its neighborhood that it's pulled back towards through shape matching (elasticity),
+//! its velocity is smoothed with its neighbors' (viscosity), and heat is conducted between neighbors. The
+//! stiffness and viscosity both depend on the particle's temperature, so the material can melt and solidify.
+//!
+//! Any particle whose `group` is one of the fluid's groups is treated as a fluid particle.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::neighbor_grid::NeighborGrid;
+use crate::physics::particle::Particle;
+use crate::physics::rigid_body::polar_decomposition_angle;
+use crate::physics::sph;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+
+/// A position based fluid made from all of the particles in a set of groups.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Fluid {
+    /// the particle groups that belong to this fluid
+    pub groups: Vec<u32>,
+    /// the density that the fluid tries to keep (mass per unit area)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub rest_density: f64,
+    /// the smoothing kernel radius, h
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub kernel_radius: f64,
+    /// number of times the density constraints are solved each substep
+    pub iterations: u32,
+    /// constraint force mixing parameter, which prevents a division by zero for particles with few neighbors
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub relaxation: f64,
+    /// strength (k) of the artificial pressure
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub pressure_strength: f64,
+    /// exponent (n) of the artificial pressure
+    pub pressure_exponent: i32,
+    /// distance (Δq), as a fraction of the kernel radius, at which the artificial pressure is evaluated
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub pressure_distance: f64,
+    /// XSPH viscosity coefficient (c), where 0.0 is inviscid and 1.0 moves every particle with its neighbors. It's
+    /// ignored if the fluid is viscoelastic, whose viscosity depends on the temperature instead
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub viscosity: f64,
+    /// vorticity confinement strength (ε)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub vorticity: f64,
+    /// temperature dependent elasticity, viscosity, and heat conduction
+    pub viscoelasticity: Option<Viscoelasticity>,
+}
+
+impl Fluid {
+    /// Create a new fluid out of the particles in the given groups.
+    pub fn new(groups: Vec<u32>, rest_density: f64, kernel_radius: f64) -> Fluid {
+        Fluid {
+            groups,
+            rest_density,
+            kernel_radius,
+            iterations: 3,
+            relaxation: 1.0e-6,
+            pressure_strength: 0.1,
+            pressure_exponent: 4,
+            pressure_distance: 0.2,
+            viscosity: 0.0,
+            vorticity: 0.0,
+            viscoelasticity: None,
+        }
+    }
+
+    /// A builder method to give the fluid a specific number of solver iterations after creating it.
+    pub fn iterations(mut self: Self, iterations: u32) -> Fluid {
+        self.iterations = iterations;
+        self
+    }
+
+    /// A builder method to give the fluid a specific artificial pressure (k, n, Δq) after creating it.
+    /// A strength of 0.0 turns it off.
+    pub fn artificial_pressure(
+        mut self: Self,
+        strength: f64,
+        exponent: i32,
+        distance: f64,
+    ) -> Fluid {
+        self.pressure_strength = strength;
+        self.pressure_exponent = exponent;
+        self.pressure_distance = distance;
+        self
+    }
+
+    /// A builder method to give the fluid a specific XSPH viscosity coefficient after creating it. A viscoelastic
+    /// fluid ignores it, and uses `Viscoelasticity::viscosity` instead.
+    pub fn viscosity(mut self: Self, viscosity: f64) -> Fluid {
+        self.viscosity = viscosity;
+        self
+    }
+
+    /// A builder method to give the fluid a specific vorticity confinement strength after creating it.
+    pub fn vorticity(mut self: Self, vorticity: f64) -> Fluid {
+        self.vorticity = vorticity;
+        self
+    }
+
+    /// A builder method to make the fluid a viscoelastic material after creating it. Its temperature dependent
+    /// viscosity replaces the fluid's own `viscosity`.
+    pub fn viscoelasticity(mut self: Self, viscoelasticity: Viscoelasticity) -> Fluid {
+        self.viscoelasticity = Some(viscoelasticity);
+        self
+    }
+
+    /// Whether a particle belongs to this fluid.
+    pub fn contains(self: &Self, particle: &Particle) -> bool {
+        self.groups.contains(&particle.group)
+    }
+
+    /// Indices of all of the particles that belong to this fluid.
+    pub fn members(self: &Self, particles: &[Particle]) -> Vec<usize> {
+        (0..particles.len())
+            .filter(|&index| self.contains(&particles[index]))
+            .collect()
+    }
+
+    /// The SPH density estimate at each of the given fluid particles.
+    pub fn densities(
+        self: &Self,
+        particles: &[Particle],
+        members: &[usize],
+        boundary: &Boundary,
+    ) -> Vec<f64> {
+        let grid = NeighborGrid::from_particles_in(
+            particles,
+            members.iter().copied(),
+            self.kernel_radius,
+            boundary,
+        );
+        members
+            .iter()
+            .map(|&i| {
+                let neighbors = grid.neighbors(particles, particles[i].pos, self.kernel_radius);
+                self.density(particles, i, &neighbors, boundary)
+            })
+            .collect()
+    }
+
+    /// Move the fluid particles so that the fluid is incompressible (and elastic, if it's viscoelastic).
+    pub fn project(self: &mut Self, particles: &mut [Particle], boundary: &Boundary) {
+        let members = self.members(particles);
+        if members.is_empty() {
+            return;
+        }
+        let h = self.kernel_radius;
+        let grid = NeighborGrid::from_particles_in(particles, members.iter().copied(), h, boundary);
+        let neighbors: Vec<Vec<usize>> = members
+            .iter()
+            .map(|&i| grid.neighbors(particles, particles[i].pos, h))
+            .collect();
+        let correction_reference = sph::poly6((self.pressure_distance * h).powi(2), h);
+
+        let mut lambdas = vec![0.0; particles.len()];
+        let mut corrections = vec![Vec2::zero(); members.len()];
+        for _ in 0..self.iterations {
+            // find the scaling factor of each density constraint
+            for (k, &i) in members.iter().enumerate() {
+                let constraint =
+                    (self.density(particles, i, &neighbors[k], boundary) / self.rest_density - 1.0)
+                        .max(0.0);
+                let mut gradient_i = Vec2::zero();
+                let mut denominator = 0.0;
+                for &j in &neighbors[k] {
+                    if j == i {
+                        continue;
+                    }
+                    let gradient_j = sph::spiky_gradient(
+                        boundary.displacement(particles[i].pos, particles[j].pos),
+                        h,
+                    ) * (particles[j].mass / self.rest_density);
+                    gradient_i += gradient_j;
+                    denominator += gradient_j.mag_squared() / particles[j].mass;
+                }
+                denominator += gradient_i.mag_squared() / particles[i].mass;
+                lambdas[i] = -constraint / (denominator + self.relaxation);
+            }
+
+            // find the position corrections
+            for (k, &i) in members.iter().enumerate() {
+                let mut correction = Vec2::zero();
+                for &j in &neighbors[k] {
+                    if j == i {
+                        continue;
+                    }
+                    let r = boundary.displacement(particles[i].pos, particles[j].pos);
+                    let artificial_pressure = if self.pressure_strength == 0.0 {
+                        0.0
+                    } else {
+                        -self.pressure_strength
+                            * (sph::poly6(r.mag_squared(), h) / correction_reference)
+                                .powi(self.pressure_exponent)
+                    };
+                    correction += sph::spiky_gradient(r, h)
+                        * (particles[j].mass * (lambdas[i] + artificial_pressure)
+                            + particles[i].mass * lambdas[j]);
+                }
+                corrections[k] = correction / (particles[i].mass * self.rest_density);
+            }
+
+            for (k, &i) in members.iter().enumerate() {
+                particles[i].pos += corrections[k];
+            }
+        }
+
+        if let Some(viscoelasticity) = &mut self.viscoelasticity {
+            viscoelasticity.project(particles, &members, &neighbors, boundary);
+        }
+    }
+
+    /// Apply vorticity confinement and then XSPH viscosity to the velocities of the fluid particles.
+    pub fn correct_velocities(
+        self: &Self,
+        particles: &mut [Particle],
+        boundary: &Boundary,
+        dt: f64,
+    ) {
+        if self.viscosity == 0.0 && self.vorticity == 0.0 && self.viscoelasticity.is_none() {
+            return;
+        }
+        let members = self.members(particles);
+        let h = self.kernel_radius;
+        let grid = NeighborGrid::from_particles_in(particles, members.iter().copied(), h, boundary);
+        let neighbors: Vec<Vec<usize>> = members
+            .iter()
+            .map(|&i| grid.neighbors(particles, particles[i].pos, h))
+            .collect();
+        // m/ρ is the area that each particle represents
+        let mut areas = vec![0.0; particles.len()];
+        for (k, &i) in members.iter().enumerate() {
+            areas[i] = particles[i].mass / self.density(particles, i, &neighbors[k], boundary);
+        }
+
+        if self.vorticity != 0.0 {
+            let mut curls = vec![0.0; particles.len()];
+            for (k, &i) in members.iter().enumerate() {
+                for &j in &neighbors[k] {
+                    let gradient = sph::spiky_gradient(
+                        boundary.displacement(particles[i].pos, particles[j].pos),
+                        h,
+                    );
+                    curls[i] += areas[j] * gradient.cross(&(particles[j].vel - particles[i].vel));
+                }
+            }
+            for (k, &i) in members.iter().enumerate() {
+                // points towards increasing vorticity
+                let mut location = Vec2::zero();
+                for &j in &neighbors[k] {
+                    let gradient = sph::spiky_gradient(
+                        boundary.displacement(particles[i].pos, particles[j].pos),
+                        h,
+                    );
+                    location += gradient * (areas[j] * (curls[j].abs() - curls[i].abs()));
+                }
+                let magnitude = location.mag();
+                if magnitude > 0.0 {
+                    let n = location / magnitude;
+                    particles[i].vel += Vec2::new(n.y, -n.x) * (curls[i] * self.vorticity * dt);
+                }
+            }
+        }
+
+        if self.viscosity != 0.0 || self.viscoelasticity.is_some() {
+            let mut smoothing = vec![Vec2::zero(); members.len()];
+            for (k, &i) in members.iter().enumerate() {
+                for &j in &neighbors[k] {
+                    let weight = sph::poly6(
+                        (boundary.displacement(particles[i].pos, particles[j].pos)).mag_squared(),
+                        h,
+                    );
+                    smoothing[k] += (particles[j].vel - particles[i].vel) * (areas[j] * weight);
+                }
+            }
+            for (k, &i) in members.iter().enumerate() {
+                let viscosity = match &self.viscoelasticity {
+                    Some(viscoelasticity) => viscoelasticity.viscosity_at(particles[i].temperature),
+                    None => self.viscosity,
+                };
+                particles[i].vel += smoothing[k] * viscosity;
+            }
+        }
+
+        if let Some(viscoelasticity) = &self.viscoelasticity {
+            viscoelasticity.conduct_heat(particles, &members, &neighbors, &areas, boundary, h, dt);
+        }
+    }
+
+    fn density(
+        self: &Self,
+        particles: &[Particle],
+        i: usize,
+        neighbors: &[usize],
+        boundary: &Boundary,
+    ) -> f64 {
+        neighbors
+            .iter()
+            .map(|&j| {
+                particles[j].mass
+                    * sph::poly6(
+                        (boundary.displacement(particles[i].pos, particles[j].pos)).mag_squared(),
+                        self.kernel_radius,
+                    )
+            })
+            .sum()
+    }
+}
+
+/// A temperature dependent, viscoelastic, heat conducting material.
+///
+/// Below the solid temperature the material has its solid stiffness and viscosity, above the melting
+/// temperature it has its liquid stiffness and viscosity, and in between they are linearly interpolated.
+/// A solid remembers its rest shape, while a melting material flows plastically towards its current shape.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Viscoelasticity {
+    /// temperature at and below which the material is fully solid
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub solid_temperature: f64,
+    /// temperature at and above which the material is fully liquid
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub melt_temperature: f64,
+    /// shape matching stiffness of the solid, in [0, 1]
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub solid_stiffness: f64,
+    /// shape matching stiffness of the liquid, in [0, 1]
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub liquid_stiffness: f64,
+    /// XSPH viscosity coefficient of the solid
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub solid_viscosity: f64,
+    /// XSPH viscosity coefficient of the liquid
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub liquid_viscosity: f64,
+    /// thermal conductivity, with which heat flows between neighbors (it's the thermal diffusivity of a material
+    /// with a specific heat of 1)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub conductivity: f64,
+    /// for each particle, its neighborhood's rest positions relative to the neighborhood's rest center of mass
+    rest_shapes: HashMap<usize, Vec<(usize, Vec2)>>,
+}
+
+impl Viscoelasticity {
+    /// Create a new material that is solid below `solid_temperature` and liquid above `melt_temperature`.
+    pub fn new(solid_temperature: f64, melt_temperature: f64) -> Viscoelasticity {
+        Viscoelasticity {
+            solid_temperature,
+            melt_temperature,
+            solid_stiffness: 0.5,
+            liquid_stiffness: 0.0,
+            solid_viscosity: 0.5,
+            liquid_viscosity: 0.01,
+            conductivity: 0.0,
+            rest_shapes: HashMap::new(),
+        }
+    }
+
+    /// A builder method to give the material specific (solid, liquid) stiffnesses after creating it.
+    pub fn stiffness(mut self: Self, solid: f64, liquid: f64) -> Viscoelasticity {
+        self.solid_stiffness = solid;
+        self.liquid_stiffness = liquid;
+        self
+    }
+
+    /// A builder method to give the material specific (solid, liquid) viscosities after creating it.
+    pub fn viscosity(mut self: Self, solid: f64, liquid: f64) -> Viscoelasticity {
+        self.solid_viscosity = solid;
+        self.liquid_viscosity = liquid;
+        self
+    }
+
+    /// A builder method to give the material a specific thermal conductivity after creating it.
+    pub fn conductivity(mut self: Self, conductivity: f64) -> Viscoelasticity {
+        self.conductivity = conductivity;
+        self
+    }
+
+    /// How melted the material is at a temperature, from 0.0 (solid) to 1.0 (liquid).
+    pub fn liquid_fraction(self: &Self, temperature: f64) -> f64 {
+        if self.melt_temperature <= self.solid_temperature {
+            return if temperature >= self.melt_temperature {
+                1.0
+            } else {
+                0.0
+            };
+        }
+        ((temperature - self.solid_temperature) / (self.melt_temperature - self.solid_temperature))
+            .clamp(0.0, 1.0)
+    }
+
+    /// The shape matching stiffness at a temperature.
+    pub fn stiffness_at(self: &Self, temperature: f64) -> f64 {
+        let fraction = self.liquid_fraction(temperature);
+        self.solid_stiffness + (self.liquid_stiffness - self.solid_stiffness) * fraction
+    }
+
+    /// The XSPH viscosity coefficient at a temperature.
+    pub fn viscosity_at(self: &Self, temperature: f64) -> f64 {
+        let fraction = self.liquid_fraction(temperature);
+        self.solid_viscosity + (self.liquid_viscosity - self.solid_viscosity) * fraction
+    }
+
+    /// Pull every particle's neighborhood towards its rest shape, and let the rest shapes flow plastically.
+    fn project(
+        self: &mut Self,
+        particles: &mut [Particle],
+        members: &[usize],
+        neighbors: &[Vec<usize>],
+        boundary: &Boundary,
+    ) {
+        let mut displacements = vec![Vec2::zero(); particles.len()];
+        let mut counts = vec![0u32; particles.len()];
+
+        for (k, &i) in members.iter().enumerate() {
+            let fraction = self.liquid_fraction(particles[i].temperature);
+            let stiffness = self.stiffness_at(particles[i].temperature);
+            let shape = self
+                .rest_shapes
+                .entry(i)
+                .or_insert_with(|| capture_shape(particles, i, &neighbors[k], boundary));
+            if fraction >= 1.0 {
+                // a liquid has no memory of its shape
+                *shape = capture_shape(particles, i, &neighbors[k], boundary);
+                continue;
+            }
+
+            let center = shape_center(particles, i, shape, boundary);
+            let mut a = [[0.0; 2]; 2];
+            for &(j, q) in shape.iter() {
+                let p = boundary.displacement(particles[j].pos, center);
+                a[0][0] += particles[j].mass * p.x * q.x;
+                a[0][1] += particles[j].mass * p.x * q.y;
+                a[1][0] += particles[j].mass * p.y * q.x;
+                a[1][1] += particles[j].mass * p.y * q.y;
+            }
+            let angle = polar_decomposition_angle(a);
+
+            for (j, q) in shape.iter_mut() {
+                let goal = center + q.rotate(angle);
+                displacements[*j] += boundary.displacement(goal, particles[*j].pos) * stiffness;
+                counts[*j] += 1;
+                // plastic flow towards the current shape
+                *q += (boundary
+                    .displacement(particles[*j].pos, center)
+                    .rotate(-angle)
+                    - *q)
+                    * fraction;
+            }
+        }
+
+        for (i, particle) in particles.iter_mut().enumerate() {
+            if counts[i] > 0 {
+                particle.pos += displacements[i] / counts[i] as f64;
+            }
+        }
+    }
+
+    /// Conduct heat between neighboring particles, m·c·dT/dt = m·k∇²T, where each particle's temperature changes
+    /// according to its own heat capacity.
+    #[allow(clippy::too_many_arguments)]
+    fn conduct_heat(
+        self: &Self,
+        particles: &mut [Particle],
+        members: &[usize],
+        neighbors: &[Vec<usize>],
+        areas: &[f64],
+        boundary: &Boundary,
+        h: f64,
+        dt: f64,
+    ) {
+        if self.conductivity == 0.0 {
+            return;
+        }
+        let heat: Vec<f64> = members
+            .iter()
+            .enumerate()
+            .map(|(k, &i)| {
+                neighbors[k]
+                    .iter()
+                    .map(|&j| {
+                        // the heat that flows from j to i is the opposite of the heat that flows from i to j, so
+                        // none is created or destroyed
+                        let r = (boundary.displacement(particles[i].pos, particles[j].pos)).mag();
+                        let densities = particles[i].mass / areas[i] + particles[j].mass / areas[j];
+                        2.0 * particles[i].mass * particles[j].mass / densities
+                            * (particles[j].temperature - particles[i].temperature)
+                            * sph::viscosity_laplacian(r, h)
+                    })
+                    .sum::<f64>()
+                    * self.conductivity
+                    * dt
+            })
+            .collect();
+        for (k, &i) in members.iter().enumerate() {
+            particles[i].add_heat(heat[k]);
+        }
+    }
+}
+
+/// Record the current positions of particle i's neighborhood relative to its center of mass.
+fn capture_shape(
+    particles: &[Particle],
+    i: usize,
+    neighborhood: &[usize],
+    boundary: &Boundary,
+) -> Vec<(usize, Vec2)> {
+    let mut shape: Vec<(usize, Vec2)> = neighborhood.iter().map(|&j| (j, Vec2::zero())).collect();
+    let center = shape_center(particles, i, &shape, boundary);
+    for (j, q) in shape.iter_mut() {
+        *q = boundary.displacement(particles[*j].pos, center);
+    }
+    shape
+}
+
+/// The current center of mass of particle i's neighborhood, using the images of the neighbors that are closest
+/// to particle i.
+fn shape_center(
+    particles: &[Particle],
+    i: usize,
+    shape: &[(usize, Vec2)],
+    boundary: &Boundary,
+) -> Vec2 {
+    let origin = particles[i].pos;
+    let mut total_mass = 0.0;
+    let mut weighted = Vec2::zero();
+    for &(j, _) in shape {
+        total_mass += particles[j].mass;
+        weighted += boundary.displacement(particles[j].pos, origin) * particles[j].mass;
+    }
+    origin + weighted / total_mass
+}
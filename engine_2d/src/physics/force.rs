@@ -1,14 +1,31 @@
+use crate::physics::boundary::Boundary;
+use crate::physics::collision::StaticCollider;
+use crate::physics::ewald::Ewald;
+use crate::physics::neighbor_grid::NeighborGrid;
+use crate::physics::pair_table::PairTable;
+use crate::physics::particle::Particle;
+use crate::physics::potential::Potential;
+use crate::physics::registry::{ForceFunction, Named};
+use crate::physics::sph;
+use crate::vec2::Vec2;
+
 /// Force on a Particle or between interacting Particles
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Force {
     /// The fundamental type of 2d force.
     ///
     /// The other Force types usually return a configured GeneralForce or RawForce when being handled.
+    ///
+    /// Not implemented yet, so it doesn't exert any force.
     InteractionForce {},
 
     /// The other type of fundamental 2d force. However, this one is less realistic than InteractionForce.
     /// This is because it is not an interaction between two particles, meaning there can be no reaction force.
     /// No reaction force means that total linear momentum will not be conserved, unlike in the real universe.
-    RawForce {},
+    ///
+    /// The force on each particle is given by a named closure, so that it can be saved and loaded.
+    RawForce { function: Named<ForceFunction> },
 
     /// A general restoring force (F = -kx^n - bv) that attempts to satisfy a given constraint.
     ///
@@ -16,21 +33,461 @@ pub enum Force {
     /// - gravity { (Link)MaxDistance, G*mass*mass, -2, 0 }
     /// - dampened spring between Particles { LinkFixedDistance, stiffness, 1, 0.5 }
     /// - stiff pendulum { FixedDistance, 99, 1, 99 }
+    ///
+    /// Not implemented yet, so it doesn't exert any force.
     ConstraintForce {},
 
-    /// A simple downwards pull of gravity (F = -mg)
-    WorldGravity {},
+    /// A simple downwards pull of gravity (F = mg), on every particle with a finite mass
+    WorldGravity {
+        /// acceleration due to gravity (g), ex: (0, -9.81)
+        acceleration: Vec2,
+    },
+
+    /// Newtonian gravitational attraction between every pair of Particles (F = Gm₁m₂/r²). Particles with infinite
+    /// mass are left out, since they would pull with an infinite force.
+    Gravity {
+        /// gravitational constant (G)
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        constant: f64,
+    },
 
-    /// Newtonian gravitational attraction between two Particles
-    Gravity {},
+    /// Drag force from a still medium (F = -½ρC_dA|v|v), where the area A of a particle is its diameter.
+    /// [Wikipedia](https://en.wikipedia.org/wiki/Drag_equation)
+    Drag {
+        /// density of the medium (ρ)
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        density: f64,
+        /// drag coefficient (C_d)
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        coefficient: f64,
+    },
 
-    /// Drag force. [Wikipedia](https://en.wikipedia.org/wiki/Drag_equation)
-    Drag {},
+    /// Weakly compressible SPH (WCSPH) pressure and viscosity forces between the particles in a set of groups.
+    /// This is the force based alternative to a position based `Fluid`.
+    ///
+    /// The pressure comes from the Tait equation of state, p = B((ρ/ρ₀)^γ - 1), and the viscosity is the
+    /// laplacian form from Müller, Charypar, Gross. 2003. [Link](https://doi.org/10.2312/SCA03/154-159).
+    /// The stiffness B is usually chosen as ρ₀c²/γ, where c is about ten times the fastest expected speed,
+    /// which keeps density fluctuations around 1%.
+    Wcsph {
+        groups: Vec<u32>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        rest_density: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        kernel_radius: f64,
+        /// stiffness (B) of the Tait equation
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        stiffness: f64,
+        /// exponent (γ) of the Tait equation, usually 7
+        exponent: i32,
+        /// dynamic viscosity (μ)
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        viscosity: f64,
+    },
+
+    /// Surface tension and adhesion forces, from Akinci, Akinci, Teschner. 2013.
+    /// [Link](https://doi.org/10.1145/2508363.2508395).
+    ///
+    /// Particles in a pair of groups that appear in the `cohesion` table pull on each other with a cohesion
+    /// kernel, and are pushed to minimize the surface's curvature. Particles in a pair of groups that appear in
+    /// the `adhesion` table (usually a fluid group and a solid group) are pulled together, as are particles and
+    /// static colliders whose groups appear in the `adhesion` table.
+    ///
+    /// A pinned particle (with infinite mass), like a particle of a solid wall, counts as a particle of unit mass,
+    /// like a static collider does, and isn't pushed or pulled.
+    SurfaceTension {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        kernel_radius: f64,
+        /// rest density of the fluid, used to correct for particle deficiency at the surface
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        rest_density: f64,
+        /// cohesion strength (γ) for each pair of groups
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        cohesion: PairTable<f64>,
+        /// adhesion strength (β) for each pair of groups
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        adhesion: PairTable<f64>,
+    },
+
+    /// Pair potentials (Lennard-Jones, Morse, Coulomb, etc) between the particles in each pair of groups that
+    /// appears in the `potentials` table.
+    ///
+    /// Pairs further apart than the cutoff don't interact, and the potentials are shifted to be zero at the cutoff
+    /// so that the energy stays continuous. A cutoff of `f64::INFINITY` turns the cutoff off, in which case every
+    /// pair of particles interacts.
+    PairPotential {
+        potentials: PairTable<Potential>,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        cutoff: f64,
+    },
+
+    /// Coulomb forces between every charged particle, including all of their periodic images when the boundary is
+    /// periodic (using Ewald summation). In an open boundary every pair of charges interacts directly.
+    Ewald {
+        /// Coulomb's constant (k)
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        constant: f64,
+        /// roughly the relative error of the Ewald sums, ex: 1e-6
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        accuracy: f64,
+    },
 }
 
 impl Force {
     /// Send the calculated force to the Particle(s) as a Vec2
-    pub fn send(self: &Self) {
-        todo!();
+    pub fn send(
+        self: &Self,
+        particles: &mut [Particle],
+        colliders: &[StaticCollider],
+        boundary: &Boundary,
+    ) {
+        match self {
+            Force::Wcsph {
+                groups,
+                rest_density,
+                kernel_radius,
+                stiffness,
+                exponent,
+                viscosity,
+            } => wcsph(
+                particles,
+                boundary,
+                groups,
+                *rest_density,
+                *kernel_radius,
+                *stiffness,
+                *exponent,
+                *viscosity,
+            ),
+            Force::SurfaceTension {
+                kernel_radius,
+                rest_density,
+                cohesion,
+                adhesion,
+            } => surface_tension(
+                particles,
+                colliders,
+                boundary,
+                *kernel_radius,
+                *rest_density,
+                cohesion,
+                adhesion,
+            ),
+            Force::PairPotential { potentials, cutoff } => {
+                let mut forces = vec![Vec2::zero(); particles.len()];
+                for_each_pair(
+                    particles,
+                    boundary,
+                    potentials,
+                    *cutoff,
+                    |i, j, r, potential| {
+                        let distance = r.mag();
+                        let force = r / distance
+                            * potential.force(distance, particles[i].charge * particles[j].charge);
+                        forces[i] += force;
+                        forces[j] -= force;
+                    },
+                );
+                for (particle, force) in particles.iter_mut().zip(forces) {
+                    particle.add_force(force);
+                }
+            }
+            Force::Ewald { constant, accuracy } => {
+                let forces = Ewald::new(*constant)
+                    .accuracy(*accuracy)
+                    .forces(particles, boundary);
+                for (particle, force) in particles.iter_mut().zip(forces) {
+                    particle.add_force(force);
+                }
+            }
+            Force::RawForce { function } => {
+                let function = function
+                    .function()
+                    .expect("the raw force's closure should be resolved from a registry");
+                for particle in particles.iter_mut() {
+                    let force = function(particle);
+                    particle.add_force(force);
+                }
+            }
+            Force::WorldGravity { acceleration } => {
+                for particle in particles.iter_mut().filter(|p| p.mass.is_finite()) {
+                    let weight = *acceleration * particle.mass;
+                    particle.add_force(weight);
+                }
+            }
+            Force::Gravity { constant } => {
+                let mut forces = vec![Vec2::zero(); particles.len()];
+                for_each_massive_pair(particles, boundary, |i, j, r| {
+                    let distance = r.mag();
+                    let force =
+                        r * (-constant * particles[i].mass * particles[j].mass / distance.powi(3));
+                    forces[i] += force;
+                    forces[j] -= force;
+                });
+                for (particle, force) in particles.iter_mut().zip(forces) {
+                    particle.add_force(force);
+                }
+            }
+            Force::Drag {
+                density,
+                coefficient,
+            } => {
+                for particle in particles.iter_mut() {
+                    let drag = particle.vel
+                        * (-density * coefficient * particle.radius * particle.vel.mag());
+                    particle.add_force(drag);
+                }
+            }
+            Force::InteractionForce {} | Force::ConstraintForce {} => {}
+        }
+    }
+
+    /// The potential energy stored by the force. Forces that don't come from a potential (or whose potential
+    /// isn't tracked) have none.
+    pub fn potential_energy(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        match self {
+            Force::PairPotential { potentials, cutoff } => {
+                let mut energy = 0.0;
+                for_each_pair(
+                    particles,
+                    boundary,
+                    potentials,
+                    *cutoff,
+                    |i, j, r, potential| {
+                        energy += potential.shifted_energy(
+                            r.mag(),
+                            particles[i].charge * particles[j].charge,
+                            *cutoff,
+                        );
+                    },
+                );
+                energy
+            }
+            Force::Ewald { constant, accuracy } => Ewald::new(*constant)
+                .accuracy(*accuracy)
+                .energy(particles, boundary),
+            Force::WorldGravity { acceleration } => -particles
+                .iter()
+                .filter(|p| p.mass.is_finite())
+                .map(|p| acceleration.dot(&p.pos) * p.mass)
+                .sum::<f64>(),
+            Force::Gravity { constant } => {
+                let mut energy = 0.0;
+                for_each_massive_pair(particles, boundary, |i, j, r| {
+                    energy -= constant * particles[i].mass * particles[j].mass / r.mag();
+                });
+                energy
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Call `f(i, j, rᵢ - rⱼ)` once for every pair of distinct particles that both have a finite mass.
+fn for_each_massive_pair(
+    particles: &[Particle],
+    boundary: &Boundary,
+    mut f: impl FnMut(usize, usize, Vec2),
+) {
+    let massive: Vec<usize> = (0..particles.len())
+        .filter(|&index| particles[index].mass.is_finite())
+        .collect();
+    for (k, &i) in massive.iter().enumerate() {
+        for &j in &massive[k + 1..] {
+            let r = boundary.displacement(particles[i].pos, particles[j].pos);
+            if r.mag() > 0.0 {
+                f(i, j, r);
+            }
+        }
+    }
+}
+
+/// Call `f(i, j, rᵢ - rⱼ, potential)` once for every pair of distinct particles closer than the cutoff whose
+/// groups have a potential in the table.
+fn for_each_pair(
+    particles: &[Particle],
+    boundary: &Boundary,
+    potentials: &PairTable<Potential>,
+    cutoff: f64,
+    mut f: impl FnMut(usize, usize, Vec2, &Potential),
+) {
+    let members: Vec<usize> = (0..particles.len())
+        .filter(|&index| potentials.involves(particles[index].group))
+        .collect();
+    let mut visit = |i: usize, j: usize| {
+        if let Some(potential) = potentials.get(particles[i].group, particles[j].group) {
+            let r = boundary.displacement(particles[i].pos, particles[j].pos);
+            let distance = r.mag();
+            if distance < cutoff && distance > 0.0 {
+                f(i, j, r, potential);
+            }
+        }
+    };
+    if cutoff.is_finite() {
+        let grid =
+            NeighborGrid::from_particles_in(particles, members.iter().copied(), cutoff, boundary);
+        for &i in &members {
+            for j in grid.candidates(particles[i].pos) {
+                if j > i {
+                    visit(i, j);
+                }
+            }
+        }
+    } else {
+        for (k, &i) in members.iter().enumerate() {
+            for &j in &members[k + 1..] {
+                visit(i, j);
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn wcsph(
+    particles: &mut [Particle],
+    boundary: &Boundary,
+    groups: &[u32],
+    rest_density: f64,
+    h: f64,
+    stiffness: f64,
+    exponent: i32,
+    viscosity: f64,
+) {
+    let members: Vec<usize> = (0..particles.len())
+        .filter(|&index| groups.contains(&particles[index].group))
+        .collect();
+    let grid = NeighborGrid::from_particles_in(particles, members.iter().copied(), h, boundary);
+    let neighbors: Vec<Vec<usize>> = members
+        .iter()
+        .map(|&i| grid.neighbors(particles, particles[i].pos, h))
+        .collect();
+
+    let mut densities = vec![0.0; particles.len()];
+    let mut pressures = vec![0.0; particles.len()];
+    for (k, &i) in members.iter().enumerate() {
+        for &j in &neighbors[k] {
+            densities[i] += particles[j].mass
+                * sph::poly6(
+                    boundary
+                        .displacement(particles[i].pos, particles[j].pos)
+                        .mag_squared(),
+                    h,
+                );
+        }
+        pressures[i] = stiffness * ((densities[i] / rest_density).powi(exponent) - 1.0);
+    }
+
+    for (k, &i) in members.iter().enumerate() {
+        let mut force = Vec2::zero();
+        for &j in &neighbors[k] {
+            if j == i {
+                continue;
+            }
+            let r = boundary.displacement(particles[i].pos, particles[j].pos);
+            // symmetric pressure force, so that linear momentum is conserved
+            force -= sph::spiky_gradient(r, h)
+                * (particles[i].mass
+                    * particles[j].mass
+                    * (pressures[i] / densities[i].powi(2) + pressures[j] / densities[j].powi(2)));
+            force += (particles[j].vel - particles[i].vel)
+                * (viscosity * particles[i].mass * particles[j].mass
+                    / (densities[i] * densities[j])
+                    * sph::viscosity_laplacian(r.mag(), h));
+        }
+        particles[i].add_force(force);
+    }
+}
+
+fn surface_tension(
+    particles: &mut [Particle],
+    colliders: &[StaticCollider],
+    boundary: &Boundary,
+    h: f64,
+    rest_density: f64,
+    cohesion: &PairTable<f64>,
+    adhesion: &PairTable<f64>,
+) {
+    let members: Vec<usize> = (0..particles.len())
+        .filter(|&index| {
+            cohesion.involves(particles[index].group) || adhesion.involves(particles[index].group)
+        })
+        .collect();
+    let grid = NeighborGrid::from_particles_in(particles, members.iter().copied(), h, boundary);
+    let neighbors: Vec<Vec<usize>> = members
+        .iter()
+        .map(|&i| grid.neighbors(particles, particles[i].pos, h))
+        .collect();
+    let cohesive = |i: usize, j: usize| -> Option<f64> {
+        cohesion
+            .get(particles[i].group, particles[j].group)
+            .copied()
+    };
+    // a pinned particle acts like a solid particle of unit mass, as a static collider does
+    let mass = |i: usize| -> f64 {
+        if particles[i].mass.is_finite() {
+            particles[i].mass
+        } else {
+            1.0
+        }
+    };
+
+    // densities and surface normals, using only the neighbors that the particle is cohesive with
+    let mut densities = vec![0.0; particles.len()];
+    let mut normals = vec![Vec2::zero(); particles.len()];
+    for (k, &i) in members.iter().enumerate() {
+        for &j in neighbors[k].iter().filter(|&&j| cohesive(i, j).is_some()) {
+            densities[i] += mass(j)
+                * sph::poly6(
+                    boundary
+                        .displacement(particles[i].pos, particles[j].pos)
+                        .mag_squared(),
+                    h,
+                );
+        }
+    }
+    for (k, &i) in members.iter().enumerate() {
+        for &j in neighbors[k].iter().filter(|&&j| cohesive(i, j).is_some()) {
+            normals[i] +=
+                sph::spiky_gradient(boundary.displacement(particles[i].pos, particles[j].pos), h)
+                    * (h * mass(j) / densities[j]);
+        }
+    }
+
+    let mut forces = vec![Vec2::zero(); particles.len()];
+    for (k, &i) in members.iter().enumerate() {
+        if particles[i].mass.is_infinite() {
+            continue;
+        }
+        for &j in &neighbors[k] {
+            if j == i {
+                continue;
+            }
+            let r = boundary.displacement(particles[i].pos, particles[j].pos);
+            let distance = r.mag();
+            if distance == 0.0 {
+                continue;
+            }
+            if let Some(gamma) = cohesive(i, j) {
+                let correction = 2.0 * rest_density / (densities[i] + densities[j]);
+                let cohesion_force =
+                    r / distance * (-gamma * mass(i) * mass(j) * sph::cohesion(distance, h));
+                let curvature_force = (normals[i] - normals[j]) * (-gamma * mass(i));
+                forces[i] += (cohesion_force + curvature_force) * correction;
+            }
+            if let Some(beta) = adhesion.get(particles[i].group, particles[j].group) {
+                forces[i] +=
+                    r / distance * (-beta * mass(i) * mass(j) * sph::adhesion(distance, h));
+            }
+        }
+        // a static collider acts like a solid particle of unit mass at the closest point on its surface
+        for collider in colliders {
+            if let Some(beta) = adhesion.get(particles[i].group, collider.group) {
+                let (_, normal, distance) = collider.closest(particles[i].pos);
+                forces[i] += normal * (-beta * particles[i].mass * sph::adhesion(distance, h));
+            }
+        }
+    }
+    for &i in &members {
+        particles[i].add_force(forces[i]);
     }
 }
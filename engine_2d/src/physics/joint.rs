@@ -0,0 +1,394 @@
+//! Provides joints that connect two particle-cluster rigid bodies.
+//!
+//! Since a rigid body in this engine is just a cluster of particles, a joint never touches anything other than
+//! the member particles. Every correction is a rigid translation and/or rotation of a body's members, weighted
+//! by the body's mass and moment of inertia, in the same position based manner as
+//! Müller, Macklin, Chentanez, Jeschke, Kim. 2020. [Link](https://doi.org/10.1111/cgf.14105).
+//!
+//! Limits are enforced at the position level each substep, while motors act at the velocity level, after the
+//! particle velocities have been updated from their displacements.
+//!
+//! In a periodic box the anchors of the two bodies are compared with the minimum image convention, so a joint
+//! holds across the boundary.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::particle::Particle;
+use crate::physics::rigid_body::RigidBody;
+use crate::vec2::Vec2;
+
+use std::f64::consts::PI;
+
+/// The type of joint.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum JointKind {
+    /// The bodies share a pivot point (given in world space when the joint is created) and can freely rotate about it.
+    Revolute { pivot: Vec2 },
+    /// The bodies can only slide relative to each other along an axis (given in world space when the joint
+    /// is created). Their relative rotation is locked.
+    Prismatic { axis: Vec2 },
+    /// The bodies are locked together.
+    Weld,
+}
+
+/// A joint motor, which drives the joint towards a target speed without exceeding a maximum torque.
+///
+/// For a prismatic joint, the speed is a linear speed along the axis and the torque is a force along the axis.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Motor {
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub target_speed: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub max_torque: f64,
+}
+
+/// A joint between two rigid bodies.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Joint {
+    /// indices of the two bodies in `System::rigid_bodies`
+    pub bodies: (usize, usize),
+    pub kind: JointKind,
+    /// (lower, upper) limits of the relative angle, or of the displacement along the axis for a prismatic joint
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub limits: Option<(f64, f64)>,
+    pub motor: Option<Motor>,
+    /// the anchor point in the rest frame of each body
+    local_anchors: (Vec2, Vec2),
+    /// the slider axis in the rest frame of the first body
+    local_axis: Vec2,
+    /// relative angle of the bodies when the joint was attached
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    reference_angle: f64,
+}
+
+impl Joint {
+    /// Create a new joint between two bodies.
+    pub fn new(body_a: usize, body_b: usize, kind: JointKind) -> Joint {
+        Joint {
+            bodies: (body_a, body_b),
+            kind,
+            limits: None,
+            motor: None,
+            local_anchors: (Vec2::zero(), Vec2::zero()),
+            local_axis: Vec2::new(1.0, 0.0),
+            reference_angle: 0.0,
+        }
+    }
+
+    /// A builder method to give the joint limits after creating it.
+    pub fn limits(mut self: Self, lower: f64, upper: f64) -> Joint {
+        self.limits = Some((lower, upper));
+        self
+    }
+
+    /// A builder method to give the joint a motor after creating it.
+    ///
+    /// Panics if the maximum torque isn't finite and non-negative.
+    pub fn motor(mut self: Self, target_speed: f64, max_torque: f64) -> Joint {
+        assert!(
+            max_torque >= 0.0 && max_torque.is_finite(),
+            "a motor's maximum torque must be finite and non-negative, not {max_torque}"
+        );
+        self.motor = Some(Motor {
+            target_speed,
+            max_torque,
+        });
+        self
+    }
+
+    /// Record the anchors and reference angle from the current configuration of the bodies.
+    pub fn attach(
+        self: &mut Self,
+        bodies: &[RigidBody],
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) {
+        let a = Frame::of(&bodies[self.bodies.0], particles, boundary);
+        let b = Frame::of(&bodies[self.bodies.1], particles, boundary);
+        let anchor = match self.kind {
+            JointKind::Revolute { pivot } => pivot,
+            JointKind::Prismatic { .. } | JointKind::Weld => {
+                a.center + boundary.displacement(b.center, a.center) / 2.0
+            }
+        };
+        if let JointKind::Prismatic { axis } = self.kind {
+            self.local_axis = (axis / axis.mag()).rotate(-a.angle);
+        }
+        self.local_anchors = (
+            boundary.displacement(anchor, a.center).rotate(-a.angle),
+            boundary.displacement(anchor, b.center).rotate(-b.angle),
+        );
+        self.reference_angle = b.angle - a.angle;
+    }
+
+    /// The angle of the second body relative to the first, measured from when the joint was attached.
+    pub fn relative_angle(
+        self: &Self,
+        bodies: &[RigidBody],
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) -> f64 {
+        let a = &bodies[self.bodies.0];
+        let b = &bodies[self.bodies.1];
+        wrap_angle(
+            b.angle(particles, boundary) - a.angle(particles, boundary) - self.reference_angle,
+        )
+    }
+
+    /// Move the member particles of both bodies so that the joint is satisfied.
+    pub fn project(
+        self: &Self,
+        bodies: &[RigidBody],
+        particles: &mut [Particle],
+        boundary: &Boundary,
+    ) {
+        let (body_a, body_b) = (&bodies[self.bodies.0], &bodies[self.bodies.1]);
+
+        match self.kind {
+            JointKind::Revolute { .. } => {
+                let (a, b) = self.frames(bodies, particles, boundary);
+                self.correct_position(
+                    body_a,
+                    body_b,
+                    &a,
+                    &b,
+                    boundary.displacement(b.anchor, a.anchor),
+                    particles,
+                    boundary,
+                );
+                if let Some((lower, upper)) = self.limits {
+                    let angle = self.relative_angle(bodies, particles, boundary);
+                    if angle < lower {
+                        self.correct_angle(body_a, body_b, angle - lower, particles, boundary);
+                    } else if angle > upper {
+                        self.correct_angle(body_a, body_b, angle - upper, particles, boundary);
+                    }
+                }
+            }
+            JointKind::Prismatic { .. } => {
+                let angle = self.relative_angle(bodies, particles, boundary);
+                self.correct_angle(body_a, body_b, angle, particles, boundary);
+                let (a, b) = self.frames(bodies, particles, boundary);
+                let axis = self.local_axis.rotate(a.angle);
+                let normal = Vec2::new(-axis.y, axis.x);
+                let separation = boundary.displacement(b.anchor, a.anchor);
+                let mut error = normal * separation.dot(&normal);
+                if let Some((lower, upper)) = self.limits {
+                    let slide = separation.dot(&axis);
+                    if slide < lower {
+                        error += axis * (slide - lower);
+                    } else if slide > upper {
+                        error += axis * (slide - upper);
+                    }
+                }
+                self.correct_position(body_a, body_b, &a, &b, error, particles, boundary);
+            }
+            JointKind::Weld => {
+                let angle = self.relative_angle(bodies, particles, boundary);
+                self.correct_angle(body_a, body_b, angle, particles, boundary);
+                let (a, b) = self.frames(bodies, particles, boundary);
+                self.correct_position(
+                    body_a,
+                    body_b,
+                    &a,
+                    &b,
+                    boundary.displacement(b.anchor, a.anchor),
+                    particles,
+                    boundary,
+                );
+            }
+        }
+    }
+
+    /// Drive the joint towards the motor's target speed by changing the velocities of the member particles.
+    pub fn apply_motor(
+        self: &Self,
+        bodies: &[RigidBody],
+        particles: &mut [Particle],
+        boundary: &Boundary,
+        dt: f64,
+    ) {
+        let motor = match &self.motor {
+            Some(motor) => motor,
+            None => return,
+        };
+        let (body_a, body_b) = (&bodies[self.bodies.0], &bodies[self.bodies.1]);
+        let max_impulse = motor.max_torque.abs() * dt;
+        if max_impulse.is_nan() {
+            return;
+        }
+
+        match self.kind {
+            JointKind::Revolute { .. } => {
+                let inverse_inertia_a = inverse(body_a.moment_of_inertia(particles, boundary));
+                let inverse_inertia_b = inverse(body_b.moment_of_inertia(particles, boundary));
+                if inverse_inertia_a + inverse_inertia_b == 0.0 {
+                    return;
+                }
+                let relative_speed = body_b.angular_velocity(particles, boundary)
+                    - body_a.angular_velocity(particles, boundary);
+                let impulse = ((motor.target_speed - relative_speed)
+                    / (inverse_inertia_a + inverse_inertia_b))
+                    .clamp(-max_impulse, max_impulse);
+                body_a.add_velocity(
+                    particles,
+                    boundary,
+                    Vec2::zero(),
+                    -impulse * inverse_inertia_a,
+                );
+                body_b.add_velocity(
+                    particles,
+                    boundary,
+                    Vec2::zero(),
+                    impulse * inverse_inertia_b,
+                );
+            }
+            JointKind::Prismatic { .. } => {
+                // the motor force acts along the axis through the anchors, so it can't create a net torque
+                let (a, b) = self.frames(bodies, particles, boundary);
+                let axis = self.local_axis.rotate(a.angle);
+                let (r_a, r_b) = (a.anchor - a.center, b.anchor - b.center);
+                let w_a = a.inverse_mass + a.inverse_inertia * r_a.cross(&axis).powi(2);
+                let w_b = b.inverse_mass + b.inverse_inertia * r_b.cross(&axis).powi(2);
+                if w_a + w_b == 0.0 {
+                    return;
+                }
+                let relative_speed = (body_b.velocity(particles)
+                    + Vec2::new(-r_b.y, r_b.x) * body_b.angular_velocity(particles, boundary)
+                    - body_a.velocity(particles)
+                    - Vec2::new(-r_a.y, r_a.x) * body_a.angular_velocity(particles, boundary))
+                .dot(&axis);
+                let impulse = axis
+                    * ((motor.target_speed - relative_speed) / (w_a + w_b))
+                        .clamp(-max_impulse, max_impulse);
+                body_a.add_velocity(
+                    particles,
+                    boundary,
+                    impulse * -a.inverse_mass,
+                    -r_a.cross(&impulse) * a.inverse_inertia,
+                );
+                body_b.add_velocity(
+                    particles,
+                    boundary,
+                    impulse * b.inverse_mass,
+                    r_b.cross(&impulse) * b.inverse_inertia,
+                );
+            }
+            JointKind::Weld => (),
+        }
+    }
+
+    fn frames(
+        self: &Self,
+        bodies: &[RigidBody],
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) -> (Frame, Frame) {
+        let mut a = Frame::of(&bodies[self.bodies.0], particles, boundary);
+        let mut b = Frame::of(&bodies[self.bodies.1], particles, boundary);
+        a.anchor = a.center + self.local_anchors.0.rotate(a.angle);
+        b.anchor = b.center + self.local_anchors.1.rotate(b.angle);
+        (a, b)
+    }
+
+    /// Remove a positional error between the two anchors, where error = anchor_b - anchor_a.
+    #[allow(clippy::too_many_arguments)]
+    fn correct_position(
+        self: &Self,
+        body_a: &RigidBody,
+        body_b: &RigidBody,
+        a: &Frame,
+        b: &Frame,
+        error: Vec2,
+        particles: &mut [Particle],
+        boundary: &Boundary,
+    ) {
+        let magnitude = error.mag();
+        if magnitude == 0.0 {
+            return;
+        }
+        let n = error / magnitude;
+        let (r_a, r_b) = (a.anchor - a.center, b.anchor - b.center);
+        let w_a = a.inverse_mass + a.inverse_inertia * r_a.cross(&n).powi(2);
+        let w_b = b.inverse_mass + b.inverse_inertia * r_b.cross(&n).powi(2);
+        if w_a + w_b == 0.0 {
+            return;
+        }
+        let impulse = n * (magnitude / (w_a + w_b));
+
+        body_a.translate(particles, impulse * a.inverse_mass);
+        body_a.rotate(particles, boundary, r_a.cross(&impulse) * a.inverse_inertia);
+        body_b.translate(particles, impulse * -b.inverse_mass);
+        body_b.rotate(
+            particles,
+            boundary,
+            -r_b.cross(&impulse) * b.inverse_inertia,
+        );
+    }
+
+    /// Remove an angular error, where error = (angle_b - angle_a) - target.
+    fn correct_angle(
+        self: &Self,
+        body_a: &RigidBody,
+        body_b: &RigidBody,
+        error: f64,
+        particles: &mut [Particle],
+        boundary: &Boundary,
+    ) {
+        let inverse_inertia_a = inverse(body_a.moment_of_inertia(particles, boundary));
+        let inverse_inertia_b = inverse(body_b.moment_of_inertia(particles, boundary));
+        let w = inverse_inertia_a + inverse_inertia_b;
+        if w == 0.0 {
+            return;
+        }
+        let impulse = error / w;
+        body_a.rotate(particles, boundary, impulse * inverse_inertia_a);
+        body_b.rotate(particles, boundary, -impulse * inverse_inertia_b);
+    }
+}
+
+/// The derived state of a body that a joint needs.
+struct Frame {
+    center: Vec2,
+    angle: f64,
+    anchor: Vec2,
+    inverse_mass: f64,
+    inverse_inertia: f64,
+}
+
+impl Frame {
+    fn of(body: &RigidBody, particles: &[Particle], boundary: &Boundary) -> Frame {
+        Frame {
+            center: body.center_of_mass(particles, boundary),
+            angle: body.angle(particles, boundary),
+            anchor: Vec2::zero(),
+            inverse_mass: if body.is_pinned(particles) {
+                0.0
+            } else {
+                inverse(body.mass(particles))
+            },
+            inverse_inertia: inverse(body.moment_of_inertia(particles, boundary)),
+        }
+    }
+}
+
+/// 1/x, except that an infinite (or zero) quantity can't be moved at all.
+fn inverse(x: f64) -> f64 {
+    if x == 0.0 || x.is_infinite() {
+        0.0
+    } else {
+        1.0 / x
+    }
+}
+
+/// Map an angle into (-π, π].
+fn wrap_angle(angle: f64) -> f64 {
+    let wrapped = (angle + PI).rem_euclid(2.0 * PI) - PI;
+    if wrapped == -PI {
+        PI
+    } else {
+        wrapped
+    }
+}
@@ -0,0 +1,450 @@
+//! Provides meshing, which turns a simple polygon into particles and triangles that a body can be built from.
+//!
+//! A polygon can be meshed in a few ways:
+//!
+//! - Ear clipping: the polygon's own vertices become the particles, and are triangulated by repeatedly cutting off
+//!   an "ear" (a triangle made of three consecutive vertices that contains no other vertex).
+//! - Hexagonal: the polygon's boundary is resampled at a spacing, and its interior is filled with a hexagonal
+//!   (triangular) lattice of particles at the same spacing.
+//! - Poisson disk: the boundary is resampled, and the interior is filled with randomly placed particles that are
+//!   never closer than the spacing, from Bridson. 2007. [Link](https://doi.org/10.1145/1278780.1278807).
+//!
+//! Sampled particles are connected with a Delaunay triangulation (Bowyer-Watson), and any triangles that fall
+//! outside of the polygon are removed. A concave polygon keeps its shape as long as the spacing is small compared
+//! to its narrowest features.
+
+use crate::physics::collision;
+use crate::physics::constraint::Material;
+use crate::physics::particle::Particle;
+use crate::random::Rng;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+
+/// How a polygon is turned into particles.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Meshing {
+    /// Use only the polygon's vertices, triangulated by ear clipping.
+    EarClipping,
+    /// Resample the boundary and fill the interior with a hexagonal lattice.
+    Hexagonal {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        spacing: f64,
+    },
+    /// Resample the boundary and fill the interior with Poisson disk samples.
+    PoissonDisk {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        spacing: f64,
+        seed: u64,
+    },
+}
+
+/// A description of a body meshed from a polygon, which `System::add_polygon_soft_body` and
+/// `System::add_polygon_rigid_body` build.
+pub struct PolygonMesh {
+    /// the polygon's vertices, in either winding order
+    pub vertices: Vec<Vec2>,
+    pub meshing: Meshing,
+    /// mass of each particle
+    pub particle_mass: f64,
+    /// radius of each particle, where None uses half of the spacing (or of the shortest edge, for ear clipping)
+    pub radius: Option<f64>,
+    pub color: (u8, u8, u8, u8),
+    pub group: u32,
+    /// compliance of the distance constraints along every edge of the mesh (infinite leaves them out)
+    pub edge_compliance: f64,
+    /// compliance of the area constraints on every triangle of the mesh (infinite leaves them out)
+    pub area_compliance: f64,
+    /// material of the strain constraints on every triangle of the mesh, if there are any
+    pub material: Option<Material>,
+}
+
+impl PolygonMesh {
+    /// Mesh a polygon.
+    ///
+    /// Panics if the meshing's spacing isn't positive and finite.
+    pub fn new(vertices: Vec<Vec2>, meshing: Meshing) -> PolygonMesh {
+        assert_valid_spacing(meshing);
+        let particle = Particle::new();
+        PolygonMesh {
+            vertices,
+            meshing,
+            particle_mass: particle.mass,
+            radius: None,
+            color: particle.color,
+            group: 0,
+            edge_compliance: 0.0,
+            area_compliance: f64::INFINITY,
+            material: None,
+        }
+    }
+
+    /// A builder method to give each of the mesh's particles a specific mass after creating it.
+    pub fn particle_mass(mut self: Self, mass: f64) -> PolygonMesh {
+        self.particle_mass = mass;
+        self
+    }
+
+    /// A builder method to give the mesh's particles a specific radius after creating it.
+    pub fn radius(mut self: Self, radius: f64) -> PolygonMesh {
+        self.radius = Some(radius);
+        self
+    }
+
+    /// A builder method to give the mesh's particles a specific color after creating it.
+    pub fn color(mut self: Self, color: (u8, u8, u8, u8)) -> PolygonMesh {
+        self.color = color;
+        self
+    }
+
+    /// A builder method to give the mesh's particles a specific group after creating it.
+    pub fn group(mut self: Self, group: u32) -> PolygonMesh {
+        self.group = group;
+        self
+    }
+
+    /// A builder method to give the edge and area constraints specific compliances after creating it.
+    pub fn compliance(mut self: Self, edge: f64, area: f64) -> PolygonMesh {
+        self.edge_compliance = edge;
+        self.area_compliance = area;
+        self
+    }
+
+    /// A builder method to give each of the mesh's triangles a strain constraint of some material after
+    /// creating it.
+    pub fn material(mut self: Self, material: Material) -> PolygonMesh {
+        self.material = Some(material);
+        self
+    }
+
+    /// Mesh the polygon. Returns the particles' positions, the triangles (counterclockwise), and the boundary
+    /// (counterclockwise), where the first `boundary.len()` positions are the boundary.
+    pub fn build(self: &Self) -> (Vec<Vec2>, Vec<[usize; 3]>, Vec<usize>) {
+        assert_valid_spacing(self.meshing);
+        let polygon = counterclockwise(&self.vertices);
+        match self.meshing {
+            Meshing::EarClipping => {
+                let triangles = triangulate(&polygon);
+                let boundary = (0..polygon.len()).collect();
+                (polygon, triangles, boundary)
+            }
+            Meshing::Hexagonal { spacing } | Meshing::PoissonDisk { spacing, .. } => {
+                let mut points = resample(&polygon, spacing);
+                let boundary = (0..points.len()).collect();
+                points.extend(sample(&polygon, self.meshing));
+                let triangles = delaunay(&points)
+                    .into_iter()
+                    .filter(|&[a, b, c]| {
+                        collision::contains(&polygon, (points[a] + points[b] + points[c]) / 3.0)
+                    })
+                    .collect();
+                (points, triangles, boundary)
+            }
+        }
+    }
+
+    /// The particles to place at the meshed positions.
+    pub(crate) fn particles(self: &Self, positions: &[Vec2]) -> Vec<Particle> {
+        let radius = self.radius.unwrap_or(match self.meshing {
+            Meshing::Hexagonal { spacing } | Meshing::PoissonDisk { spacing, .. } => spacing / 2.0,
+            Meshing::EarClipping => {
+                let count = self.vertices.len();
+                (0..count)
+                    .map(|k| (self.vertices[(k + 1) % count] - self.vertices[k]).mag())
+                    .fold(f64::INFINITY, f64::min)
+                    / 2.0
+            }
+        });
+        positions
+            .iter()
+            .map(|pos| {
+                Particle::new()
+                    .pos(pos.x, pos.y)
+                    .mass(self.particle_mass)
+                    .radius(radius)
+                    .color(self.color)
+                    .group(self.group)
+            })
+            .collect()
+    }
+}
+
+/// The signed area of a polygon (positive when it's counterclockwise).
+pub fn signed_area(polygon: &[Vec2]) -> f64 {
+    let count = polygon.len();
+    (0..count)
+        .map(|k| polygon[k].cross(&polygon[(k + 1) % count]))
+        .sum::<f64>()
+        / 2.0
+}
+
+/// The polygon in counterclockwise order.
+fn counterclockwise(polygon: &[Vec2]) -> Vec<Vec2> {
+    let mut polygon = polygon.to_vec();
+    if signed_area(&polygon) < 0.0 {
+        polygon.reverse();
+    }
+    polygon
+}
+
+/// Triangulate a simple polygon by ear clipping. Returns triangles of indices into the polygon, which are
+/// counterclockwise if the polygon is.
+pub fn triangulate(polygon: &[Vec2]) -> Vec<[usize; 3]> {
+    let orientation = signed_area(polygon).signum();
+    let mut remaining: Vec<usize> = (0..polygon.len()).collect();
+    let mut triangles = Vec::new();
+    while remaining.len() > 3 {
+        let count = remaining.len();
+        let ear = (0..count).find(|&k| {
+            let (a, b, c) = (
+                remaining[(k + count - 1) % count],
+                remaining[k],
+                remaining[(k + 1) % count],
+            );
+            let convex =
+                (polygon[b] - polygon[a]).cross(&(polygon[c] - polygon[b])) * orientation > 0.0;
+            convex
+                && remaining.iter().all(|&other| {
+                    other == a
+                        || other == b
+                        || other == c
+                        || !in_triangle(polygon[other], polygon[a], polygon[b], polygon[c])
+                })
+        });
+        // a degenerate polygon (ex: collinear vertices everywhere) has no proper ears left, so clip anyway
+        let k = ear.unwrap_or(0);
+        triangles.push([
+            remaining[(k + count - 1) % count],
+            remaining[k],
+            remaining[(k + 1) % count],
+        ]);
+        remaining.remove(k);
+    }
+    if remaining.len() == 3 {
+        triangles.push([remaining[0], remaining[1], remaining[2]]);
+    }
+    triangles
+}
+
+/// Whether p is inside of (or on) the triangle abc, in either winding order.
+fn in_triangle(p: Vec2, a: Vec2, b: Vec2, c: Vec2) -> bool {
+    let (d1, d2, d3) = (
+        (b - a).cross(&(p - a)),
+        (c - b).cross(&(p - b)),
+        (a - c).cross(&(p - c)),
+    );
+    !((d1 < 0.0 || d2 < 0.0 || d3 < 0.0) && (d1 > 0.0 || d2 > 0.0 || d3 > 0.0))
+}
+
+/// The Delaunay triangulation of a set of points (Bowyer-Watson). Returns counterclockwise triangles of indices
+/// into the points.
+pub fn delaunay(points: &[Vec2]) -> Vec<[usize; 3]> {
+    if points.len() < 3 {
+        return Vec::new();
+    }
+    // start with a triangle that's much larger than all of the points
+    let (min, max) = points.iter().fold(
+        (
+            Vec2::new(f64::INFINITY, f64::INFINITY),
+            Vec2::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        ),
+        |(min, max), p| {
+            (
+                Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    );
+    let center = (min + max) / 2.0;
+    let size = (max - min).mag().max(1.0) * 20.0;
+    let mut vertices = points.to_vec();
+    let n = points.len();
+    vertices.push(center + Vec2::new(-size, -size));
+    vertices.push(center + Vec2::new(size, -size));
+    vertices.push(center + Vec2::new(0.0, size));
+    let mut triangles = vec![[n, n + 1, n + 2]];
+
+    for (index, &point) in points.iter().enumerate() {
+        // remove every triangle whose circumcircle contains the point, and re-triangulate the hole from the point
+        let (bad, good): (Vec<[usize; 3]>, Vec<[usize; 3]>) = triangles
+            .into_iter()
+            .partition(|&triangle| in_circumcircle(point, triangle.map(|k| vertices[k])));
+        triangles = good;
+        let mut edges: HashMap<(usize, usize), u32> = HashMap::new();
+        for [a, b, c] in &bad {
+            for (u, v) in [(*a, *b), (*b, *c), (*c, *a)] {
+                *edges.entry((u.min(v), u.max(v))).or_default() += 1;
+            }
+        }
+        for [a, b, c] in &bad {
+            for (u, v) in [(*a, *b), (*b, *c), (*c, *a)] {
+                // only the hole's boundary edges appear once, and they keep their counterclockwise direction
+                if edges[&(u.min(v), u.max(v))] == 1 {
+                    triangles.push([u, v, index]);
+                }
+            }
+        }
+    }
+    triangles
+        .into_iter()
+        .filter(|triangle| triangle.iter().all(|&k| k < n))
+        .collect()
+}
+
+/// Whether p is strictly inside of the circumcircle of a counterclockwise triangle.
+fn in_circumcircle(p: Vec2, [a, b, c]: [Vec2; 3]) -> bool {
+    let (a, b, c) = (a - p, b - p, c - p);
+    let determinant = a.mag_squared() * b.cross(&c)
+        + b.mag_squared() * c.cross(&a)
+        + c.mag_squared() * a.cross(&b);
+    determinant > 0.0
+}
+
+/// Panic unless the meshing's spacing is positive and finite, since resampling and sampling would never finish.
+fn assert_valid_spacing(meshing: Meshing) {
+    if let Meshing::Hexagonal { spacing } | Meshing::PoissonDisk { spacing, .. } = meshing {
+        assert!(
+            spacing > 0.0 && spacing.is_finite(),
+            "the spacing of a mesh must be positive and finite, not {spacing}"
+        );
+    }
+}
+
+/// Points along the boundary of a polygon, including its vertices, no further apart than the spacing.
+fn resample(polygon: &[Vec2], spacing: f64) -> Vec<Vec2> {
+    let count = polygon.len();
+    let mut points = Vec::new();
+    for k in 0..count {
+        let (a, b) = (polygon[k], polygon[(k + 1) % count]);
+        let pieces = ((b - a).mag() / spacing).ceil().max(1.0) as usize;
+        for piece in 0..pieces {
+            points.push(a + (b - a) * (piece as f64 / pieces as f64));
+        }
+    }
+    points
+}
+
+/// The distance from p to the closest point on the polygon's boundary.
+fn distance_to_boundary(polygon: &[Vec2], p: Vec2) -> f64 {
+    let count = polygon.len();
+    (0..count)
+        .map(|k| {
+            let (a, b) = (polygon[k], polygon[(k + 1) % count]);
+            let ab = b - a;
+            let t = ((p - a).dot(&ab) / ab.mag_squared().max(f64::MIN_POSITIVE)).clamp(0.0, 1.0);
+            (p - (a + ab * t)).mag()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Points that fill the interior of a polygon, at least half of the spacing away from its boundary. Ear clipping
+/// doesn't add any interior points.
+///
+/// Panics if the meshing's spacing isn't positive and finite.
+pub fn sample(polygon: &[Vec2], meshing: Meshing) -> Vec<Vec2> {
+    assert_valid_spacing(meshing);
+    let (min, max) = polygon.iter().fold(
+        (
+            Vec2::new(f64::INFINITY, f64::INFINITY),
+            Vec2::new(f64::NEG_INFINITY, f64::NEG_INFINITY),
+        ),
+        |(min, max), p| {
+            (
+                Vec2::new(min.x.min(p.x), min.y.min(p.y)),
+                Vec2::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        },
+    );
+    let inside = |p: Vec2, spacing: f64| {
+        collision::contains(polygon, p) && distance_to_boundary(polygon, p) >= spacing / 2.0
+    };
+    match meshing {
+        Meshing::EarClipping => Vec::new(),
+        Meshing::Hexagonal { spacing } => {
+            let row_height = spacing * 3.0_f64.sqrt() / 2.0;
+            let mut points = Vec::new();
+            let mut row = 0;
+            let mut y = min.y;
+            while y <= max.y {
+                let mut x = min.x + if row % 2 == 1 { spacing / 2.0 } else { 0.0 };
+                while x <= max.x {
+                    let p = Vec2::new(x, y);
+                    if inside(p, spacing) {
+                        points.push(p);
+                    }
+                    x += spacing;
+                }
+                y += row_height;
+                row += 1;
+            }
+            points
+        }
+        Meshing::PoissonDisk { spacing, seed } => {
+            let mut rng = Rng::new(seed);
+            let cell = spacing / 2.0_f64.sqrt();
+            let columns = ((max.x - min.x) / cell).ceil() as usize + 1;
+            let rows = ((max.y - min.y) / cell).ceil() as usize + 1;
+            let mut grid: Vec<Option<usize>> = vec![None; columns * rows];
+            let cell_of = |p: Vec2| {
+                (
+                    ((p.x - min.x) / cell) as usize,
+                    ((p.y - min.y) / cell) as usize,
+                )
+            };
+            let mut points: Vec<Vec2> = Vec::new();
+            let mut active = Vec::new();
+            // start from random points until one lands inside of the polygon
+            for _ in 0..1000 {
+                let p = Vec2::new(rng.range(min.x, max.x), rng.range(min.y, max.y));
+                if inside(p, spacing) {
+                    let (x, y) = cell_of(p);
+                    grid[y * columns + x] = Some(0);
+                    points.push(p);
+                    active.push(0);
+                    break;
+                }
+            }
+            while !active.is_empty() {
+                let slot = (rng.uniform() * active.len() as f64) as usize;
+                let origin = points[active[slot]];
+                let mut found = false;
+                for _ in 0..30 {
+                    let p = origin
+                        + Vec2::new_polar(
+                            spacing * (1.0 + rng.uniform()),
+                            rng.range(0.0, std::f64::consts::TAU),
+                        );
+                    if p.x < min.x
+                        || p.x > max.x
+                        || p.y < min.y
+                        || p.y > max.y
+                        || !inside(p, spacing)
+                    {
+                        continue;
+                    }
+                    let (x, y) = cell_of(p);
+                    let far_enough = (y.saturating_sub(2)..(y + 3).min(rows)).all(|ny| {
+                        (x.saturating_sub(2)..(x + 3).min(columns)).all(|nx| {
+                            match grid[ny * columns + nx] {
+                                Some(other) => (points[other] - p).mag() >= spacing,
+                                None => true,
+                            }
+                        })
+                    });
+                    if far_enough {
+                        grid[y * columns + x] = Some(points.len());
+                        active.push(points.len());
+                        points.push(p);
+                        found = true;
+                        break;
+                    }
+                }
+                if !found {
+                    active.swap_remove(slot);
+                }
+            }
+            points
+        }
+    }
+}
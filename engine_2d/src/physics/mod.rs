@@ -1,4 +1,21 @@
+pub mod boundary;
+pub mod collision;
 pub mod constraint;
+pub mod diagnostics;
+pub mod ewald;
+pub mod fluid;
 pub mod force;
+pub mod joint;
+pub mod mesh;
+pub mod neighbor_grid;
+pub mod pair_table;
 pub mod particle;
+pub mod pneumatic;
+pub mod potential;
+pub mod registry;
+pub mod rigid_body;
+pub mod soft_body;
+pub mod sph;
 pub mod system;
+pub mod thermal;
+pub mod thermostat;
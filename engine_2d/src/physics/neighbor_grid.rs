@@ -0,0 +1,139 @@
+//! Provides a uniform spatial grid for quickly finding the particles that are near a point.
+//!
+//! Checking every pair of particles is O(n²), which quickly becomes too slow for things like fluids. Instead,
+//! particles are sorted into square cells that are at least as wide as the interaction radius, so that all of a
+//! particle's neighbors are guaranteed to be in its own cell or one of the eight cells surrounding it.
+//!
+//! In a periodic box the cells wrap around, and distances use the minimum image convention.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+
+/// A uniform grid of particle indices.
+pub struct NeighborGrid {
+    cell_size: Vec2,
+    cells: HashMap<(i64, i64), Vec<usize>>,
+    boundary: Boundary,
+    /// the number of cells across the box in each direction, if the boundary is periodic
+    wrap: Option<(i64, i64)>,
+}
+
+impl NeighborGrid {
+    /// Create an empty grid with the given cell size.
+    pub fn new(cell_size: f64) -> NeighborGrid {
+        NeighborGrid::with_boundary(cell_size, &Boundary::Open)
+    }
+
+    /// Create an empty grid with the given (minimum) cell size, which wraps around a periodic boundary.
+    /// The cells are stretched slightly so that a whole number of them fits across the box.
+    pub fn with_boundary(cell_size: f64, boundary: &Boundary) -> NeighborGrid {
+        match *boundary {
+            Boundary::Open => NeighborGrid {
+                cell_size: Vec2::new(cell_size, cell_size),
+                cells: HashMap::new(),
+                boundary: *boundary,
+                wrap: None,
+            },
+            Boundary::Periodic { min, max } => {
+                let size = max - min;
+                let count_x = ((size.x / cell_size).floor() as i64).max(1);
+                let count_y = ((size.y / cell_size).floor() as i64).max(1);
+                NeighborGrid {
+                    cell_size: Vec2::new(size.x / count_x as f64, size.y / count_y as f64),
+                    cells: HashMap::new(),
+                    boundary: *boundary,
+                    wrap: Some((count_x, count_y)),
+                }
+            }
+        }
+    }
+
+    /// Create a grid containing the given particles.
+    pub fn from_particles(
+        particles: &[Particle],
+        members: impl IntoIterator<Item = usize>,
+        cell_size: f64,
+    ) -> NeighborGrid {
+        NeighborGrid::from_particles_in(particles, members, cell_size, &Boundary::Open)
+    }
+
+    /// Create a grid containing the given particles, which wraps around a periodic boundary.
+    pub fn from_particles_in(
+        particles: &[Particle],
+        members: impl IntoIterator<Item = usize>,
+        cell_size: f64,
+        boundary: &Boundary,
+    ) -> NeighborGrid {
+        let mut grid = NeighborGrid::with_boundary(cell_size, boundary);
+        for index in members {
+            grid.insert(index, particles[index].pos);
+        }
+        grid
+    }
+
+    /// Add a particle index at a position.
+    pub fn insert(self: &mut Self, index: usize, pos: Vec2) {
+        self.cells.entry(self.cell(pos)).or_default().push(index);
+    }
+
+    /// Remove everything from the grid.
+    pub fn clear(self: &mut Self) {
+        self.cells.clear();
+    }
+
+    /// All of the particle indices in the cell containing `pos` and its eight surrounding cells.
+    pub fn candidates(self: &Self, pos: Vec2) -> impl Iterator<Item = usize> + '_ {
+        let (cx, cy) = self.cell(pos);
+        let mut cells: Vec<(i64, i64)> = (-1..=1)
+            .flat_map(|dx| (-1..=1).map(move |dy| (cx + dx, cy + dy)))
+            .collect();
+        if let Some((count_x, count_y)) = self.wrap {
+            // with fewer than three cells across, the surrounding cells wrap onto the same cells more than once
+            cells = cells
+                .into_iter()
+                .map(|(x, y)| (x.rem_euclid(count_x), y.rem_euclid(count_y)))
+                .collect();
+            cells.sort_unstable();
+            cells.dedup();
+        }
+        cells
+            .into_iter()
+            .filter_map(move |cell| self.cells.get(&cell))
+            .flatten()
+            .copied()
+    }
+
+    /// All of the particle indices within `radius` of `pos` (which includes a particle located at `pos`).
+    ///
+    /// The radius shouldn't be larger than the cell size.
+    pub fn neighbors(self: &Self, particles: &[Particle], pos: Vec2, radius: f64) -> Vec<usize> {
+        let radius_squared = radius.powi(2);
+        self.candidates(pos)
+            .filter(|&index| {
+                self.boundary
+                    .displacement(particles[index].pos, pos)
+                    .mag_squared()
+                    < radius_squared
+            })
+            .collect()
+    }
+
+    fn cell(self: &Self, pos: Vec2) -> (i64, i64) {
+        match (self.wrap, self.boundary) {
+            (Some((count_x, count_y)), Boundary::Periodic { min, .. }) => {
+                let pos = self.boundary.wrap(pos) - min;
+                (
+                    ((pos.x / self.cell_size.x).floor() as i64).rem_euclid(count_x),
+                    ((pos.y / self.cell_size.y).floor() as i64).rem_euclid(count_y),
+                )
+            }
+            _ => (
+                (pos.x / self.cell_size.x).floor() as i64,
+                (pos.y / self.cell_size.y).floor() as i64,
+            ),
+        }
+    }
+}
@@ -0,0 +1,63 @@
+//! Provides a table of parameters that are keyed by a pair of particle groups.
+//!
+//! Many interactions (cohesion, adhesion, molecular potentials, etc) have a strength that depends on which kinds of
+//! particles are interacting. The order of the pair doesn't matter, so (a, b) and (b, a) are the same entry.
+
+use std::collections::HashMap;
+
+/// A symmetric table of values keyed by a pair of `Particle::group` values.
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PairTable<T> {
+    /// saved as a list of (a, b, value), since some formats (like JSON) only allow strings as keys
+    #[cfg_attr(
+        feature = "serde",
+        serde(
+            with = "crate::save::pairs",
+            bound(
+                serialize = "T: serde::Serialize",
+                deserialize = "T: serde::Deserialize<'de>"
+            )
+        )
+    )]
+    entries: HashMap<(u32, u32), T>,
+}
+
+impl<T> PairTable<T> {
+    /// Create an empty table.
+    pub fn new() -> PairTable<T> {
+        PairTable {
+            entries: HashMap::new(),
+        }
+    }
+
+    /// A builder method to add an entry to the table after creating it.
+    pub fn with(mut self: Self, group_a: u32, group_b: u32, value: T) -> PairTable<T> {
+        self.set(group_a, group_b, value);
+        self
+    }
+
+    /// Set the value for a pair of groups.
+    pub fn set(self: &mut Self, group_a: u32, group_b: u32, value: T) {
+        self.entries.insert(Self::key(group_a, group_b), value);
+    }
+
+    /// Get the value for a pair of groups, if there is one.
+    pub fn get(self: &Self, group_a: u32, group_b: u32) -> Option<&T> {
+        self.entries.get(&Self::key(group_a, group_b))
+    }
+
+    /// Whether a group appears in any of the table's pairs.
+    pub fn involves(self: &Self, group: u32) -> bool {
+        self.entries.keys().any(|&(a, b)| a == group || b == group)
+    }
+
+    /// All of the entries in the table, as ((group_a, group_b), value).
+    pub fn iter(self: &Self) -> impl Iterator<Item = (&(u32, u32), &T)> {
+        self.entries.iter()
+    }
+
+    fn key(group_a: u32, group_b: u32) -> (u32, u32) {
+        (group_a.min(group_b), group_a.max(group_b))
+    }
+}
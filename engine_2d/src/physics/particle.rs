@@ -19,16 +19,20 @@ pub const EARTH_BLUE: (u8, u8, u8, u8) = (10, 30, 220, 255);
 pub const FOREST_GREEN: (u8, u8, u8, u8) = (1, 79, 55, 255);
 
 /// A physical particle.
-#[derive(Default)]
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Particle {
     /// mass of the particle
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub mass: f64,
     /// 2-dimensional position of the particle
     pub pos: Vec2,
     /// 2-dimensional velocity of the particle
     pub vel: Vec2,
-    /// a collection of all of the forces acting on the particle
+    /// a collection of all of the constant forces acting on the particle
     pub forces: Vec<Vec2>,
+    /// the sum of the forces applied by the system during this step, which is cleared after every update
+    applied_force: Vec2,
     /// the previous 2-dimensional position of the particle
     old_pos: Vec2,
     /// a field intended to be used as a unique ID for anytime that it is useful
@@ -36,9 +40,19 @@ pub struct Particle {
     /// when you want to group together particles with shared properties, etc
     pub group: u32,
     /// radius of the particle
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub radius: f64,
     /// 32-bit color: (r, g, b, a)
     pub color: (u8, u8, u8, u8),
+    /// temperature of the particle
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub temperature: f64,
+    /// heat capacity per unit mass of the particle
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub specific_heat: f64,
+    /// electric charge of the particle
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub charge: f64,
 }
 
 impl Particle {
@@ -48,6 +62,7 @@ impl Particle {
             mass: 10.0,
             radius: 10.0,
             color: CRIMSON,
+            specific_heat: 1.0,
             ..Default::default()
         }
     }
@@ -82,6 +97,24 @@ impl Particle {
         self
     }
 
+    /// A builder method to give the particle a specific temperature after creating it.
+    pub fn temperature(mut self: Self, temperature: f64) -> Particle {
+        self.temperature = temperature;
+        self
+    }
+
+    /// A builder method to give the particle a specific specific heat after creating it.
+    pub fn specific_heat(mut self: Self, specific_heat: f64) -> Particle {
+        self.specific_heat = specific_heat;
+        self
+    }
+
+    /// A builder method to give the particle a specific charge after creating it.
+    pub fn charge(mut self: Self, charge: f64) -> Particle {
+        self.charge = charge;
+        self
+    }
+
     /// A builder method to give the particle a specific position after creating it.
     pub fn pos(mut self: Self, x: f64, y: f64) -> Particle {
         self.pos = Vec2::new(x, y);
@@ -94,6 +127,66 @@ impl Particle {
         self
     }
 
+    /// The heat capacity of the particle, m·c.
+    pub fn heat_capacity(self: &Self) -> f64 {
+        self.mass * self.specific_heat
+    }
+
+    /// The thermal energy stored in the particle, m·c·T.
+    pub fn thermal_energy(self: &Self) -> f64 {
+        self.heat_capacity() * self.temperature
+    }
+
+    /// Add heat to the particle, which changes its temperature by Q/(m·c).
+    pub fn add_heat(self: &mut Self, heat: f64) {
+        let capacity = self.heat_capacity();
+        if capacity > 0.0 {
+            self.temperature += heat / capacity;
+        }
+    }
+
+    /// The inverse of the particle's mass, which is 0.0 for a particle with infinite mass (an immovable one).
+    pub fn inverse_mass(self: &Self) -> f64 {
+        if self.mass.is_infinite() {
+            0.0
+        } else {
+            1.0 / self.mass
+        }
+    }
+
+    /// The potential energy of the particle's constant forces, -ΣF·r, since a constant force (like uniform gravity)
+    /// is the gradient of a linear potential.
+    pub fn potential_energy(self: &Self) -> f64 {
+        -self
+            .forces
+            .iter()
+            .map(|force| force.dot(&self.pos))
+            .sum::<f64>()
+    }
+
+    /// The kinetic energy of the particle, ½mv².
+    pub fn kinetic_energy(self: &Self) -> f64 {
+        if self.mass.is_finite() {
+            0.5 * self.mass * self.vel.mag_squared()
+        } else {
+            0.0
+        }
+    }
+
+    /// The linear momentum of the particle, mv (zero for a particle with infinite mass, which can't move).
+    pub fn momentum(self: &Self) -> Vec2 {
+        if self.mass.is_finite() {
+            self.vel * self.mass
+        } else {
+            Vec2::zero()
+        }
+    }
+
+    /// Apply a force to the particle for the next update only.
+    pub fn add_force(self: &mut Self, force: Vec2) {
+        self.applied_force += force;
+    }
+
     /// An explicit, first-order symplectic integrator that updates the
     /// Particle (uses the Semi-implicit/Symplectic Euler Method).
     ///
@@ -106,10 +199,11 @@ impl Particle {
     /// $$\vec{v} _{n+1} = \vec{v} _{n} + \frac{1}{m}\Sigma\vec{F} _{n}\Delta t$$
     /// $$\vec{x} _{n+1} = \vec{x} _{n} + \vec{v} _{n+1}\Delta t$$
     pub fn update(self: &mut Self, dt: f64) {
-        let mut total_force = Vec2::zero();
+        let mut total_force = self.applied_force;
         for force in &self.forces {
             total_force += *force;
         }
+        self.applied_force = Vec2::zero();
         self.vel += (total_force / self.mass) * dt;
         self.old_pos = self.pos;
         self.pos += self.vel * dt;
@@ -0,0 +1,168 @@
+//! Provides pneumatic bodies, which are closed loops of particles that enclose an area (the 2d volume).
+//!
+//! A pneumatic body is inflated in one of two ways:
+//!
+//! - Area: the enclosed area is held at a target by a position based area constraint, which behaves like an
+//!   incompressible (or, with compliance, slightly compressible) fluid filling the loop.
+//! - Gas: the loop is filled with an ideal gas, whose pressure follows PV = nRT. In 2d the volume is the enclosed
+//!   area, so the pressure is P = nRT/A, and each particle is pushed outward by P times the gradient of the area
+//!   with respect to its position. The gas stores a potential energy of -nRT·ln(A).
+//!
+//! The target area or the amount of gas (nRT) can be changed at any time, which lets a body be inflated and
+//! deflated while the simulation runs, like a balloon or an inflatable actuator.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+/// How a pneumatic body is inflated.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Inflation {
+    /// Hold the enclosed area at a target, with a compliance (0.0 is incompressible).
+    Area {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        target: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        compliance: f64,
+    },
+    /// Fill the body with an ideal gas, where amount is nRT.
+    Gas {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        amount: f64,
+    },
+}
+
+/// A closed loop of particles that is inflated.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PneumaticBody {
+    /// indices of the loop's particles in `System::particles`, in counterclockwise order
+    pub particles: Vec<usize>,
+    pub inflation: Inflation,
+    /// compliance of the distance constraints along the loop's edges, if `System::add_pneumatic_body` should
+    /// add them
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub edge_compliance: Option<f64>,
+    /// indices of the loop's edge constraints in `System::constraints`
+    pub edges: Vec<usize>,
+}
+
+impl PneumaticBody {
+    /// Inflate a closed loop of already added particles.
+    pub fn new(particles: Vec<usize>, inflation: Inflation) -> PneumaticBody {
+        PneumaticBody {
+            particles,
+            inflation,
+            edge_compliance: None,
+            edges: Vec::new(),
+        }
+    }
+
+    /// A builder method to also connect the loop's neighboring particles with distance constraints after creating
+    /// it. This isn't needed if the particles are already connected (ex: the outline of a `SoftBody`).
+    pub fn edges(mut self: Self, compliance: f64) -> PneumaticBody {
+        self.edge_compliance = Some(compliance);
+        self
+    }
+
+    /// The positions of the loop's particles, unwrapped across a periodic boundary so that the loop is in one piece.
+    fn positions(self: &Self, particles: &[Particle], boundary: &Boundary) -> Vec<Vec2> {
+        let mut positions = Vec::with_capacity(self.particles.len());
+        for (k, &index) in self.particles.iter().enumerate() {
+            positions.push(if k == 0 {
+                particles[index].pos
+            } else {
+                positions[k - 1]
+                    + boundary
+                        .displacement(particles[index].pos, particles[self.particles[k - 1]].pos)
+            });
+        }
+        positions
+    }
+
+    /// The signed area enclosed by the loop (positive when the loop is counterclockwise).
+    pub fn area(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        let positions = self.positions(particles, boundary);
+        let count = positions.len();
+        (0..count)
+            .map(|k| positions[k].cross(&positions[(k + 1) % count]))
+            .sum::<f64>()
+            / 2.0
+    }
+
+    /// The gradient of the enclosed area with respect to each particle's position.
+    fn area_gradients(positions: &[Vec2]) -> Vec<Vec2> {
+        let count = positions.len();
+        (0..count)
+            .map(|k| {
+                let (previous, next) = (
+                    positions[(k + count - 1) % count],
+                    positions[(k + 1) % count],
+                );
+                Vec2::new(next.y - previous.y, previous.x - next.x) / 2.0
+            })
+            .collect()
+    }
+
+    /// The pressure inside of the body, if it's filled with gas.
+    pub fn pressure(self: &Self, particles: &[Particle], boundary: &Boundary) -> Option<f64> {
+        match self.inflation {
+            Inflation::Gas { amount } => Some(amount / self.area(particles, boundary)),
+            Inflation::Area { .. } => None,
+        }
+    }
+
+    /// The potential energy stored by the gas inside of the body, -nRT·ln(A).
+    pub fn potential_energy(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        match self.inflation {
+            Inflation::Gas { amount } => {
+                let area = self.area(particles, boundary);
+                if area > 0.0 {
+                    -amount * area.ln()
+                } else {
+                    0.0
+                }
+            }
+            Inflation::Area { .. } => 0.0,
+        }
+    }
+
+    /// Apply the gas pressure to the particles, if the body is filled with gas.
+    pub fn apply_pressure(self: &Self, particles: &mut [Particle], boundary: &Boundary) {
+        if let Inflation::Gas { amount } = self.inflation {
+            let positions = self.positions(particles, boundary);
+            let area = self.area(particles, boundary);
+            if area <= 0.0 {
+                return;
+            }
+            let pressure = amount / area;
+            for (&index, gradient) in self.particles.iter().zip(Self::area_gradients(&positions)) {
+                particles[index].add_force(gradient * pressure);
+            }
+        }
+    }
+
+    /// Move the particles so that the body encloses its target area, if it's inflated by area.
+    pub fn project(self: &Self, particles: &mut [Particle], boundary: &Boundary, dt: f64) {
+        if let Inflation::Area { target, compliance } = self.inflation {
+            let positions = self.positions(particles, boundary);
+            let gradients = Self::area_gradients(&positions);
+            let denominator = self
+                .particles
+                .iter()
+                .zip(&gradients)
+                .map(|(&index, gradient)| particles[index].inverse_mass() * gradient.mag_squared())
+                .sum::<f64>()
+                + compliance / dt.powi(2);
+            if denominator == 0.0 {
+                return;
+            }
+            let lambda = -(self.area(particles, boundary) - target) / denominator;
+            for (&index, gradient) in self.particles.iter().zip(gradients) {
+                let inverse_mass = particles[index].inverse_mass();
+                particles[index].pos += gradient * (lambda * inverse_mass);
+            }
+        }
+    }
+}
@@ -0,0 +1,82 @@
+//! Provides the standard pair potentials of molecular dynamics.
+//!
+//! A pair potential U(r) only depends on the distance between two particles, so the force between them is
+//! F = -dU/dr along the line connecting them. Every potential here gives its energy and its force from the same
+//! formula, so that a simulation which only uses them conserves energy.
+//!
+//! Potentials are usually cut off at some radius r_c, past which they are ignored. To keep the energy continuous
+//! at the cutoff, the potential is shifted by U(r_c), so that it is U(r) - U(r_c) inside of the cutoff, and zero
+//! outside of it. The force isn't changed by the shift.
+
+/// A potential between a pair of particles.
+#[derive(Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Potential {
+    /// U = 4ε((σ/r)¹² - (σ/r)⁶), with a well of depth ε at r = 2^(1/6)σ.
+    LennardJones {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        epsilon: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        sigma: f64,
+    },
+    /// U = D(1 - e^(-a(r - r₀)))² - D, with a well of depth D at r₀, whose width is set by a.
+    Morse {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        depth: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        width: f64,
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        equilibrium: f64,
+    },
+    /// U = k·qᵢ·qⱼ/r, using the particles' charges, where k is Coulomb's constant.
+    Coulomb {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        constant: f64,
+    },
+}
+
+impl Potential {
+    /// The potential energy of two particles a distance r apart, whose charges multiply to `charges`.
+    pub fn energy(self: &Self, r: f64, charges: f64) -> f64 {
+        match *self {
+            Potential::LennardJones { epsilon, sigma } => {
+                let s6 = (sigma / r).powi(6);
+                4.0 * epsilon * (s6 * s6 - s6)
+            }
+            Potential::Morse {
+                depth,
+                width,
+                equilibrium,
+            } => depth * (1.0 - (-width * (r - equilibrium)).exp()).powi(2) - depth,
+            Potential::Coulomb { constant } => constant * charges / r,
+        }
+    }
+
+    /// The force between two particles a distance r apart, -dU/dr, where positive is repulsive.
+    pub fn force(self: &Self, r: f64, charges: f64) -> f64 {
+        match *self {
+            Potential::LennardJones { epsilon, sigma } => {
+                let s6 = (sigma / r).powi(6);
+                24.0 * epsilon / r * (2.0 * s6 * s6 - s6)
+            }
+            Potential::Morse {
+                depth,
+                width,
+                equilibrium,
+            } => {
+                let decay = (-width * (r - equilibrium)).exp();
+                -2.0 * depth * width * decay * (1.0 - decay)
+            }
+            Potential::Coulomb { constant } => constant * charges / r.powi(2),
+        }
+    }
+
+    /// The energy, shifted so that it goes to zero at the cutoff (and is zero past it).
+    pub fn shifted_energy(self: &Self, r: f64, charges: f64, cutoff: f64) -> f64 {
+        if r >= cutoff {
+            0.0
+        } else {
+            self.energy(r, charges) - self.energy(cutoff, charges)
+        }
+    }
+}
@@ -0,0 +1,175 @@
+//! Provides named closures, and a registry to find them by name.
+//!
+//! Closures can't be saved to a file, so forces and constraints that are defined by a closure hold it as a
+//! `Named` closure instead. Only its name is saved, and when the system is loaded the name is looked up in a
+//! `Registry` of the closures that the program defines.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::physics::registry::{Named, Registry};
+//! use rusty_particle_physics_2d::prelude::*;
+//!
+//! // a uniform wind that pushes every particle to the right
+//! let registry = Registry::new().force("wind", |_particle: &Particle| Vec2::new(5.0, 0.0));
+//!
+//! let mut sim = System::new();
+//! sim.forces.push(Force::RawForce {
+//!     function: registry.force_function("wind").unwrap(),
+//! });
+//! ```
+
+use crate::physics::constraint::Constraint;
+use crate::physics::force::Force;
+use crate::physics::particle::Particle;
+use crate::physics::system::System;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// The closure of a general constraint, which takes the positions of the constrained particles.
+pub type ConstraintFunction = dyn Fn(Vec<Vec2>) -> f64;
+
+/// The closure of a raw force, which gives the force on a particle.
+pub type ForceFunction = dyn Fn(&Particle) -> Vec2;
+
+/// A closure with a name, which is all that is saved of it.
+pub struct Named<F: ?Sized> {
+    pub name: String,
+    /// None when the closure was loaded, until it's resolved from a registry
+    function: Option<Rc<F>>,
+}
+
+impl<F: ?Sized> Clone for Named<F> {
+    fn clone(&self) -> Named<F> {
+        Named {
+            name: self.name.clone(),
+            function: self.function.clone(),
+        }
+    }
+}
+
+impl<F: ?Sized> Named<F> {
+    /// A name without its closure, which has to be resolved from a registry before it's used.
+    pub fn unresolved(name: &str) -> Named<F> {
+        Named {
+            name: name.to_string(),
+            function: None,
+        }
+    }
+
+    /// The closure, or None if it hasn't been resolved yet.
+    pub fn function(self: &Self) -> Option<&F> {
+        self.function.as_deref()
+    }
+}
+
+impl Named<ConstraintFunction> {
+    /// Name the closure of a general constraint.
+    pub fn constraint(
+        name: &str,
+        function: impl Fn(Vec<Vec2>) -> f64 + 'static,
+    ) -> Named<ConstraintFunction> {
+        Named {
+            name: name.to_string(),
+            function: Some(Rc::new(function)),
+        }
+    }
+}
+
+impl Named<ForceFunction> {
+    /// Name the closure of a raw force.
+    pub fn force(
+        name: &str,
+        function: impl Fn(&Particle) -> Vec2 + 'static,
+    ) -> Named<ForceFunction> {
+        Named {
+            name: name.to_string(),
+            function: Some(Rc::new(function)),
+        }
+    }
+}
+
+/// The named closures that forces and constraints can be built from, and that loaded systems are resolved with.
+#[derive(Clone, Default)]
+pub struct Registry {
+    constraints: HashMap<String, Rc<ConstraintFunction>>,
+    forces: HashMap<String, Rc<ForceFunction>>,
+}
+
+impl Registry {
+    /// Create an empty registry.
+    pub fn new() -> Registry {
+        Registry::default()
+    }
+
+    /// A builder method to register the closure of a general constraint after creating it.
+    pub fn constraint(
+        mut self: Self,
+        name: &str,
+        function: impl Fn(Vec<Vec2>) -> f64 + 'static,
+    ) -> Registry {
+        self.constraints.insert(name.to_string(), Rc::new(function));
+        self
+    }
+
+    /// A builder method to register the closure of a raw force after creating it.
+    pub fn force(
+        mut self: Self,
+        name: &str,
+        function: impl Fn(&Particle) -> Vec2 + 'static,
+    ) -> Registry {
+        self.forces.insert(name.to_string(), Rc::new(function));
+        self
+    }
+
+    /// The registered closure of a general constraint.
+    pub fn constraint_function(self: &Self, name: &str) -> Option<Named<ConstraintFunction>> {
+        self.constraints.get(name).map(|function| Named {
+            name: name.to_string(),
+            function: Some(function.clone()),
+        })
+    }
+
+    /// The registered closure of a raw force.
+    pub fn force_function(self: &Self, name: &str) -> Option<Named<ForceFunction>> {
+        self.forces.get(name).map(|function| Named {
+            name: name.to_string(),
+            function: Some(function.clone()),
+        })
+    }
+
+    /// Give every named closure in a system the registered closure with its name. Returns the name of the first
+    /// closure that isn't registered as an error.
+    pub fn resolve(self: &Self, system: &mut System) -> Result<(), String> {
+        for constraint in &mut system.constraints {
+            if let Constraint::Constraint { function, .. } = constraint {
+                let registered = self.constraints.get(&function.name).ok_or(&function.name)?;
+                function.function = Some(registered.clone());
+            }
+        }
+        for force in &mut system.forces {
+            if let Force::RawForce { function } = force {
+                let registered = self.forces.get(&function.name).ok_or(&function.name)?;
+                function.function = Some(registered.clone());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<F: ?Sized> serde::Serialize for Named<F> {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.name)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de, F: ?Sized> serde::Deserialize<'de> for Named<F> {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Named<F>, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        Ok(Named::unresolved(&name))
+    }
+}
@@ -1 +1,301 @@
+//! Provides rigid bodies that are made out of clusters of particles.
+//!
+//! Since the particle is the most fundamental object in this engine, a rigid body is nothing more than a group
+//! of particles that are pulled towards a rigidly transformed copy of their rest shape. This is the shape matching
+//! method of Müller, Heidelberger, Teschner, Gross. 2005. [Link](https://doi.org/10.1145/1073204.1073216).
+//!
+//! Each substep, the optimal rotation of the cluster is found through a polar decomposition of the cluster's
+//! covariance matrix, and every member particle is then moved towards its goal position. The center of mass,
+//! orientation, and angular velocity of the body are never stored, they are derived from the member particles.
+//!
+//! In a periodic box the member particles are wrapped one by one, so a body can straddle the boundary. Every
+//! offset within a body is then found with the minimum image convention, which only requires that the body is
+//! smaller than half of the box.
+//!
+//! Members with infinite mass are pinned in place, and the rest of the body turns about them: the center of mass is
+//! the pinned member's position, and only the members with a finite mass are weighted by their mass.
 
+use crate::physics::boundary::Boundary;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+/// A rigid body made from a cluster of particles.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RigidBody {
+    /// indices of the member particles in the System
+    pub particles: Vec<usize>,
+    /// positions of the member particles in the rest shape, relative to the rest center of mass
+    rest_offsets: Vec<Vec2>,
+    /// how strongly the particles are pulled towards their goal positions each substep, in [0, 1]
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub stiffness: f64,
+}
+
+impl RigidBody {
+    /// Create a rigid body out of the given particles, using their current positions as the rest shape.
+    ///
+    /// Panics if there are no particles.
+    pub fn new(
+        members: Vec<usize>,
+        particles: &[Particle],
+        boundary: &Boundary,
+        stiffness: f64,
+    ) -> RigidBody {
+        assert!(
+            !members.is_empty(),
+            "a rigid body needs at least one particle"
+        );
+        let rest_center = Self::mass_weighted_center(&members, particles, boundary);
+        let rest_offsets = members
+            .iter()
+            .map(|&index| boundary.displacement(particles[index].pos, rest_center))
+            .collect();
+
+        RigidBody {
+            particles: members,
+            rest_offsets,
+            stiffness: stiffness.clamp(0.0, 1.0),
+        }
+    }
+
+    /// The positions of the member particles in the rest shape, relative to the rest center of mass.
+    pub fn rest_offsets(self: &Self) -> &[Vec2] {
+        &self.rest_offsets
+    }
+
+    /// Total mass of the body, leaving out pinned members (with infinite mass).
+    pub fn mass(self: &Self, particles: &[Particle]) -> f64 {
+        self.particles
+            .iter()
+            .map(|&index| finite_mass(&particles[index]))
+            .sum()
+    }
+
+    /// Whether any member has infinite mass, which pins the body in place (although it can still turn about the
+    /// pinned member).
+    pub fn is_pinned(self: &Self, particles: &[Particle]) -> bool {
+        pinned_member(&self.particles, particles).is_some()
+    }
+
+    /// Current center of mass of the body (inside the box, if the boundary is periodic).
+    pub fn center_of_mass(self: &Self, particles: &[Particle], boundary: &Boundary) -> Vec2 {
+        Self::mass_weighted_center(&self.particles, particles, boundary)
+    }
+
+    /// Current velocity of the body's center of mass, which is the velocity of a pinned member if there is one.
+    pub fn velocity(self: &Self, particles: &[Particle]) -> Vec2 {
+        if let Some(pinned) = pinned_member(&self.particles, particles) {
+            return particles[pinned].vel;
+        }
+        let mass = self.mass(particles);
+        if mass == 0.0 {
+            return Vec2::zero();
+        }
+        let mut momentum = Vec2::zero();
+        for &index in &self.particles {
+            momentum += particles[index].vel * particles[index].mass;
+        }
+        momentum / mass
+    }
+
+    /// Current orientation angle of the body, measured counterclockwise from the rest shape.
+    ///
+    /// This is the angle of the optimal rotation found through the polar decomposition of the
+    /// cluster's covariance matrix.
+    pub fn angle(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        let center = self.center_of_mass(particles, boundary);
+        polar_decomposition_angle(self.covariance(particles, boundary, center))
+    }
+
+    /// Moment of inertia of the body about its current center of mass.
+    pub fn moment_of_inertia(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        let center = self.center_of_mass(particles, boundary);
+        self.particles
+            .iter()
+            .map(|&index| {
+                finite_mass(&particles[index])
+                    * boundary
+                        .displacement(particles[index].pos, center)
+                        .mag_squared()
+            })
+            .sum()
+    }
+
+    /// Current angular velocity of the body about its center of mass (counterclockwise is positive).
+    ///
+    /// Found from ω = L / I, where L is the angular momentum and I is the moment of inertia of
+    /// the member particles about the center of mass.
+    pub fn angular_velocity(self: &Self, particles: &[Particle], boundary: &Boundary) -> f64 {
+        let center = self.center_of_mass(particles, boundary);
+        let velocity = self.velocity(particles);
+        let mut angular_momentum = 0.0;
+        let mut inertia = 0.0;
+        for &index in &self.particles {
+            let particle = &particles[index];
+            let r = boundary.displacement(particle.pos, center);
+            angular_momentum += finite_mass(particle) * r.cross(&(particle.vel - velocity));
+            inertia += finite_mass(particle) * r.mag_squared();
+        }
+        if inertia > 0.0 {
+            angular_momentum / inertia
+        } else {
+            0.0
+        }
+    }
+
+    /// Pull the member particles towards the rigidly transformed rest shape. Pinned members aren't moved.
+    pub fn project(self: &Self, particles: &mut [Particle], boundary: &Boundary) {
+        let center = self.center_of_mass(particles, boundary);
+        let angle = polar_decomposition_angle(self.covariance(particles, boundary, center));
+        for (&index, offset) in self.particles.iter().zip(&self.rest_offsets) {
+            let goal = center + offset.rotate(angle);
+            let particle = &mut particles[index];
+            if particle.inverse_mass() == 0.0 {
+                continue;
+            }
+            particle.pos += boundary.displacement(goal, particle.pos) * self.stiffness;
+        }
+    }
+
+    /// Rigidly move every member particle by an offset.
+    pub fn translate(self: &Self, particles: &mut [Particle], offset: Vec2) {
+        for &index in &self.particles {
+            particles[index].pos += offset;
+        }
+    }
+
+    /// Rigidly rotate every member particle counterclockwise about the center of mass.
+    pub fn rotate(self: &Self, particles: &mut [Particle], boundary: &Boundary, angle: f64) {
+        let center = self.center_of_mass(particles, boundary);
+        for &index in &self.particles {
+            let particle = &mut particles[index];
+            let offset = boundary.displacement(particle.pos, center);
+            particle.pos += offset.rotate(angle) - offset;
+        }
+    }
+
+    /// Change the velocity of every member particle as if the whole body was given an extra
+    /// linear velocity and an extra angular velocity about its center of mass.
+    pub fn add_velocity(
+        self: &Self,
+        particles: &mut [Particle],
+        boundary: &Boundary,
+        linear: Vec2,
+        angular: f64,
+    ) {
+        let center = self.center_of_mass(particles, boundary);
+        for &index in &self.particles {
+            let particle = &mut particles[index];
+            let r = boundary.displacement(particle.pos, center);
+            particle.vel += linear + Vec2::new(-r.y, r.x) * angular;
+        }
+    }
+
+    /// The mass weighted covariance matrix between the current and rest shapes, A = Σ m p qᵀ.
+    fn covariance(
+        self: &Self,
+        particles: &[Particle],
+        boundary: &Boundary,
+        center: Vec2,
+    ) -> [[f64; 2]; 2] {
+        let mut a = [[0.0; 2]; 2];
+        for (&index, q) in self.particles.iter().zip(&self.rest_offsets) {
+            let particle = &particles[index];
+            let (p, mass) = (
+                boundary.displacement(particle.pos, center),
+                finite_mass(particle),
+            );
+            a[0][0] += mass * p.x * q.x;
+            a[0][1] += mass * p.x * q.y;
+            a[1][0] += mass * p.y * q.x;
+            a[1][1] += mass * p.y * q.y;
+        }
+        a
+    }
+
+    /// The center of mass of the members, measured from the images that are closest to the first member. This is
+    /// the position of a pinned member if there is one, and the plain average if every member is massless.
+    fn mass_weighted_center(
+        members: &[usize],
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) -> Vec2 {
+        if let Some(pinned) = pinned_member(members, particles) {
+            return particles[pinned].pos;
+        }
+        let origin = particles[members[0]].pos;
+        let total_mass: f64 = members.iter().map(|&index| particles[index].mass).sum();
+        let mut weighted = Vec2::zero();
+        for &index in members {
+            let weight = if total_mass > 0.0 {
+                particles[index].mass / total_mass
+            } else {
+                1.0 / members.len() as f64
+            };
+            weighted += boundary.displacement(particles[index].pos, origin) * weight;
+        }
+        boundary.wrap(origin + weighted)
+    }
+}
+
+/// The first of the members with infinite mass, if there is one.
+fn pinned_member(members: &[usize], particles: &[Particle]) -> Option<usize> {
+    members
+        .iter()
+        .copied()
+        .find(|&index| particles[index].mass.is_infinite())
+}
+
+/// A particle's mass, where a pinned particle (with infinite mass) counts as massless.
+fn finite_mass(particle: &Particle) -> f64 {
+    if particle.mass.is_finite() {
+        particle.mass
+    } else {
+        0.0
+    }
+}
+
+/// The angle of the rotation part R of the polar decomposition A = RS of a 2x2 matrix.
+///
+/// In 2d the optimal rotation has a closed form, so no iteration is needed.
+pub(crate) fn polar_decomposition_angle(a: [[f64; 2]; 2]) -> f64 {
+    (a[1][0] - a[0][1]).atan2(a[0][0] + a[1][1])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prelude::*;
+
+    #[test]
+    fn pinned_member() {
+        let mut sim = System::new();
+        sim.add_particle(Particle::new().pos(0.0, 0.0).mass(f64::INFINITY));
+        sim.add_particle(Particle::new().pos(1.0, 0.0).vel(0.0, 1.0));
+        sim.add_particle(Particle::new().pos(0.0, 1.0).vel(-1.0, 0.0));
+        let body = sim.add_rigid_body(vec![0, 1, 2], 1.0);
+        for _ in 0..10 {
+            sim.step_forward(0.01);
+        }
+
+        // the body turns about its pinned member, which stays put
+        let pin = sim.particles[0].pos;
+        assert_eq!((pin.x, pin.y), (0.0, 0.0));
+        for particle in &sim.particles[1..] {
+            assert!((particle.pos.mag() - 1.0).abs() < 1e-9);
+        }
+        let rigid_body = &sim.rigid_bodies[body];
+        assert_eq!(rigid_body.mass(&sim.particles), 2.0 * Particle::new().mass);
+        assert!(rigid_body.is_pinned(&sim.particles));
+        assert!(rigid_body.angle(&sim.particles, &sim.boundary) > 0.0);
+        assert!(rigid_body
+            .angular_velocity(&sim.particles, &sim.boundary)
+            .is_finite());
+    }
+
+    #[test]
+    #[should_panic(expected = "a rigid body needs at least one particle")]
+    fn empty() {
+        System::new().add_rigid_body(Vec::new(), 1.0);
+    }
+}
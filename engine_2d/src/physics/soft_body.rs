@@ -0,0 +1,544 @@
+//! Provides soft bodies, which are lattices of particles connected by distance constraints (mass-spring systems).
+//!
+//! A soft body is built from a rectangular or triangular lattice. Neighboring particles are connected by three
+//! kinds of distance constraints, each with its own compliance:
+//!
+//! - stretch: between nearest neighbors, which keeps the lattice from stretching
+//! - shear: across the diagonals of each cell, which keeps the cells from collapsing sideways
+//! - bend: between particles two apart along the lattice directions, which keeps the body from folding
+//!
+//! A soft, stretchy membrane (like cloth) has compliant shear and bend constraints, and a jelly has
+//! them all. A compliance of 0.0 is perfectly stiff, and an infinite compliance leaves that kind of constraint out
+//! entirely. Edges of the lattice can be pinned in place, by giving their particles infinite mass.
+//!
+//! Springs don't behave much like real materials, so a lattice can also be given a `Material`, which adds a strain
+//! constraint to each of its triangles. Usually the distance constraints are then left out.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::constraint::{Constraint, Material};
+use crate::physics::mesh::PolygonMesh;
+use crate::physics::particle::Particle;
+use crate::vec2::Vec2;
+
+/// The arrangement of a lattice's particles.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LatticeKind {
+    /// Particles on a square grid.
+    Rectangular,
+    /// Particles on a grid of equilateral triangles, where every other row is offset by half of the spacing.
+    /// Its triangles can't deform without stretching, so its stretch compliance mostly decides how soft it is.
+    Triangular,
+}
+
+/// An edge of a lattice.
+#[derive(Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Edge {
+    Bottom,
+    Right,
+    Top,
+    Left,
+}
+
+/// The (column, row) coordinates of a particle in a lattice.
+type Coordinates = (usize, usize);
+
+/// A pair of particles in a lattice.
+type Pair = (Coordinates, Coordinates);
+
+/// A description of a soft body, which `System::add_soft_body` builds.
+pub struct Lattice {
+    pub kind: LatticeKind,
+    /// position of the bottom left particle
+    pub origin: Vec2,
+    pub columns: usize,
+    pub rows: usize,
+    /// distance between neighboring particles
+    pub spacing: f64,
+    /// mass of each particle
+    pub particle_mass: f64,
+    pub radius: f64,
+    pub color: (u8, u8, u8, u8),
+    pub group: u32,
+    pub stretch_compliance: f64,
+    pub shear_compliance: f64,
+    pub bend_compliance: f64,
+    /// edges whose particles are pinned in place
+    pub pinned: Vec<Edge>,
+    /// material of the strain constraints on each triangle, if there are any
+    pub material: Option<Material>,
+}
+
+impl Lattice {
+    /// A rectangular lattice of particles.
+    pub fn rectangular(columns: usize, rows: usize, spacing: f64) -> Lattice {
+        Lattice::new(LatticeKind::Rectangular, columns, rows, spacing)
+    }
+
+    /// A triangular lattice of particles.
+    pub fn triangular(columns: usize, rows: usize, spacing: f64) -> Lattice {
+        Lattice::new(LatticeKind::Triangular, columns, rows, spacing)
+    }
+
+    fn new(kind: LatticeKind, columns: usize, rows: usize, spacing: f64) -> Lattice {
+        let particle = Particle::new();
+        Lattice {
+            kind,
+            origin: Vec2::zero(),
+            columns,
+            rows,
+            spacing,
+            particle_mass: particle.mass,
+            radius: spacing / 2.0,
+            color: particle.color,
+            group: 0,
+            stretch_compliance: 0.0,
+            shear_compliance: 0.0,
+            bend_compliance: f64::INFINITY,
+            pinned: Vec::new(),
+            material: None,
+        }
+    }
+
+    /// A builder method to give the lattice a specific origin (its bottom left particle) after creating it.
+    pub fn origin(mut self: Self, x: f64, y: f64) -> Lattice {
+        self.origin = Vec2::new(x, y);
+        self
+    }
+
+    /// A builder method to give each of the lattice's particles a specific mass after creating it.
+    pub fn particle_mass(mut self: Self, mass: f64) -> Lattice {
+        self.particle_mass = mass;
+        self
+    }
+
+    /// A builder method to give the lattice's particles a specific radius after creating it.
+    pub fn radius(mut self: Self, radius: f64) -> Lattice {
+        self.radius = radius;
+        self
+    }
+
+    /// A builder method to give the lattice's particles a specific color after creating it.
+    pub fn color(mut self: Self, color: (u8, u8, u8, u8)) -> Lattice {
+        self.color = color;
+        self
+    }
+
+    /// A builder method to give the lattice's particles a specific group after creating it.
+    pub fn group(mut self: Self, group: u32) -> Lattice {
+        self.group = group;
+        self
+    }
+
+    /// A builder method to give the stretch, shear and bend constraints specific compliances after creating it.
+    pub fn compliance(mut self: Self, stretch: f64, shear: f64, bend: f64) -> Lattice {
+        self.stretch_compliance = stretch;
+        self.shear_compliance = shear;
+        self.bend_compliance = bend;
+        self
+    }
+
+    /// A builder method to pin an edge of the lattice in place after creating it.
+    pub fn pin(mut self: Self, edge: Edge) -> Lattice {
+        self.pinned.push(edge);
+        self
+    }
+
+    /// A builder method to give each of the lattice's triangles a strain constraint of some material after
+    /// creating it.
+    pub fn material(mut self: Self, material: Material) -> Lattice {
+        self.material = Some(material);
+        self
+    }
+
+    /// The position of the particle in a column and row.
+    fn position(self: &Self, column: usize, row: usize) -> Vec2 {
+        match self.kind {
+            LatticeKind::Rectangular => {
+                self.origin + Vec2::new(column as f64, row as f64) * self.spacing
+            }
+            LatticeKind::Triangular => {
+                let offset = if row % 2 == 1 { 0.5 } else { 0.0 };
+                self.origin
+                    + Vec2::new(
+                        (column as f64 + offset) * self.spacing,
+                        row as f64 * self.spacing * 3.0_f64.sqrt() / 2.0,
+                    )
+            }
+        }
+    }
+
+    fn is_pinned(self: &Self, column: usize, row: usize) -> bool {
+        self.pinned.iter().any(|edge| match edge {
+            Edge::Bottom => row == 0,
+            Edge::Right => column + 1 == self.columns,
+            Edge::Top => row + 1 == self.rows,
+            Edge::Left => column == 0,
+        })
+    }
+
+    /// The particles that the lattice is made of, row by row from the bottom left.
+    pub(crate) fn particles(self: &Self) -> Vec<Particle> {
+        let mut particles = Vec::with_capacity(self.columns * self.rows);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let pos = self.position(column, row);
+                let mass = if self.is_pinned(column, row) {
+                    f64::INFINITY
+                } else {
+                    self.particle_mass
+                };
+                particles.push(
+                    Particle::new()
+                        .pos(pos.x, pos.y)
+                        .mass(mass)
+                        .radius(self.radius)
+                        .color(self.color)
+                        .group(self.group),
+                );
+            }
+        }
+        particles
+    }
+
+    /// The (stretch, shear, bend) pairs of the lattice, as (column, row) coordinates.
+    fn pairs(self: &Self) -> (Vec<Pair>, Vec<Pair>, Vec<Pair>) {
+        let (mut stretch, mut shear, mut bend) = (Vec::new(), Vec::new(), Vec::new());
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let here = (column, row);
+                // every pair is found once, from its lower (or left) particle
+                let connect = |list: &mut Vec<_>, dx: isize, dy: usize| {
+                    if let Some(there) = self.offset(here, dx, dy) {
+                        list.push((here, there));
+                    }
+                };
+                match self.kind {
+                    LatticeKind::Rectangular => {
+                        connect(&mut stretch, 1, 0);
+                        connect(&mut stretch, 0, 1);
+                        connect(&mut shear, 1, 1);
+                        connect(&mut shear, -1, 1);
+                        connect(&mut bend, 2, 0);
+                        connect(&mut bend, 0, 2);
+                    }
+                    LatticeKind::Triangular => {
+                        let (up_left, up_right) = self.up_neighbors(here);
+                        stretch.extend(self.offset(here, 1, 0).map(|there| (here, there)));
+                        stretch.extend(up_left.map(|there| (here, there)));
+                        stretch.extend(up_right.map(|there| (here, there)));
+                        // the long diagonals of the rhombi made by each pair of neighboring triangles
+                        shear.extend(
+                            up_right
+                                .and_then(|up| self.offset(up, 1, 0))
+                                .map(|t| (here, t)),
+                        );
+                        shear.extend(
+                            up_left
+                                .and_then(|up| self.offset(up, -1, 0))
+                                .map(|t| (here, t)),
+                        );
+                        shear.extend(self.offset(here, 0, 2).map(|there| (here, there)));
+                        bend.extend(self.offset(here, 2, 0).map(|there| (here, there)));
+                        bend.extend(
+                            up_right
+                                .and_then(|up| self.up_neighbors(up).1)
+                                .map(|there| (here, there)),
+                        );
+                        bend.extend(
+                            up_left
+                                .and_then(|up| self.up_neighbors(up).0)
+                                .map(|there| (here, there)),
+                        );
+                    }
+                }
+            }
+        }
+        (stretch, shear, bend)
+    }
+
+    /// The triangles of the lattice, as (column, row) coordinates in counterclockwise order.
+    fn triangles(self: &Self) -> Vec<[Coordinates; 3]> {
+        let mut triangles = Vec::new();
+        for row in 0..self.rows.saturating_sub(1) {
+            for column in 0..self.columns {
+                let here = (column, row);
+                match self.kind {
+                    LatticeKind::Rectangular => {
+                        if let (Some(right), Some(up), Some(diagonal)) = (
+                            self.offset(here, 1, 0),
+                            self.offset(here, 0, 1),
+                            self.offset(here, 1, 1),
+                        ) {
+                            triangles.push([here, right, diagonal]);
+                            triangles.push([here, diagonal, up]);
+                        }
+                    }
+                    LatticeKind::Triangular => {
+                        let (up_left, up_right) = self.up_neighbors(here);
+                        if let (Some(right), Some(up_right)) = (self.offset(here, 1, 0), up_right) {
+                            triangles.push([here, right, up_right]);
+                        }
+                        if let (Some(up_left), Some(up_right)) = (up_left, up_right) {
+                            triangles.push([here, up_right, up_left]);
+                        }
+                    }
+                }
+            }
+        }
+        triangles
+    }
+
+    /// The boundary of the lattice, as (column, row) coordinates in counterclockwise order.
+    fn outline(self: &Self) -> Vec<Coordinates> {
+        if self.columns < 2 || self.rows < 2 {
+            return (0..self.rows)
+                .flat_map(|row| (0..self.columns).map(move |column| (column, row)))
+                .collect();
+        }
+        let (last_column, last_row) = (self.columns - 1, self.rows - 1);
+        let bottom = (0..last_column).map(|column| (column, 0));
+        let right = (0..last_row).map(|row| (last_column, row));
+        let top = (1..=last_column).rev().map(|column| (column, last_row));
+        let left = (1..=last_row).rev().map(|row| (0, row));
+        bottom.chain(right).chain(top).chain(left).collect()
+    }
+
+    /// The coordinates (dx, dy) away from a particle, if it's inside of the lattice.
+    fn offset(
+        self: &Self,
+        (column, row): Coordinates,
+        dx: isize,
+        dy: usize,
+    ) -> Option<Coordinates> {
+        let column = column.checked_add_signed(dx)?;
+        let row = row + dy;
+        if column < self.columns && row < self.rows {
+            Some((column, row))
+        } else {
+            None
+        }
+    }
+
+    /// The up-left and up-right neighbors of a particle in a triangular lattice.
+    fn up_neighbors(self: &Self, here: Coordinates) -> (Option<Coordinates>, Option<Coordinates>) {
+        // odd rows are shifted right by half of the spacing
+        if here.1 % 2 == 1 {
+            (self.offset(here, 0, 1), self.offset(here, 1, 1))
+        } else {
+            (self.offset(here, -1, 1), self.offset(here, 0, 1))
+        }
+    }
+}
+
+/// A handle to a soft body that has been added to a `System`.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SoftBody {
+    /// indices of the body's particles in `System::particles`, row by row from the bottom left (a body meshed
+    /// from a polygon is a single row, starting with its outline)
+    pub particles: Vec<usize>,
+    pub columns: usize,
+    pub rows: usize,
+    /// indices of the body's stretch constraints in `System::constraints`
+    pub stretch: Vec<usize>,
+    /// indices of the body's shear constraints in `System::constraints`
+    pub shear: Vec<usize>,
+    /// indices of the body's bend constraints in `System::constraints`
+    pub bend: Vec<usize>,
+    /// indices of the body's area constraints in `System::constraints`
+    pub area: Vec<usize>,
+    /// indices of the body's strain constraints in `System::constraints`
+    pub strain: Vec<usize>,
+    /// the body's triangles (as indices in `System::particles`), in counterclockwise order
+    pub triangles: Vec<[usize; 3]>,
+    /// the boundary of the body (as indices in `System::particles`), in counterclockwise order
+    pub outline: Vec<usize>,
+}
+
+impl SoftBody {
+    /// Build the handle for a lattice whose particles start at `first_particle` in `System::particles`, and
+    /// return it with the body's constraints (stretch, then shear, then bend, then strain) to be added starting
+    /// at `first_constraint` in `System::constraints`.
+    pub(crate) fn new(
+        lattice: &Lattice,
+        first_particle: usize,
+        first_constraint: usize,
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) -> (SoftBody, Vec<Constraint>) {
+        let index = |(column, row): Coordinates| first_particle + row * lattice.columns + column;
+        let (stretch, shear, bend) = lattice.pairs();
+        let mut constraints = Vec::new();
+        let mut indices = [Vec::new(), Vec::new(), Vec::new()];
+        for (k, (pairs, compliance)) in [
+            (stretch, lattice.stretch_compliance),
+            (shear, lattice.shear_compliance),
+            (bend, lattice.bend_compliance),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            if compliance.is_infinite() {
+                continue;
+            }
+            for (a, b) in pairs {
+                indices[k].push(first_constraint + constraints.len());
+                constraints.push(Constraint::distance(
+                    index(a),
+                    index(b),
+                    particles,
+                    boundary,
+                    compliance,
+                ));
+            }
+        }
+        let [stretch, shear, bend] = indices;
+        let triangles: Vec<[usize; 3]> = lattice
+            .triangles()
+            .into_iter()
+            .map(|triangle| triangle.map(index))
+            .collect();
+        let mut strain = Vec::new();
+        if let Some(material) = lattice.material {
+            for &triangle in &triangles {
+                strain.push(first_constraint + constraints.len());
+                constraints.push(Constraint::strain(triangle, particles, boundary, material));
+            }
+        }
+        let body = SoftBody {
+            particles: (0..lattice.columns * lattice.rows)
+                .map(|k| first_particle + k)
+                .collect(),
+            columns: lattice.columns,
+            rows: lattice.rows,
+            stretch,
+            shear,
+            bend,
+            area: Vec::new(),
+            strain,
+            triangles,
+            outline: lattice.outline().into_iter().map(index).collect(),
+        };
+        (body, constraints)
+    }
+
+    /// Build the handle for a mesh whose particles start at `first_particle` in `System::particles`, and return
+    /// it with the body's constraints (edges, then areas, then strain) to be added starting at `first_constraint`
+    /// in `System::constraints`. The edges are the body's stretch constraints.
+    pub(crate) fn from_mesh(
+        mesh: &PolygonMesh,
+        triangles: &[[usize; 3]],
+        outline: &[usize],
+        first_particle: usize,
+        first_constraint: usize,
+        particles: &[Particle],
+        boundary: &Boundary,
+    ) -> (SoftBody, Vec<Constraint>) {
+        let triangles: Vec<[usize; 3]> = triangles
+            .iter()
+            .map(|triangle| triangle.map(|k| first_particle + k))
+            .collect();
+        let mut constraints = Vec::new();
+        let mut stretch = Vec::new();
+        if mesh.edge_compliance.is_finite() {
+            let mut edges: Vec<(usize, usize)> = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+                .map(|(a, b)| (a.min(b), a.max(b)))
+                .collect();
+            edges.sort_unstable();
+            edges.dedup();
+            for (a, b) in edges {
+                stretch.push(first_constraint + constraints.len());
+                constraints.push(Constraint::distance(
+                    a,
+                    b,
+                    particles,
+                    boundary,
+                    mesh.edge_compliance,
+                ));
+            }
+        }
+        let mut area = Vec::new();
+        if mesh.area_compliance.is_finite() {
+            for &triangle in &triangles {
+                area.push(first_constraint + constraints.len());
+                constraints.push(Constraint::triangle_area(
+                    triangle,
+                    particles,
+                    boundary,
+                    mesh.area_compliance,
+                ));
+            }
+        }
+        let mut strain = Vec::new();
+        if let Some(material) = mesh.material {
+            for &triangle in &triangles {
+                strain.push(first_constraint + constraints.len());
+                constraints.push(Constraint::strain(triangle, particles, boundary, material));
+            }
+        }
+        let count = particles.len() - first_particle;
+        let body = SoftBody {
+            particles: (first_particle..particles.len()).collect(),
+            columns: count,
+            rows: 1,
+            stretch,
+            shear: Vec::new(),
+            bend: Vec::new(),
+            area,
+            strain,
+            triangles,
+            outline: outline.iter().map(|k| first_particle + k).collect(),
+        };
+        (body, constraints)
+    }
+
+    /// The index in `System::particles` of the particle in a column and row of the body.
+    pub fn particle(self: &Self, column: usize, row: usize) -> usize {
+        self.particles[row * self.columns + column]
+    }
+
+    /// Change the compliances of the body's stretch, shear and bend constraints.
+    pub fn set_compliance(
+        self: &Self,
+        constraints: &mut [Constraint],
+        stretch: f64,
+        shear: f64,
+        bend: f64,
+    ) {
+        for (indices, value) in [
+            (&self.stretch, stretch),
+            (&self.shear, shear),
+            (&self.bend, bend),
+        ] {
+            for &index in indices {
+                if let Constraint::Distance { compliance, .. } = &mut constraints[index] {
+                    *compliance = value;
+                }
+            }
+        }
+    }
+
+    /// The center of mass of the body's movable (finite mass) particles.
+    pub fn center_of_mass(self: &Self, particles: &[Particle]) -> Vec2 {
+        let (weighted, mass) = self
+            .particles
+            .iter()
+            .map(|&index| &particles[index])
+            .filter(|particle| particle.mass.is_finite())
+            .fold((Vec2::zero(), 0.0), |(weighted, mass), particle| {
+                (
+                    weighted + particle.pos * particle.mass,
+                    mass + particle.mass,
+                )
+            });
+        if mass > 0.0 {
+            weighted / mass
+        } else {
+            Vec2::zero()
+        }
+    }
+}
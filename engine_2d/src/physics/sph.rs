@@ -0,0 +1,64 @@
+//! Smoothing kernels for smoothed particle hydrodynamics (SPH), normalized for two dimensions.
+//!
+//! Each kernel W(r, h) is zero outside of the kernel radius h, and integrates to one over the plane.
+//! The kernels are the ones from Müller, Charypar, Gross. 2003. [Link](https://doi.org/10.2312/SCA03/154-159).
+
+use crate::vec2::Vec2;
+
+use std::f64::consts::PI;
+
+/// The poly6 kernel, W = 4/(πh⁸) (h² - r²)³. It takes r² so that no square root is needed.
+pub fn poly6(r_squared: f64, h: f64) -> f64 {
+    if r_squared >= h.powi(2) {
+        0.0
+    } else {
+        4.0 / (PI * h.powi(8)) * (h.powi(2) - r_squared).powi(3)
+    }
+}
+
+/// The gradient of the spiky kernel, W = 10/(πh⁵) (h - r)³, with respect to r.
+///
+/// Unlike poly6, its gradient doesn't vanish as r → 0, which keeps particles from clumping together.
+pub fn spiky_gradient(r: Vec2, h: f64) -> Vec2 {
+    let distance = r.mag();
+    if distance >= h || distance == 0.0 {
+        Vec2::zero()
+    } else {
+        r * (-30.0 / (PI * h.powi(5)) * (h - distance).powi(2) / distance)
+    }
+}
+
+/// The laplacian of the viscosity kernel, ∇²W = 40/(πh⁵) (h - r).
+pub fn viscosity_laplacian(r: f64, h: f64) -> f64 {
+    if r >= h {
+        0.0
+    } else {
+        40.0 / (PI * h.powi(5)) * (h - r)
+    }
+}
+
+/// The cohesion kernel from Akinci, Akinci, Teschner. 2013. [Link](https://doi.org/10.1145/2508363.2508395).
+///
+/// It is repulsive at short range (r < h/2 roughly) and attractive at long range, which lets it hold a fluid
+/// together without making the particles clump.
+pub fn cohesion(r: f64, h: f64) -> f64 {
+    let normalization = 35840.0 / (209.0 * PI * h.powi(8));
+    if r > h || r <= 0.0 {
+        0.0
+    } else if r > h / 2.0 {
+        normalization * (h - r).powi(3) * r.powi(3)
+    } else {
+        normalization * (2.0 * (h - r).powi(3) * r.powi(3) - h.powi(6) / 64.0)
+    }
+}
+
+/// The adhesion kernel from Akinci, Akinci, Teschner. 2013. [Link](https://doi.org/10.1145/2508363.2508395).
+///
+/// It's only non-zero for h/2 < r ≤ h, so it attracts fluid towards a solid without pulling it inside.
+pub fn adhesion(r: f64, h: f64) -> f64 {
+    if r > h || r <= h / 2.0 {
+        0.0
+    } else {
+        0.686_724 / h.powf(2.25) * (-4.0 * r.powi(2) / h + 6.0 * r - 2.0 * h).powf(0.25)
+    }
+}
@@ -16,20 +16,74 @@
 //! }
 //! ```
 
+use crate::import::image::{Image, ImageBody};
+use crate::import::svg::{Svg, SvgScene};
+use crate::physics::boundary::Boundary;
+use crate::physics::collision::{Collisions, StaticCollider};
 use crate::physics::constraint::Constraint;
+use crate::physics::diagnostics::Diagnostics;
+use crate::physics::fluid::Fluid;
 use crate::physics::force::Force;
+use crate::physics::joint::Joint;
+use crate::physics::mesh::PolygonMesh;
 use crate::physics::particle::Particle;
+use crate::physics::pneumatic::PneumaticBody;
+use crate::physics::rigid_body::RigidBody;
+use crate::physics::soft_body::{Lattice, SoftBody};
+use crate::physics::thermal::HeatTransfer;
+use crate::physics::thermostat::Thermostat;
+use crate::recording::Recorder;
+use crate::vec2::Vec2;
+
+use std::collections::HashMap;
+use std::rc::Rc;
 
 /// A system is a collection of interacting particles, global forces, and constraints.
-#[derive(Default)]
+#[derive(Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct System {
     pub running: bool,
     pub substeps: u32,
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     time: f64,
     pub particles: Vec<Particle>,
     particle_id_counter: u32,
     pub forces: Vec<Force>,
     pub constraints: Vec<Constraint>,
+    pub rigid_bodies: Vec<RigidBody>,
+    pub soft_bodies: Vec<SoftBody>,
+    pub pneumatic_bodies: Vec<PneumaticBody>,
+    pub joints: Vec<Joint>,
+    pub fluids: Vec<Fluid>,
+    pub colliders: Vec<StaticCollider>,
+    pub collisions: Option<Collisions>,
+    pub heat_transfer: Option<HeatTransfer>,
+    pub thermostats: Vec<Thermostat>,
+    pub boundary: Boundary,
+    /// the state that the system had when it first stepped forward, which `System::restart` returns to
+    #[cfg_attr(feature = "serde", serde(skip))]
+    initial_state: Option<Snapshot>,
+    /// snapshots saved by name
+    #[cfg_attr(feature = "serde", serde(skip))]
+    snapshots: HashMap<String, Snapshot>,
+    /// records the system's frames as it steps forward
+    #[cfg_attr(feature = "serde", serde(skip))]
+    recorder: Option<Recorder>,
+}
+
+/// A copy of the entire state of a system at some moment, which the system can later be restored to.
+///
+/// Snapshots are immutable and share their state, so cloning one is cheap.
+#[derive(Clone)]
+pub struct Snapshot {
+    state: Rc<System>,
+}
+
+impl Snapshot {
+    /// The simulation time when the snapshot was taken.
+    pub fn time(self: &Self) -> f64 {
+        self.state.time
+    }
 }
 
 impl System {
@@ -45,38 +99,433 @@ impl System {
     /// Step the system's simulation forward in time by amount dt.
     pub fn step_forward(self: &mut Self, dt: f64) {
         if self.running {
-            let sub_dt = dt / self.substeps as f64;
-            for force in &self.forces {
-                force.send();
+            if self.initial_state.is_none() {
+                self.capture_initial_state();
             }
+            let sub_dt = dt / self.substeps as f64;
             for _ in 0..self.substeps {
+                for force in &self.forces {
+                    force.send(&mut self.particles, &self.colliders, &self.boundary);
+                }
+                for body in &self.pneumatic_bodies {
+                    body.apply_pressure(&mut self.particles, &self.boundary);
+                }
                 for particle in &mut self.particles {
                     particle.update(sub_dt);
                 }
                 for constraint in &self.constraints {
-                    constraint.project(sub_dt);
+                    constraint.project(&mut self.particles, &self.boundary, sub_dt);
+                }
+                for body in &self.pneumatic_bodies {
+                    body.project(&mut self.particles, &self.boundary, sub_dt);
+                }
+                for body in &self.rigid_bodies {
+                    body.project(&mut self.particles, &self.boundary);
+                }
+                for joint in &self.joints {
+                    joint.project(&self.rigid_bodies, &mut self.particles, &self.boundary);
+                }
+                for fluid in &mut self.fluids {
+                    fluid.project(&mut self.particles, &self.boundary);
+                }
+                for particle in &mut self.particles {
+                    for collider in &self.colliders {
+                        collider.collide(particle);
+                    }
+                }
+                if let Some(collisions) = &mut self.collisions {
+                    collisions.project(&mut self.particles, &self.boundary);
                 }
                 for particle in &mut self.particles {
                     particle.update_vel(sub_dt);
                 }
+                if let Some(collisions) = &self.collisions {
+                    collisions.restitute(&mut self.particles);
+                }
+                for joint in &self.joints {
+                    joint.apply_motor(
+                        &self.rigid_bodies,
+                        &mut self.particles,
+                        &self.boundary,
+                        sub_dt,
+                    );
+                }
+                for fluid in &self.fluids {
+                    fluid.correct_velocities(&mut self.particles, &self.boundary, sub_dt);
+                }
+                for thermostat in &mut self.thermostats {
+                    thermostat.apply(&mut self.particles, sub_dt);
+                }
+                if let Some(heat_transfer) = &mut self.heat_transfer {
+                    heat_transfer.exchange(&mut self.particles, &self.boundary, sub_dt);
+                }
+                self.wrap_particles();
             }
             // TODO: check and handle collisions, clear particle forces, remove broken constraints... //
             self.time += dt;
+            if let Some(recorder) = &self.recorder {
+                recorder.step(self);
+            }
         }
     }
 
+    /// Move every particle that has left a periodic box back into it. Bodies, joints, and fluids measure every
+    /// offset with the minimum image convention, so each particle is wrapped on its own, even if its body straddles
+    /// the boundary.
+    fn wrap_particles(self: &mut Self) {
+        if !self.boundary.is_periodic() {
+            return;
+        }
+        for particle in &mut self.particles {
+            particle.pos = self.boundary.wrap(particle.pos);
+        }
+    }
+
+    /// Take a snapshot of the system's current state (particles, forces, constraints, bodies, time, etc).
+    pub fn snapshot(self: &Self) -> Snapshot {
+        // the saved snapshots are shared pointers, so they are cheap to clone and then drop from the copy
+        let mut state = self.clone();
+        state.initial_state = None;
+        state.snapshots.clear();
+        state.recorder = None;
+        Snapshot {
+            state: Rc::new(state),
+        }
+    }
+
+    /// Restore the system to the exact state of a snapshot. Whether the system is running, its initial state,
+    /// its saved snapshots, and its recorder are kept.
+    pub fn restore(self: &mut Self, snapshot: &Snapshot) {
+        let running = self.running;
+        let initial_state = self.initial_state.take();
+        let snapshots = std::mem::take(&mut self.snapshots);
+        let recorder = self.recorder.take();
+        *self = (*snapshot.state).clone();
+        self.running = running;
+        self.initial_state = initial_state;
+        self.snapshots = snapshots;
+        self.recorder = recorder;
+    }
+
+    /// Save a snapshot of the system's current state under a name, replacing any snapshot with that name.
+    pub fn save_snapshot(self: &mut Self, name: &str) {
+        let snapshot = self.snapshot();
+        self.snapshots.insert(name.to_string(), snapshot);
+    }
+
+    /// Restore the system to the snapshot saved under a name. Returns false (and leaves the system alone) if there
+    /// is no snapshot with that name.
+    pub fn restore_snapshot(self: &mut Self, name: &str) -> bool {
+        match self.snapshots.get(name).cloned() {
+            Some(snapshot) => {
+                self.restore(&snapshot);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Remove the snapshot saved under a name, returning it if there was one.
+    pub fn remove_snapshot(self: &mut Self, name: &str) -> Option<Snapshot> {
+        self.snapshots.remove(name)
+    }
+
+    /// The names of the saved snapshots.
+    pub fn snapshot_names(self: &Self) -> impl Iterator<Item = &str> {
+        self.snapshots.keys().map(String::as_str)
+    }
+
+    /// Make the system's current state its initial state, which `System::restart` returns to. This happens
+    /// automatically the first time the system steps forward.
+    pub fn capture_initial_state(self: &mut Self) {
+        self.initial_state = Some(self.snapshot());
+    }
+
+    /// Restore the system to its initial state. Does nothing if the system hasn't stepped forward yet (it's
+    /// already in its initial state).
+    pub fn restart(self: &mut Self) {
+        if let Some(initial_state) = self.initial_state.clone() {
+            self.restore(&initial_state);
+        }
+    }
+
+    /// Start recording the system's frames as it steps forward, beginning with its current state. Replaces (and
+    /// returns) the recorder that was already recording, if there was one.
+    pub fn record(self: &mut Self, recorder: Recorder) -> Option<Recorder> {
+        recorder.start(self);
+        self.recorder.replace(recorder)
+    }
+
+    /// Stop recording, and return the recorder so that it can be finished.
+    pub fn stop_recording(self: &mut Self) -> Option<Recorder> {
+        self.recorder.take()
+    }
+
     /// Get the current simulation time
     pub fn time(self: &Self) -> f64 {
         self.time
     }
 
-    /// Add a new particle to the system. Returns a reference to that particle.
-    pub fn add_particle(self: &mut Self, particle: Particle) {
+    /// Get the total kinetic energy of the system's particles, Σ½mv².
+    pub fn kinetic_energy(self: &Self) -> f64 {
+        self.particles.iter().map(|p| p.kinetic_energy()).sum()
+    }
+
+    /// Get the total potential energy stored by the system's forces (and the gas in its pneumatic bodies), including
+    /// the constant forces on each particle.
+    pub fn potential_energy(self: &Self) -> f64 {
+        self.particles
+            .iter()
+            .map(|p| p.potential_energy())
+            .sum::<f64>()
+            + self
+                .forces
+                .iter()
+                .map(|force| force.potential_energy(&self.particles, &self.boundary))
+                .sum::<f64>()
+            + self
+                .pneumatic_bodies
+                .iter()
+                .map(|body| body.potential_energy(&self.particles, &self.boundary))
+                .sum::<f64>()
+    }
+
+    /// Get the total thermal energy stored in the system's particles, Σm·c·T, leaving out particles with infinite
+    /// mass (whose thermal energy is infinite).
+    pub fn thermal_energy(self: &Self) -> f64 {
+        self.particles
+            .iter()
+            .filter(|p| p.mass.is_finite())
+            .map(|p| p.thermal_energy())
+            .sum()
+    }
+
+    /// Get the total mass of the system's particles, leaving out particles with infinite mass (which are pinned in
+    /// place, and so are outside of the dynamics).
+    pub fn total_mass(self: &Self) -> f64 {
+        self.particles
+            .iter()
+            .map(|p| p.mass)
+            .filter(|mass| mass.is_finite())
+            .sum()
+    }
+
+    /// Get the total linear momentum of the system's particles, Σmv.
+    pub fn momentum(self: &Self) -> Vec2 {
+        self.particles
+            .iter()
+            .fold(Vec2::zero(), |total, p| total + p.momentum())
+    }
+
+    /// Get the total angular momentum of the system's particles about a point, Σ(r - point)×mv.
+    pub fn angular_momentum(self: &Self, point: Vec2) -> f64 {
+        self.particles
+            .iter()
+            .map(|p| (p.pos - point).cross(&p.momentum()))
+            .sum()
+    }
+
+    /// Get the center of mass of the system's particles (with finite mass), or the origin if there are none.
+    pub fn center_of_mass(self: &Self) -> Vec2 {
+        let mass = self.total_mass();
+        if mass == 0.0 {
+            return Vec2::zero();
+        }
+        self.particles
+            .iter()
+            .filter(|p| p.mass.is_finite())
+            .fold(Vec2::zero(), |total, p| total + p.pos * p.mass)
+            / mass
+    }
+
+    /// Measure all of the system's conserved quantities at once (see `Diagnostics`).
+    pub fn diagnostics(self: &Self) -> Diagnostics {
+        Diagnostics {
+            time: self.time,
+            mass: self.total_mass(),
+            momentum: self.momentum(),
+            angular_momentum: self.angular_momentum(Vec2::zero()),
+            kinetic_energy: self.kinetic_energy(),
+            potential_energy: self.potential_energy(),
+            thermal_energy: self.thermal_energy(),
+            center_of_mass: self.center_of_mass(),
+        }
+    }
+
+    /// Add a new particle to the system. Returns the index of that particle in `System::particles`.
+    pub fn add_particle(self: &mut Self, particle: Particle) -> usize {
         self.particles.push(particle.id(self.particle_id_counter));
         self.particle_id_counter += 1;
+        self.particles.len() - 1
     }
 
-    pub fn add_constraint(self: &mut Self, constraint: Constraint) {
+    /// Add many particles to the system (ex: from a generator). Returns the indices of the particles in
+    /// `System::particles`.
+    pub fn add_particles(self: &mut Self, particles: Vec<Particle>) -> Vec<usize> {
+        particles
+            .into_iter()
+            .map(|particle| self.add_particle(particle))
+            .collect()
+    }
+
+    /// Add a constraint to the system. Returns the index of the constraint in `System::constraints`.
+    pub fn add_constraint(self: &mut Self, constraint: Constraint) -> usize {
         self.constraints.push(constraint);
+        self.constraints.len() - 1
+    }
+
+    /// Turn a cluster of already added particles into a rigid body, using their current positions as the
+    /// rest shape. Returns the index of the body in `System::rigid_bodies`.
+    pub fn add_rigid_body(self: &mut Self, particles: Vec<usize>, stiffness: f64) -> usize {
+        self.rigid_bodies.push(RigidBody::new(
+            particles,
+            &self.particles,
+            &self.boundary,
+            stiffness,
+        ));
+        self.rigid_bodies.len() - 1
+    }
+
+    /// Build a soft body out of a lattice of new particles and distance constraints. Returns the index of the
+    /// body's handle in `System::soft_bodies`.
+    pub fn add_soft_body(self: &mut Self, lattice: Lattice) -> usize {
+        let first_particle = self.particles.len();
+        for particle in lattice.particles() {
+            self.add_particle(particle);
+        }
+        let (body, constraints) = SoftBody::new(
+            &lattice,
+            first_particle,
+            self.constraints.len(),
+            &self.particles,
+            &self.boundary,
+        );
+        self.constraints.extend(constraints);
+        self.soft_bodies.push(body);
+        self.soft_bodies.len() - 1
+    }
+
+    /// Build a soft body out of a polygon meshed into new particles, connected by distance constraints along the
+    /// mesh's edges, and area or strain constraints on its triangles. Returns the index of the body's handle in
+    /// `System::soft_bodies`.
+    pub fn add_polygon_soft_body(self: &mut Self, mesh: PolygonMesh) -> usize {
+        let (positions, triangles, outline) = mesh.build();
+        let first_particle = self.particles.len();
+        for particle in mesh.particles(&positions) {
+            self.add_particle(particle);
+        }
+        let (body, constraints) = SoftBody::from_mesh(
+            &mesh,
+            &triangles,
+            &outline,
+            first_particle,
+            self.constraints.len(),
+            &self.particles,
+            &self.boundary,
+        );
+        self.constraints.extend(constraints);
+        self.soft_bodies.push(body);
+        self.soft_bodies.len() - 1
+    }
+
+    /// Build a rigid body out of a polygon filled with new particles (the mesh's constraints are ignored). Returns
+    /// the index of the body in `System::rigid_bodies`, whose particles are the new particles.
+    pub fn add_polygon_rigid_body(self: &mut Self, mesh: PolygonMesh, stiffness: f64) -> usize {
+        let (positions, _, _) = mesh.build();
+        let first_particle = self.particles.len();
+        for particle in mesh.particles(&positions) {
+            self.add_particle(particle);
+        }
+        self.add_rigid_body((first_particle..self.particles.len()).collect(), stiffness)
+    }
+
+    /// Build every shape of an SVG drawing into bodies and static colliders. Returns handles to everything that
+    /// was built.
+    pub fn add_svg(self: &mut Self, svg: &Svg) -> SvgScene {
+        svg.build(self)
+    }
+
+    /// Turn an image into a grid of colored particles, which may be connected to their neighbors. Returns a handle
+    /// to the particles and constraints that were added.
+    pub fn add_image(self: &mut Self, image: &Image) -> ImageBody {
+        image.build(self)
+    }
+
+    /// Inflate a closed loop of already added particles. The loop is put in counterclockwise order, and its edge
+    /// constraints are added if it has an edge compliance. Returns the index of the body in
+    /// `System::pneumatic_bodies`.
+    pub fn add_pneumatic_body(self: &mut Self, mut body: PneumaticBody) -> usize {
+        if body.area(&self.particles, &self.boundary) < 0.0 {
+            body.particles.reverse();
+        }
+        if let Some(compliance) = body.edge_compliance {
+            let count = body.particles.len();
+            for k in 0..count {
+                let (a, b) = (body.particles[k], body.particles[(k + 1) % count]);
+                let edge = Constraint::Distance {
+                    particles: (a, b),
+                    length: self
+                        .boundary
+                        .displacement(self.particles[a].pos, self.particles[b].pos)
+                        .mag(),
+                    compliance,
+                };
+                body.edges.push(self.add_constraint(edge));
+            }
+        }
+        self.pneumatic_bodies.push(body);
+        self.pneumatic_bodies.len() - 1
+    }
+
+    /// Connect two rigid bodies with a joint, using their current configuration as the joint's rest state.
+    /// Returns the index of the joint in `System::joints`.
+    pub fn add_joint(self: &mut Self, mut joint: Joint) -> usize {
+        joint.attach(&self.rigid_bodies, &self.particles, &self.boundary);
+        self.joints.push(joint);
+        self.joints.len() - 1
+    }
+
+    /// Turn every particle in the fluid's groups into a fluid particle. Returns the index of the fluid
+    /// in `System::fluids`.
+    pub fn add_fluid(self: &mut Self, fluid: Fluid) -> usize {
+        self.fluids.push(fluid);
+        self.fluids.len() - 1
+    }
+
+    /// Connect each particle to the next with a distance constraint at its current length, and the last particle
+    /// back to the first if the links are closed (ex: a rope, or a ring). Returns the indices of the constraints in
+    /// `System::constraints`.
+    pub fn add_links(
+        self: &mut Self,
+        particles: &[usize],
+        closed: bool,
+        compliance: f64,
+    ) -> Vec<usize> {
+        let count = particles.len();
+        let links = if closed && count > 2 {
+            count
+        } else {
+            count.saturating_sub(1)
+        };
+        (0..links)
+            .map(|k| {
+                let (a, b) = (particles[k], particles[(k + 1) % count]);
+                let link = Constraint::distance(a, b, &self.particles, &self.boundary, compliance);
+                self.add_constraint(link)
+            })
+            .collect()
+    }
+
+    /// Add an immovable collider to the system. Returns the index of the collider in `System::colliders`.
+    pub fn add_collider(self: &mut Self, collider: StaticCollider) -> usize {
+        self.colliders.push(collider);
+        self.colliders.len() - 1
+    }
+
+    /// Add a thermostat that acts on some groups of particles. Returns the index of the thermostat in
+    /// `System::thermostats`.
+    pub fn add_thermostat(self: &mut Self, thermostat: Thermostat) -> usize {
+        self.thermostats.push(thermostat);
+        self.thermostats.len() - 1
     }
 }
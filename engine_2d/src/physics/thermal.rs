@@ -0,0 +1,127 @@
+//! Provides heat transfer between particles, and between particles and their surroundings.
+//!
+//! Every particle carries a temperature and a specific heat, so it stores a thermal energy of m·c·T. Heat is
+//! conducted between particles that are touching (or close to touching), at a rate proportional to their
+//! temperature difference, and is radiated to an ambient reservoir following the Stefan-Boltzmann law.
+//!
+//! Conduction only moves heat around, so it conserves the total thermal energy of the system. Radiation
+//! exchanges heat with the reservoir, which is outside of the system, so the heat that it has carried away is
+//! kept track of. Together with the heat that inelastic collisions produce, this lets energy conservation be
+//! checked across mechanical to thermal conversion.
+
+use crate::physics::boundary::Boundary;
+use crate::physics::neighbor_grid::NeighborGrid;
+use crate::physics::particle::Particle;
+
+use std::f64::consts::PI;
+
+/// Heat transfer between the particles of a system, and with an ambient reservoir.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct HeatTransfer {
+    /// thermal conductance between two touching particles (heat per unit time per unit temperature difference)
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub conductivity: f64,
+    /// how far apart (beyond touching) two particles can be and still conduct heat
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub range: f64,
+    /// radiative coupling to the ambient reservoir, ε·σ, where 0.0 turns radiation off
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub emissivity: f64,
+    /// temperature of the ambient reservoir
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub ambient_temperature: f64,
+    /// the total heat that has been radiated out of the system into the reservoir
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    radiated: f64,
+}
+
+impl HeatTransfer {
+    /// Conduct heat between touching particles, with a conductance.
+    pub fn new(conductivity: f64) -> HeatTransfer {
+        HeatTransfer {
+            conductivity,
+            range: 0.0,
+            emissivity: 0.0,
+            ambient_temperature: 0.0,
+            radiated: 0.0,
+        }
+    }
+
+    /// A builder method to let particles conduct heat from further apart than touching after creating it.
+    pub fn range(mut self: Self, range: f64) -> HeatTransfer {
+        self.range = range;
+        self
+    }
+
+    /// A builder method to make particles radiate to an ambient reservoir after creating it.
+    pub fn radiation(mut self: Self, emissivity: f64, ambient_temperature: f64) -> HeatTransfer {
+        self.emissivity = emissivity;
+        self.ambient_temperature = ambient_temperature;
+        self
+    }
+
+    /// The total heat that has been radiated out of the system (negative if the reservoir has heated it).
+    pub fn radiated_energy(self: &Self) -> f64 {
+        self.radiated
+    }
+
+    /// Exchange heat between neighboring particles, and with the reservoir, over a timestep.
+    pub fn exchange(self: &mut Self, particles: &mut [Particle], boundary: &Boundary, dt: f64) {
+        if self.conductivity != 0.0 {
+            self.conduct(particles, boundary, dt);
+        }
+        if self.emissivity != 0.0 {
+            self.radiate(particles, dt);
+        }
+    }
+
+    fn conduct(self: &Self, particles: &mut [Particle], boundary: &Boundary, dt: f64) {
+        let max_radius = particles.iter().map(|p| p.radius).fold(0.0, f64::max);
+        let reach = 2.0 * max_radius + self.range;
+        if reach <= 0.0 {
+            return;
+        }
+        let grid = NeighborGrid::from_particles_in(particles, 0..particles.len(), reach, boundary);
+        let mut heat = vec![0.0; particles.len()];
+        for i in 0..particles.len() {
+            for j in grid.candidates(particles[i].pos) {
+                if j <= i {
+                    continue;
+                }
+                let distance = boundary
+                    .displacement(particles[i].pos, particles[j].pos)
+                    .mag();
+                if distance > particles[i].radius + particles[j].radius + self.range {
+                    continue;
+                }
+                let flow =
+                    self.conductivity * (particles[j].temperature - particles[i].temperature) * dt;
+                heat[i] += flow;
+                heat[j] -= flow;
+            }
+        }
+        for (particle, heat) in particles.iter_mut().zip(heat) {
+            particle.add_heat(heat);
+        }
+    }
+
+    fn radiate(self: &mut Self, particles: &mut [Particle], dt: f64) {
+        for particle in particles.iter_mut() {
+            // the "surface area" of a 2d particle is its circumference
+            let heat = self.emissivity
+                * 2.0
+                * PI
+                * particle.radius
+                * (particle.temperature.powi(4) - self.ambient_temperature.powi(4))
+                * dt;
+            // a pinned particle (with infinite mass) can't be heated or cooled, and is left out of the system's
+            // thermal energy, so it doesn't radiate
+            let capacity = particle.heat_capacity();
+            if capacity > 0.0 && capacity.is_finite() {
+                particle.add_heat(-heat);
+                self.radiated += heat;
+            }
+        }
+    }
+}
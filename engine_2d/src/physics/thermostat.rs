@@ -0,0 +1,245 @@
+//! Provides thermostats, which hold the kinetic temperature of a set of particles at a target.
+//!
+//! The kinetic temperature is measured from the particles' velocities, using the 2d equipartition theorem
+//! ½·N_f·k_B·T = Σ½mv², with two degrees of freedom per particle and k_B = 1 (so temperatures are in units of
+//! energy). It's a different quantity from `Particle::temperature`, which is the internal temperature that heat
+//! transfer acts on.
+//!
+//! Three thermostats are available:
+//!
+//! - Langevin: every particle feels friction and a random force, which together sample the canonical
+//!   ensemble exactly. The velocity update is the exact Ornstein-Uhlenbeck step used by the BAOAB integrator,
+//!   Leimkuhler, Matthews. 2013. [Link](https://doi.org/10.1093/amrx/abs010).
+//! - Berendsen: all of the velocities are rescaled so that the temperature relaxes exponentially towards
+//!   the target. It is simple and smooth, but doesn't sample a canonical ensemble.
+//!   Berendsen et al. 1984. [Link](https://doi.org/10.1063/1.448118).
+//! - Nosé-Hoover chain: a chain of extra, deterministic "heat bath" variables that the particles exchange
+//!   kinetic energy with, integrated with the Trotter splitting from Martyna, Tuckerman, Tobias, Klein. 1996.
+//!   [Link](https://doi.org/10.1080/00268979600100761).
+
+use crate::physics::particle::Particle;
+use crate::random::Rng;
+
+/// The kind of thermostat, and its state.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ThermostatKind {
+    /// friction is the damping rate γ (per unit time), and rng provides the random forces
+    Langevin {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        friction: f64,
+        rng: Rng,
+    },
+    /// time_constant is how quickly the temperature relaxes towards the target
+    Berendsen {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        time_constant: f64,
+    },
+    /// time_constant is the period of the heat bath's oscillations, which sets the bath masses
+    NoseHoover {
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        time_constant: f64,
+        /// velocities of the chain's heat bath variables, from the one coupled to the particles outward
+        #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+        bath_velocities: Vec<f64>,
+    },
+}
+
+/// Holds the kinetic temperature of every particle in some groups at a target temperature.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Thermostat {
+    /// which `Particle::group`s the thermostat acts on
+    pub groups: Vec<u32>,
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
+    pub target_temperature: f64,
+    pub kind: ThermostatKind,
+}
+
+impl Thermostat {
+    /// A Langevin thermostat, with a friction rate and a seed for its random forces.
+    pub fn langevin(
+        groups: Vec<u32>,
+        target_temperature: f64,
+        friction: f64,
+        seed: u64,
+    ) -> Thermostat {
+        Thermostat {
+            groups,
+            target_temperature,
+            kind: ThermostatKind::Langevin {
+                friction,
+                rng: Rng::new(seed),
+            },
+        }
+    }
+
+    /// A Berendsen thermostat, with the time constant of its exponential relaxation.
+    pub fn berendsen(groups: Vec<u32>, target_temperature: f64, time_constant: f64) -> Thermostat {
+        Thermostat {
+            groups,
+            target_temperature,
+            kind: ThermostatKind::Berendsen { time_constant },
+        }
+    }
+
+    /// A Nosé-Hoover chain thermostat, with the period of its heat bath and the length of the chain.
+    /// A chain length of 1 is the original Nosé-Hoover thermostat.
+    pub fn nose_hoover(
+        groups: Vec<u32>,
+        target_temperature: f64,
+        time_constant: f64,
+        chain_length: usize,
+    ) -> Thermostat {
+        Thermostat {
+            groups,
+            target_temperature,
+            kind: ThermostatKind::NoseHoover {
+                time_constant,
+                bath_velocities: vec![0.0; chain_length.max(1)],
+            },
+        }
+    }
+
+    /// The temperature the thermostat is trying to reach.
+    pub fn target_temperature(self: &Self) -> f64 {
+        self.target_temperature
+    }
+
+    /// Whether a particle is one that the thermostat acts on. Particles with infinite mass are never included.
+    pub fn contains(self: &Self, particle: &Particle) -> bool {
+        self.groups.contains(&particle.group) && particle.mass.is_finite() && particle.mass > 0.0
+    }
+
+    /// The current kinetic temperature of the thermostat's particles, Σmv²/N_f with N_f = 2N.
+    pub fn kinetic_temperature(self: &Self, particles: &[Particle]) -> f64 {
+        let (kinetic_energy, count) = self.kinetic_energy(particles);
+        if count == 0 {
+            0.0
+        } else {
+            kinetic_energy / count as f64
+        }
+    }
+
+    /// Σ½mv² of the thermostat's particles, and how many of them there are.
+    fn kinetic_energy(self: &Self, particles: &[Particle]) -> (f64, usize) {
+        particles
+            .iter()
+            .filter(|p| self.contains(p))
+            .fold((0.0, 0), |(energy, count), p| {
+                (energy + 0.5 * p.mass * p.vel.mag_squared(), count + 1)
+            })
+    }
+
+    /// Act on the particles' velocities over a timestep.
+    pub fn apply(self: &mut Self, particles: &mut [Particle], dt: f64) {
+        let target = self.target_temperature;
+        let (kinetic_energy, count) = self.kinetic_energy(particles);
+        if count == 0 {
+            return;
+        }
+        let degrees_of_freedom = 2.0 * count as f64;
+        let scale = match &mut self.kind {
+            ThermostatKind::Langevin { friction, rng } => {
+                let decay = (-*friction * dt).exp();
+                let noise = (1.0 - decay.powi(2)).sqrt();
+                for particle in particles.iter_mut() {
+                    if self.groups.contains(&particle.group)
+                        && particle.mass.is_finite()
+                        && particle.mass > 0.0
+                    {
+                        particle.vel = particle.vel * decay
+                            + rng.normal_vec2() * (noise * (target / particle.mass).sqrt());
+                    }
+                }
+                return;
+            }
+            ThermostatKind::Berendsen { time_constant } => {
+                let temperature = 2.0 * kinetic_energy / degrees_of_freedom;
+                if temperature <= 0.0 {
+                    return;
+                }
+                (1.0 + dt / time_constant.max(dt) * (target / temperature - 1.0))
+                    .max(0.0)
+                    .sqrt()
+            }
+            ThermostatKind::NoseHoover {
+                time_constant,
+                bath_velocities,
+            } => nose_hoover_chain(
+                bath_velocities,
+                2.0 * kinetic_energy,
+                degrees_of_freedom,
+                target,
+                *time_constant,
+                dt,
+            ),
+        };
+        for particle in particles.iter_mut() {
+            if self.contains(particle) {
+                particle.vel *= scale;
+            }
+        }
+    }
+}
+
+/// Propagate a Nosé-Hoover chain over a timestep, given twice the particles' kinetic energy.
+/// Returns the factor that the particles' velocities are scaled by.
+fn nose_hoover_chain(
+    velocities: &mut [f64],
+    mut twice_kinetic: f64,
+    degrees_of_freedom: f64,
+    target: f64,
+    time_constant: f64,
+    dt: f64,
+) -> f64 {
+    let length = velocities.len();
+    // the first bath variable is coupled to every degree of freedom, the rest to a single one each
+    let mass = |k: usize| {
+        if k == 0 {
+            degrees_of_freedom * target * time_constant.powi(2)
+        } else {
+            target * time_constant.powi(2)
+        }
+    };
+    let force = |k: usize, velocities: &[f64], twice_kinetic: f64| {
+        if k == 0 {
+            (twice_kinetic - degrees_of_freedom * target) / mass(0)
+        } else {
+            (mass(k - 1) * velocities[k - 1].powi(2) - target) / mass(k)
+        }
+    };
+    if mass(0) <= 0.0 {
+        return 1.0;
+    }
+    let quarter = dt / 4.0;
+    let eighth = dt / 8.0;
+    let mut scale = 1.0;
+    for _ in 0..2 {
+        // a half step inward along the chain, then the particles, then a half step back out
+        for k in (0..length).rev() {
+            let outer = if k + 1 < length {
+                velocities[k + 1]
+            } else {
+                0.0
+            };
+            velocities[k] *= (-outer * eighth).exp();
+            velocities[k] += force(k, velocities, twice_kinetic) * quarter;
+            velocities[k] *= (-outer * eighth).exp();
+        }
+        let factor = (-velocities[0] * dt / 2.0).exp();
+        scale *= factor;
+        twice_kinetic *= factor.powi(2);
+        for k in 0..length {
+            let outer = if k + 1 < length {
+                velocities[k + 1]
+            } else {
+                0.0
+            };
+            velocities[k] *= (-outer * eighth).exp();
+            velocities[k] += force(k, velocities, twice_kinetic) * quarter;
+            velocities[k] *= (-outer * eighth).exp();
+        }
+    }
+    scale
+}
@@ -0,0 +1,69 @@
+//! Provides a small, seedable pseudo-random number generator.
+//!
+//! Simulations that use randomness (thermostats, scene generators, etc) should be reproducible, so every random
+//! number in the engine comes from a generator that was created from an explicit seed. The generator is
+//! xoshiro256** (Blackman, Vigna), seeded through SplitMix64. It is fast and statistically strong, but it isn't
+//! cryptographically secure.
+
+use crate::vec2::Vec2;
+
+use std::f64::consts::PI;
+
+/// A seedable pseudo-random number generator.
+#[derive(Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rng {
+    state: [u64; 4],
+}
+
+impl Rng {
+    /// Create a new generator from a seed. The same seed always gives the same sequence of numbers.
+    pub fn new(seed: u64) -> Rng {
+        let mut splitmix = seed;
+        let mut next = || {
+            splitmix = splitmix.wrapping_add(0x9e37_79b9_7f4a_7c15);
+            let mut z = splitmix;
+            z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+            z ^ (z >> 31)
+        };
+        Rng {
+            state: [next(), next(), next(), next()],
+        }
+    }
+
+    /// A uniformly distributed u64.
+    pub fn next_u64(self: &mut Self) -> u64 {
+        let result = self.state[1].wrapping_mul(5).rotate_left(7).wrapping_mul(9);
+        let t = self.state[1] << 17;
+        self.state[2] ^= self.state[0];
+        self.state[3] ^= self.state[1];
+        self.state[1] ^= self.state[2];
+        self.state[0] ^= self.state[3];
+        self.state[2] ^= t;
+        self.state[3] = self.state[3].rotate_left(45);
+        result
+    }
+
+    /// A uniformly distributed f64 in [0, 1).
+    pub fn uniform(self: &mut Self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A uniformly distributed f64 in [min, max).
+    pub fn range(self: &mut Self, min: f64, max: f64) -> f64 {
+        min + (max - min) * self.uniform()
+    }
+
+    /// A normally distributed f64 with a mean of 0 and a standard deviation of 1 (Box-Muller transform).
+    pub fn normal(self: &mut Self) -> f64 {
+        let u = 1.0 - self.uniform();
+        let v = self.uniform();
+        (-2.0 * u.ln()).sqrt() * (2.0 * PI * v).cos()
+    }
+
+    /// A Vec2 whose components are independent and normally distributed, with a standard deviation of 1.
+    pub fn normal_vec2(self: &mut Self) -> Vec2 {
+        Vec2::new(self.normal(), self.normal())
+    }
+}
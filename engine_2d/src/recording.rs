@@ -0,0 +1,494 @@
+//! Provides trajectory recording, so that runs can be analysed offline or replayed without simulating them again.
+//!
+//! A `Recorder` is given to a system with `System::record`, and then writes a frame every few steps (its stride) as
+//! the system steps forward. Each frame holds the id, position, and velocity of every particle, and any custom
+//! fields (like temperature or charge) that the recorder was given.
+//!
+//! Frames are written as CSV, with one row per particle per frame:
+//!
+//! ```text
+//! step,time,id,x,y,vx,vy,<custom fields>
+//! ```
+//!
+//! or in a compact, columnar binary format (all little endian):
+//!
+//! ```text
+//! header: b"RPPFRAME", version (u32), number of fields (u32), then each field's name as a length (u32) and UTF-8
+//! frame:  step (u64), time (f64), number of particles (u32), then the columns: ids (u32 each), then x, y, vx, vy,
+//!         and each custom field (f64 each)
+//! ```
+//!
+//! Either format can be read back as a `Recording`, whose frames can be applied to a system to replay it.
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::prelude::*;
+//! use rusty_particle_physics_2d::recording::{Recorder, Recording};
+//!
+//! let mut sim = System::new();
+//! sim.add_particle(Particle::new().vel(1.0, 0.0));
+//!
+//! let file = std::env::temp_dir().join("recording_example.frames");
+//! let recorder = Recorder::create(&file)
+//!     .unwrap()
+//!     .stride(10)
+//!     .field("temperature", |particle| particle.temperature);
+//! sim.record(recorder);
+//! for _ in 0..100 {
+//!     sim.step_forward(0.01);
+//! }
+//! sim.stop_recording().unwrap().finish().unwrap();
+//!
+//! let recording = Recording::load(&file).unwrap();
+//! assert_eq!(recording.frames.len(), 11);
+//! assert_eq!(recording.fields, vec!["temperature".to_string()]);
+//! assert!((recording.frames[10].positions[0].x - 1.0).abs() < 1e-9);
+//! ```
+
+use crate::import::ImportError;
+use crate::physics::particle::Particle;
+use crate::physics::system::System;
+use crate::vec2::Vec2;
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::rc::Rc;
+
+/// Identifies the binary format, at the start of the file.
+const MAGIC: &[u8; 8] = b"RPPFRAME";
+/// Version of the binary format.
+const VERSION: u32 = 1;
+
+/// The format that a recording is written in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum RecordingFormat {
+    /// Comma separated values, one row per particle per frame
+    Csv,
+    /// A compact, columnar binary format
+    Binary,
+}
+
+impl RecordingFormat {
+    /// The format of a file from its extension (.csv, or .frames for the binary format).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<RecordingFormat> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "csv" => Some(RecordingFormat::Csv),
+            "frames" => Some(RecordingFormat::Binary),
+            _ => None,
+        }
+    }
+}
+
+/// A custom field of each particle to record, by name.
+type FieldFunction = Rc<dyn Fn(&Particle) -> f64>;
+
+/// Where the frames are written, which is shared by the copies of a recorder (in snapshots, etc).
+struct Sink {
+    writer: Box<dyn Write>,
+    /// steps since the recording began
+    steps: u64,
+    header_written: bool,
+    /// the first error while writing, which is reported when the recording is finished
+    error: Option<io::Error>,
+}
+
+/// Records the frames of a system as it steps forward.
+#[derive(Clone)]
+pub struct Recorder {
+    format: RecordingFormat,
+    /// a frame is written every this many steps
+    stride: u64,
+    fields: Vec<(String, FieldFunction)>,
+    sink: Rc<RefCell<Sink>>,
+}
+
+impl Recorder {
+    /// Create a recorder that writes frames in a format to a writer, every step.
+    pub fn new(writer: impl Write + 'static, format: RecordingFormat) -> Recorder {
+        Recorder {
+            format,
+            stride: 1,
+            fields: Vec::new(),
+            sink: Rc::new(RefCell::new(Sink {
+                writer: Box::new(writer),
+                steps: 0,
+                header_written: false,
+                error: None,
+            })),
+        }
+    }
+
+    /// Create a recorder that writes to a new file, in the format given by its extension (.csv or .frames).
+    pub fn create(path: impl AsRef<Path>) -> Result<Recorder, ImportError> {
+        let path = path.as_ref();
+        let format = RecordingFormat::from_path(path).ok_or_else(|| {
+            ImportError::Format(format!(
+                "unknown recording format for {} (expected .csv or .frames)",
+                path.display()
+            ))
+        })?;
+        Ok(Recorder::new(BufWriter::new(File::create(path)?), format))
+    }
+
+    /// A builder method to record a frame every some number of steps after creating it.
+    pub fn stride(mut self: Self, stride: u64) -> Recorder {
+        self.stride = stride.max(1);
+        self
+    }
+
+    /// A builder method to record a custom field of each particle after creating it.
+    pub fn field(
+        mut self: Self,
+        name: &str,
+        function: impl Fn(&Particle) -> f64 + 'static,
+    ) -> Recorder {
+        self.fields.push((name.to_string(), Rc::new(function)));
+        self
+    }
+
+    /// Record the system's first frame, when the recording begins.
+    pub(crate) fn start(self: &Self, system: &System) {
+        self.write_frame(system);
+    }
+
+    /// Count a step of the system, and record a frame if it's on the stride.
+    pub(crate) fn step(self: &Self, system: &System) {
+        let steps = {
+            let mut sink = self.sink.borrow_mut();
+            sink.steps += 1;
+            sink.steps
+        };
+        if steps % self.stride == 0 {
+            self.write_frame(system);
+        }
+    }
+
+    fn write_frame(self: &Self, system: &System) {
+        let mut sink = self.sink.borrow_mut();
+        if sink.error.is_some() {
+            return;
+        }
+        let result = (|| {
+            let sink = &mut *sink;
+            if !sink.header_written {
+                self.write_header(&mut sink.writer)?;
+                sink.header_written = true;
+            }
+            match self.format {
+                RecordingFormat::Csv => self.write_csv(&mut sink.writer, sink.steps, system),
+                RecordingFormat::Binary => self.write_binary(&mut sink.writer, sink.steps, system),
+            }
+        })();
+        if let Err(error) = result {
+            sink.error = Some(error);
+        }
+    }
+
+    fn write_header(self: &Self, writer: &mut dyn Write) -> io::Result<()> {
+        match self.format {
+            RecordingFormat::Csv => {
+                write!(writer, "step,time,id,x,y,vx,vy")?;
+                for (name, _) in &self.fields {
+                    write!(writer, ",{name}")?;
+                }
+                writeln!(writer)
+            }
+            RecordingFormat::Binary => {
+                writer.write_all(MAGIC)?;
+                writer.write_all(&VERSION.to_le_bytes())?;
+                writer.write_all(&(self.fields.len() as u32).to_le_bytes())?;
+                for (name, _) in &self.fields {
+                    writer.write_all(&(name.len() as u32).to_le_bytes())?;
+                    writer.write_all(name.as_bytes())?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    fn write_csv(
+        self: &Self,
+        writer: &mut dyn Write,
+        step: u64,
+        system: &System,
+    ) -> io::Result<()> {
+        let time = system.time();
+        for p in &system.particles {
+            write!(
+                writer,
+                "{step},{time},{},{},{},{},{}",
+                p.id, p.pos.x, p.pos.y, p.vel.x, p.vel.y
+            )?;
+            for (_, function) in &self.fields {
+                write!(writer, ",{}", function(p))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
+    fn write_binary(
+        self: &Self,
+        writer: &mut dyn Write,
+        step: u64,
+        system: &System,
+    ) -> io::Result<()> {
+        let particles = &system.particles;
+        writer.write_all(&step.to_le_bytes())?;
+        writer.write_all(&system.time().to_le_bytes())?;
+        writer.write_all(&(particles.len() as u32).to_le_bytes())?;
+        let mut column = Vec::with_capacity(particles.len() * 8);
+        for p in particles {
+            column.extend_from_slice(&p.id.to_le_bytes());
+        }
+        writer.write_all(&column)?;
+        let mut write_column = |value: &dyn Fn(&Particle) -> f64| {
+            column.clear();
+            for p in particles {
+                column.extend_from_slice(&value(p).to_le_bytes());
+            }
+            writer.write_all(&column)
+        };
+        write_column(&|p| p.pos.x)?;
+        write_column(&|p| p.pos.y)?;
+        write_column(&|p| p.vel.x)?;
+        write_column(&|p| p.vel.y)?;
+        for (_, function) in &self.fields {
+            write_column(function.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Flush the recording, and report the first error that happened while writing it.
+    pub fn finish(self: &Self) -> io::Result<()> {
+        let mut sink = self.sink.borrow_mut();
+        match sink.error.take() {
+            Some(error) => Err(error),
+            None => sink.writer.flush(),
+        }
+    }
+}
+
+/// A frame of a recording.
+#[derive(Clone, Default)]
+pub struct Frame {
+    /// number of steps since the recording began
+    pub step: u64,
+    pub time: f64,
+    pub ids: Vec<u32>,
+    pub positions: Vec<Vec2>,
+    pub velocities: Vec<Vec2>,
+    /// the values of each custom field, in the order of `Recording::fields`
+    pub fields: Vec<Vec<f64>>,
+}
+
+/// A recording that was read back from a file.
+#[derive(Clone, Default)]
+pub struct Recording {
+    /// names of the custom fields
+    pub fields: Vec<String>,
+    pub frames: Vec<Frame>,
+}
+
+impl Recording {
+    /// Read a recording from a file, in the format given by its extension (.csv or .frames).
+    pub fn load(path: impl AsRef<Path>) -> Result<Recording, ImportError> {
+        let path = path.as_ref();
+        match RecordingFormat::from_path(path) {
+            Some(RecordingFormat::Csv) => Recording::from_csv(&std::fs::read_to_string(path)?),
+            Some(RecordingFormat::Binary) => Recording::from_binary(&std::fs::read(path)?),
+            None => Err(ImportError::Format(format!(
+                "unknown recording format for {} (expected .csv or .frames)",
+                path.display()
+            ))),
+        }
+    }
+
+    /// Parse a recording written as CSV.
+    pub fn from_csv(text: &str) -> Result<Recording, ImportError> {
+        let mut lines = text.lines().enumerate();
+        let (_, header) = lines.next().ok_or_else(|| ImportError::Parse {
+            line: 1,
+            message: "missing the header".to_string(),
+        })?;
+        let columns: Vec<&str> = header.split(',').map(str::trim).collect();
+        if columns.len() < 7 || columns[..7] != ["step", "time", "id", "x", "y", "vx", "vy"] {
+            return Err(ImportError::Parse {
+                line: 1,
+                message: "the header should start with step,time,id,x,y,vx,vy".to_string(),
+            });
+        }
+        let mut recording = Recording {
+            fields: columns[7..].iter().map(|name| name.to_string()).collect(),
+            frames: Vec::new(),
+        };
+        for (index, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let error = |message: String| ImportError::Parse {
+                line: index + 1,
+                message,
+            };
+            let values: Vec<&str> = line.split(',').map(str::trim).collect();
+            if values.len() != columns.len() {
+                return Err(error(format!(
+                    "expected {} values, found {}",
+                    columns.len(),
+                    values.len()
+                )));
+            }
+            let number = |column: usize| {
+                values[column]
+                    .parse::<f64>()
+                    .map_err(|_| error(format!("invalid {}: {}", columns[column], values[column])))
+            };
+            let step = values[0]
+                .parse::<u64>()
+                .map_err(|_| error(format!("invalid step: {}", values[0])))?;
+            let id = values[2]
+                .parse::<u32>()
+                .map_err(|_| error(format!("invalid id: {}", values[2])))?;
+            if recording
+                .frames
+                .last()
+                .is_none_or(|frame| frame.step != step)
+            {
+                recording.frames.push(Frame {
+                    step,
+                    time: number(1)?,
+                    fields: vec![Vec::new(); recording.fields.len()],
+                    ..Default::default()
+                });
+            }
+            let frame = recording.frames.last_mut().unwrap();
+            frame.ids.push(id);
+            frame.positions.push(Vec2::new(number(3)?, number(4)?));
+            frame.velocities.push(Vec2::new(number(5)?, number(6)?));
+            for (field, values) in frame.fields.iter_mut().enumerate() {
+                values.push(number(7 + field)?);
+            }
+        }
+        Ok(recording)
+    }
+
+    /// Parse a recording written in the binary format.
+    pub fn from_binary(bytes: &[u8]) -> Result<Recording, ImportError> {
+        let mut reader = Reader { bytes, position: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(ImportError::Format(
+                "not a recording (bad magic number)".to_string(),
+            ));
+        }
+        let version = reader.u32()?;
+        if version != VERSION {
+            return Err(ImportError::Format(format!(
+                "unsupported recording version {version}"
+            )));
+        }
+        let mut recording = Recording::default();
+        for _ in 0..reader.u32()? {
+            let length = reader.u32()? as usize;
+            let name = std::str::from_utf8(reader.take(length)?)
+                .map_err(|_| ImportError::Format("a field's name isn't UTF-8".to_string()))?;
+            recording.fields.push(name.to_string());
+        }
+        while reader.position < bytes.len() {
+            let step = u64::from_le_bytes(reader.array()?);
+            let time = reader.f64()?;
+            let count = reader.u32()? as usize;
+            let ids = (0..count).map(|_| reader.u32()).collect::<Result<_, _>>()?;
+            let mut column = || {
+                (0..count)
+                    .map(|_| reader.f64())
+                    .collect::<Result<Vec<f64>, _>>()
+            };
+            let (x, y, vx, vy) = (column()?, column()?, column()?, column()?);
+            let fields = (0..recording.fields.len())
+                .map(|_| column())
+                .collect::<Result<_, _>>()?;
+            recording.frames.push(Frame {
+                step,
+                time,
+                ids,
+                positions: x.into_iter().zip(y).map(|(x, y)| Vec2::new(x, y)).collect(),
+                velocities: vx
+                    .into_iter()
+                    .zip(vy)
+                    .map(|(x, y)| Vec2::new(x, y))
+                    .collect(),
+                fields,
+            });
+        }
+        Ok(recording)
+    }
+
+    /// The index of the last frame at or before a time (or the first frame, if the time is before it).
+    pub fn frame_at(self: &Self, time: f64) -> usize {
+        self.frames
+            .partition_point(|frame| frame.time <= time)
+            .saturating_sub(1)
+    }
+
+    /// Move a system's particles to their positions and velocities in a frame, matching them by id. Recorded
+    /// particles that the system doesn't have are added to it, with the looks of `Particle::new`.
+    pub fn apply(self: &Self, frame: usize, system: &mut System) {
+        let Some(frame) = self.frames.get(frame) else {
+            return;
+        };
+        let indices: HashMap<u32, usize> = system
+            .particles
+            .iter()
+            .enumerate()
+            .map(|(index, particle)| (particle.id, index))
+            .collect();
+        for (k, &id) in frame.ids.iter().enumerate() {
+            let (pos, vel) = (frame.positions[k], frame.velocities[k]);
+            match indices.get(&id) {
+                Some(&index) => {
+                    system.particles[index].pos = pos;
+                    system.particles[index].vel = vel;
+                }
+                None => system
+                    .particles
+                    .push(Particle::new().id(id).pos(pos.x, pos.y).vel(vel.x, vel.y)),
+            }
+        }
+    }
+}
+
+/// Reads the little endian values of the binary format.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(self: &mut Self, count: usize) -> Result<&'a [u8], ImportError> {
+        let bytes = self
+            .bytes
+            .get(self.position..self.position + count)
+            .ok_or_else(|| {
+                ImportError::Format("the recording ends in the middle of a frame".to_string())
+            })?;
+        self.position += count;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(self: &mut Self) -> Result<[u8; N], ImportError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(self: &mut Self) -> Result<u32, ImportError> {
+        Ok(u32::from_le_bytes(self.array()?))
+    }
+
+    fn f64(self: &mut Self) -> Result<f64, ImportError> {
+        Ok(f64::from_le_bytes(self.array()?))
+    }
+}
@@ -15,6 +15,7 @@
 //! draw_point((x + width / 2.0), (height / 2.0 - y));
 //! ```
 
+use crate::physics::boundary::Boundary;
 use crate::vec2::Vec2;
 
 const DEFAULT_COLOR: (u8, u8, u8, u8) = crate::physics::particle::GREY;
@@ -22,17 +23,23 @@ const PAN_STEP: f64 = 20.0;
 const ZOOM_STEP: f64 = 0.15;
 
 /// A two dimensional view into the simulation.
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct View2D {
     /// amount by which the view is offset from the (0, 0) coordinate in the simulation
     pub view_offset: Vec2,
     /// zoom parameter
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub zoom: f64,
     /// background color of the view
     pub bg_color: (u8, u8, u8, u8),
     /// amount by which panning increases the view offset
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub pan_step: f64,
     /// amount by which zooming changes the zoom parameter
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub zoom_step: f64,
+    /// whether to also draw the periodic images of particles that are partially across a periodic boundary
+    pub ghost_images: bool,
 }
 
 impl View2D {
@@ -44,6 +51,7 @@ impl View2D {
             bg_color: DEFAULT_COLOR,
             pan_step: PAN_STEP,
             zoom_step: ZOOM_STEP,
+            ghost_images: true,
         }
     }
 
@@ -107,4 +115,23 @@ impl View2D {
 
         (vec, radius)
     }
+
+    /// Maps a circle to the view, along with its ghost images across a periodic boundary (if `ghost_images`
+    /// is on). The first element is always the circle itself.
+    pub fn map_images_to_view(
+        self: &Self,
+        pos: Vec2,
+        radius: f64,
+        boundary: &Boundary,
+    ) -> Vec<(Vec2, f64)> {
+        if self.ghost_images {
+            boundary
+                .images(pos, radius)
+                .into_iter()
+                .map(|image| self.map_to_view(image, radius))
+                .collect()
+        } else {
+            vec![self.map_to_view(pos, radius)]
+        }
+    }
 }
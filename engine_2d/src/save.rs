@@ -0,0 +1,357 @@
+//! Provides saving and loading of entire systems, as JSON, RON, or bincode (with the `serde` feature).
+//!
+//! Everything in a system is saved except for its snapshots, including the simulation time and the state of any
+//! random number generators, so a loaded system continues exactly where the saved one left off. Closures can't be
+//! saved, so raw forces and general constraints only save the name of their closure, and loading a system looks
+//! each name up in a `Registry`.
+//!
+//! JSON has no way to write infinite or NaN numbers (like the mass of a pinned particle, or the position of one
+//! that blew up), so human readable formats write every such number as the string "inf", "-inf", or "NaN".
+//!
+//! # Example
+//!
+//! ```rust
+//! use rusty_particle_physics_2d::physics::registry::Registry;
+//! use rusty_particle_physics_2d::prelude::*;
+//! use rusty_particle_physics_2d::save::Format;
+//!
+//! let registry = Registry::new().force("wind", |_particle: &Particle| Vec2::new(5.0, 0.0));
+//!
+//! let mut sim = System::new();
+//! sim.add_particle(Particle::new().mass(f64::INFINITY));
+//! sim.forces.push(Force::RawForce {
+//!     function: registry.force_function("wind").unwrap(),
+//! });
+//!
+//! let bytes = sim.to_bytes(Format::Json).unwrap();
+//! let loaded = System::from_bytes(&bytes, Format::Json, &registry).unwrap();
+//! assert_eq!(loaded.particles[0].mass, f64::INFINITY);
+//! ```
+
+use crate::physics::registry::Registry;
+use crate::physics::system::System;
+
+use std::fmt;
+use std::path::Path;
+
+/// A file format that a system can be saved in.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Format {
+    /// JSON, which is human readable and widely supported
+    Json,
+    /// Rusty Object Notation, which is human readable and closer to Rust's own types
+    Ron,
+    /// bincode, which is compact and fast, but not human readable
+    Bincode,
+}
+
+impl Format {
+    /// The format of a file from its extension (.json, .ron, or .bin/.bincode).
+    pub fn from_path(path: impl AsRef<Path>) -> Option<Format> {
+        let extension = path.as_ref().extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "json" => Some(Format::Json),
+            "ron" => Some(Format::Ron),
+            "bin" | "bincode" => Some(Format::Bincode),
+            _ => None,
+        }
+    }
+}
+
+/// An error from saving or loading a system.
+#[derive(Debug)]
+pub enum SaveError {
+    /// The file couldn't be read or written.
+    Io(std::io::Error),
+    Json(serde_json::Error),
+    Ron(ron::Error),
+    Bincode(bincode::Error),
+    /// The file's extension isn't one of the supported formats.
+    UnknownFormat(String),
+    /// A raw force or general constraint names a closure that isn't in the registry.
+    Unregistered(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(error) => write!(f, "{error}"),
+            SaveError::Json(error) => write!(f, "{error}"),
+            SaveError::Ron(error) => write!(f, "{error}"),
+            SaveError::Bincode(error) => write!(f, "{error}"),
+            SaveError::UnknownFormat(path) => {
+                write!(
+                    f,
+                    "unknown format for {path} (expected .json, .ron, or .bin)"
+                )
+            }
+            SaveError::Unregistered(name) => write!(f, "no closure is registered as \"{name}\""),
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl From<std::io::Error> for SaveError {
+    fn from(error: std::io::Error) -> SaveError {
+        SaveError::Io(error)
+    }
+}
+
+impl From<serde_json::Error> for SaveError {
+    fn from(error: serde_json::Error) -> SaveError {
+        SaveError::Json(error)
+    }
+}
+
+impl From<ron::Error> for SaveError {
+    fn from(error: ron::Error) -> SaveError {
+        SaveError::Ron(error)
+    }
+}
+
+impl From<ron::error::SpannedError> for SaveError {
+    fn from(error: ron::error::SpannedError) -> SaveError {
+        SaveError::Ron(error.code)
+    }
+}
+
+impl From<bincode::Error> for SaveError {
+    fn from(error: bincode::Error) -> SaveError {
+        SaveError::Bincode(error)
+    }
+}
+
+fn format_of(path: &Path) -> Result<Format, SaveError> {
+    Format::from_path(path).ok_or_else(|| SaveError::UnknownFormat(path.display().to_string()))
+}
+
+impl System {
+    /// Save the system to a file, in the format given by its extension.
+    pub fn save(self: &Self, path: impl AsRef<Path>) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        std::fs::write(path, self.to_bytes(format_of(path)?)?)?;
+        Ok(())
+    }
+
+    /// Load a system from a file, in the format given by its extension, resolving its named closures from a
+    /// registry.
+    pub fn load(path: impl AsRef<Path>, registry: &Registry) -> Result<System, SaveError> {
+        let path = path.as_ref();
+        let format = format_of(path)?;
+        System::from_bytes(&std::fs::read(path)?, format, registry)
+    }
+
+    /// Serialize the system in a format.
+    pub fn to_bytes(self: &Self, format: Format) -> Result<Vec<u8>, SaveError> {
+        Ok(match format {
+            Format::Json => serde_json::to_vec_pretty(self)?,
+            Format::Ron => {
+                ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?.into_bytes()
+            }
+            Format::Bincode => bincode::serialize(self)?,
+        })
+    }
+
+    /// Deserialize a system in a format, resolving its named closures from a registry.
+    pub fn from_bytes(
+        bytes: &[u8],
+        format: Format,
+        registry: &Registry,
+    ) -> Result<System, SaveError> {
+        let mut system: System = match format {
+            Format::Json => serde_json::from_slice(bytes)?,
+            Format::Ron => ron::de::from_bytes(bytes)?,
+            Format::Bincode => bincode::deserialize(bytes)?,
+        };
+        registry
+            .resolve(&mut system)
+            .map_err(SaveError::Unregistered)?;
+        Ok(system)
+    }
+}
+
+/// Serializes floats that can be infinite or NaN (and containers of them), as strings when the format is human
+/// readable, since JSON would otherwise write them as `null` and then fail to read them back.
+pub(crate) mod float {
+    use crate::physics::pair_table::PairTable;
+
+    use serde::de::DeserializeOwned;
+    use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+    use std::fmt;
+
+    pub fn serialize<S: Serializer, T: Floats>(
+        value: &T,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        value.to_floats().serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Floats>(
+        deserializer: D,
+    ) -> Result<T, D::Error> {
+        T::Repr::deserialize(deserializer).map(T::from_floats)
+    }
+
+    /// A type made of f64s, which is saved as the same shape made of `Float`s.
+    pub trait Floats: Sized {
+        type Repr: Serialize + DeserializeOwned + Clone;
+        fn to_floats(self: &Self) -> Self::Repr;
+        fn from_floats(repr: Self::Repr) -> Self;
+    }
+
+    impl Floats for f64 {
+        type Repr = Float;
+        fn to_floats(self: &Self) -> Float {
+            Float(*self)
+        }
+        fn from_floats(repr: Float) -> f64 {
+            repr.0
+        }
+    }
+
+    impl<T: Floats> Floats for Option<T> {
+        type Repr = Option<T::Repr>;
+        fn to_floats(self: &Self) -> Self::Repr {
+            self.as_ref().map(T::to_floats)
+        }
+        fn from_floats(repr: Self::Repr) -> Self {
+            repr.map(T::from_floats)
+        }
+    }
+
+    impl<T: Floats> Floats for Vec<T> {
+        type Repr = Vec<T::Repr>;
+        fn to_floats(self: &Self) -> Self::Repr {
+            self.iter().map(T::to_floats).collect()
+        }
+        fn from_floats(repr: Self::Repr) -> Self {
+            repr.into_iter().map(T::from_floats).collect()
+        }
+    }
+
+    impl<T: Floats> Floats for [T; 2] {
+        type Repr = [T::Repr; 2];
+        fn to_floats(self: &Self) -> Self::Repr {
+            [self[0].to_floats(), self[1].to_floats()]
+        }
+        fn from_floats([a, b]: Self::Repr) -> Self {
+            [T::from_floats(a), T::from_floats(b)]
+        }
+    }
+
+    impl<T: Floats> Floats for (T, T) {
+        type Repr = (T::Repr, T::Repr);
+        fn to_floats(self: &Self) -> Self::Repr {
+            (self.0.to_floats(), self.1.to_floats())
+        }
+        fn from_floats((a, b): Self::Repr) -> Self {
+            (T::from_floats(a), T::from_floats(b))
+        }
+    }
+
+    impl<T: Floats> Floats for PairTable<T> {
+        type Repr = PairTable<T::Repr>;
+        fn to_floats(self: &Self) -> Self::Repr {
+            self.iter()
+                .fold(PairTable::new(), |table, (&(a, b), value)| {
+                    table.with(a, b, value.to_floats())
+                })
+        }
+        fn from_floats(repr: Self::Repr) -> Self {
+            repr.iter()
+                .fold(PairTable::new(), |table, (&(a, b), value)| {
+                    table.with(a, b, T::from_floats(value.clone()))
+                })
+        }
+    }
+
+    /// An f64 that is saved as "inf", "-inf", or "NaN" when it isn't finite and the format is human readable.
+    #[derive(Clone, Copy)]
+    pub struct Float(f64);
+
+    impl Serialize for Float {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let Float(value) = *self;
+            if !serializer.is_human_readable() || value.is_finite() {
+                serializer.serialize_f64(value)
+            } else if value.is_nan() {
+                serializer.serialize_str("NaN")
+            } else if value > 0.0 {
+                serializer.serialize_str("inf")
+            } else {
+                serializer.serialize_str("-inf")
+            }
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Float {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Float, D::Error> {
+            if deserializer.is_human_readable() {
+                deserializer.deserialize_any(FloatVisitor).map(Float)
+            } else {
+                deserializer.deserialize_f64(FloatVisitor).map(Float)
+            }
+        }
+    }
+
+    struct FloatVisitor;
+
+    impl de::Visitor<'_> for FloatVisitor {
+        type Value = f64;
+
+        fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+            write!(f, "a number, \"inf\", \"-inf\", or \"NaN\"")
+        }
+
+        fn visit_f64<E: de::Error>(self, value: f64) -> Result<f64, E> {
+            Ok(value)
+        }
+
+        fn visit_i64<E: de::Error>(self, value: i64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_u64<E: de::Error>(self, value: u64) -> Result<f64, E> {
+            Ok(value as f64)
+        }
+
+        fn visit_str<E: de::Error>(self, value: &str) -> Result<f64, E> {
+            match value {
+                "inf" => Ok(f64::INFINITY),
+                "-inf" => Ok(f64::NEG_INFINITY),
+                "NaN" => Ok(f64::NAN),
+                _ => Err(E::invalid_value(de::Unexpected::Str(value), &self)),
+            }
+        }
+    }
+}
+
+/// Serializes a map keyed by pairs as a list of (a, b, value), since JSON only allows strings as keys.
+pub(crate) mod pairs {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+    use std::collections::HashMap;
+
+    pub fn serialize<S: Serializer, T: Serialize>(
+        entries: &HashMap<(u32, u32), T>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        // sorted, so that saving the same table always gives the same file
+        let mut list: Vec<(u32, u32, &T)> = entries
+            .iter()
+            .map(|(&(a, b), value)| (a, b, value))
+            .collect();
+        list.sort_by_key(|&(a, b, _)| (a, b));
+        list.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>, T: Deserialize<'de>>(
+        deserializer: D,
+    ) -> Result<HashMap<(u32, u32), T>, D::Error> {
+        let list: Vec<(u32, u32, T)> = Vec::deserialize(deserializer)?;
+        Ok(list
+            .into_iter()
+            .map(|(a, b, value)| ((a, b), value))
+            .collect())
+    }
+}
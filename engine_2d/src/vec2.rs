@@ -5,8 +5,11 @@
 
 /// A 2d euclidean vector
 #[derive(Copy, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vec2 {
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub x: f64,
+    #[cfg_attr(feature = "serde", serde(with = "crate::save::float"))]
     pub y: f64,
 }
 
@@ -49,6 +52,15 @@ impl Vec2 {
         self.x * other.y - self.y * other.x
     }
 
+    /// Rotate the Vec2 counterclockwise by an angle (in radians)
+    pub fn rotate(self: &Self, angle: f64) -> Vec2 {
+        let (sin, cos) = angle.sin_cos();
+        Vec2 {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
+
     /// Perform an affine transformation on the Vec2.
     ///
     /// y = Ax + b
@@ -20,6 +20,20 @@
 //! | R      | Reset Sim   |
 //! | Q      | Quit        |
 //!
+//! A recording (see `rusty_particle_physics_2d::recording`) can also be replayed, without simulating it, with
+//! `Renderer::replay`. While replaying:
+//!
+//! |  Key / Mouse  |            Action             |
+//! |---------------|-------------------------------|
+//! | Space         | Pause/Play                    |
+//! | , / .         | Step Back/Forward a Frame     |
+//! | [ / ]         | Skip Back/Forward 10%         |
+//! | R or Home     | Back to the Start             |
+//! | End           | Skip to the End               |
+//! | Click & Drag  | Scrub Through the Recording   |
+//!
+//! (Panning, zooming, and quitting work the same as above.)
+//!
 //!
 //! # Example usage:
 //!
@@ -37,12 +51,15 @@
 
 use rusty_particle_physics_2d::interaction;
 use rusty_particle_physics_2d::physics::system::System;
+use rusty_particle_physics_2d::recording::Recording;
 use rusty_particle_physics_2d::rendering::View2D;
 use rusty_particle_physics_2d::vec2::Vec2;
 
 use winit::{
     dpi::PhysicalSize, //LogicalSize,
-    event::{DeviceEvent, ElementState, Event, KeyboardInput, VirtualKeyCode, WindowEvent},
+    event::{
+        DeviceEvent, ElementState, Event, KeyboardInput, MouseButton, VirtualKeyCode, WindowEvent,
+    },
     event_loop::{ControlFlow, EventLoop},
     window::{Window, WindowBuilder},
 };
@@ -51,10 +68,13 @@ use std::time::Instant;
 
 use softbuffer::GraphicsContext;
 
-use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Stroke, Transform};
+use tiny_skia::{Color, FillRule, Paint, PathBuilder, Pixmap, Rect, Stroke, Transform};
 
 const STROKE: f32 = 2.5;
 const STROKE_COLOR: (u8, u8, u8, u8) = rusty_particle_physics_2d::physics::particle::BLACK;
+/// height of the progress bar shown while replaying
+const PROGRESS_HEIGHT: f32 = 6.0;
+const PROGRESS_COLOR: (u8, u8, u8, u8) = rusty_particle_physics_2d::physics::particle::CRIMSON;
 
 pub struct Renderer {
     view: View2D,
@@ -168,79 +188,8 @@ impl Renderer {
                     let width = Self::dyn_width(&self.context) as f64;
                     let height = Self::dyn_height(&self.context) as f64;
 
-                    // create buffer
-                    let mut draw_buffer = Pixmap::new(width as u32, height as u32).unwrap();
-
-                    // create drawing styles
-                    let mut style = Paint::default();
-                    style.anti_alias = true;
-                    let mut stroke = Stroke::default();
-                    stroke.width = STROKE * (self.view.parameterized_zoom() as f32);
-
-                    // paint the background
-                    draw_buffer.fill(Color::from_rgba8(
-                        self.view.bg_color.0,
-                        self.view.bg_color.1,
-                        self.view.bg_color.2,
-                        self.view.bg_color.3,
-                    ));
-
-                    // draw the sim's particles
-                    for particle in &sim.particles {
-                        // get particle position and radius mapped to window space
-                        let (Vec2 { x, y }, radius) =
-                            self.view.map_to_view(particle.pos, particle.radius);
-                        let (r, g, b, a) = particle.color;
-
-                        let path = {
-                            let mut pb = PathBuilder::new();
-                            // draw the particle using (0,0) to be in the center of the screen
-                            pb.push_circle(
-                                (x + width / 2.0) as f32,
-                                (height / 2.0 - y) as f32,
-                                radius as f32,
-                            );
-                            pb.finish().unwrap()
-                        };
-
-                        // draw the particle outlines
-                        style.set_color_rgba8(
-                            STROKE_COLOR.0,
-                            STROKE_COLOR.1,
-                            STROKE_COLOR.2,
-                            STROKE_COLOR.3,
-                        );
-                        draw_buffer.stroke_path(
-                            &path,
-                            &style,
-                            &stroke,
-                            Transform::identity(),
-                            None,
-                        );
-
-                        // fill in the particle outlines
-                        style.set_color_rgba8(r, g, b, a);
-                        draw_buffer.fill_path(
-                            &path,
-                            &style,
-                            FillRule::Winding,
-                            Transform::identity(),
-                            None,
-                        );
-                    }
-
-                    // convert the draw_buffer to the format that Softbuffer uses
-                    let framebuffer: Vec<u32> = draw_buffer
-                        .pixels()
-                        .into_iter()
-                        .map(|pixel| {
-                            Renderer::rgb_to_softbuffer((pixel.red(), pixel.green(), pixel.blue()))
-                        })
-                        .collect();
-
-                    // write the contents of framebuffer to the window's framebuffer
-                    self.context
-                        .set_buffer(&framebuffer, width as u16, height as u16);
+                    let draw_buffer = Self::draw(&self.view, &sim, width, height);
+                    Self::present(&mut self.context, &draw_buffer);
 
                     // find the time since last frame
                     let elapsed = (Instant::now().duration_since(time).as_micros() as f64)
@@ -265,6 +214,219 @@ impl Renderer {
         });
     }
 
+    /// Replay a recording in a new window, without simulating it.
+    ///
+    /// The recorded positions and velocities are applied to the given sim's particles (matched by id), so the sim
+    /// should be set up the same way as the recorded one, so that its particles look (radius, color, etc) and wrap
+    /// around its boundary the same way. `System::new()` also works, in which case every particle looks the same.
+    pub fn replay(mut self: Self, recording: Recording, mut sim: System) {
+        if recording.frames.is_empty() {
+            return;
+        }
+        let start = recording.frames[0].time;
+        let end = recording.frames[recording.frames.len() - 1].time;
+        let mut playhead = start;
+        let mut playing = true;
+        let mut scrubbing = false;
+        let mut cursor_x = 0.0;
+        let mut time = Instant::now();
+
+        self.event_loop.run(move |event, _, control_flow| {
+            *control_flow = ControlFlow::Poll;
+
+            // the time of the frame some number of frames away from the current one
+            let frame_time = |playhead: f64, offset: isize| {
+                let frame = recording.frame_at(playhead).saturating_add_signed(offset);
+                recording.frames[frame.min(recording.frames.len() - 1)].time
+            };
+
+            match event {
+                Event::WindowEvent {
+                    event: WindowEvent::CloseRequested,
+                    ..
+                } => *control_flow = ControlFlow::Exit,
+                Event::WindowEvent {
+                    event: WindowEvent::CursorMoved { position, .. },
+                    ..
+                } => cursor_x = position.x,
+                Event::WindowEvent {
+                    event:
+                        WindowEvent::MouseInput {
+                            state,
+                            button: MouseButton::Left,
+                            ..
+                        },
+                    ..
+                } => scrubbing = state == ElementState::Pressed,
+                Event::DeviceEvent {
+                    event:
+                        DeviceEvent::Key(KeyboardInput {
+                            state: ElementState::Pressed,
+                            virtual_keycode: Some(code),
+                            ..
+                        }),
+                    ..
+                } => match code {
+                    VirtualKeyCode::Left => self.view.pan_left(),
+                    VirtualKeyCode::Right => self.view.pan_right(),
+                    VirtualKeyCode::Up => self.view.pan_up(),
+                    VirtualKeyCode::Down => self.view.pan_down(),
+                    VirtualKeyCode::Equals => self.view.zoom_in(),
+                    VirtualKeyCode::Minus => self.view.zoom_out(),
+                    VirtualKeyCode::Return => self.view.reset(),
+                    VirtualKeyCode::Space => playing = !playing,
+                    VirtualKeyCode::Comma => {
+                        playing = false;
+                        playhead = frame_time(playhead, -1);
+                    }
+                    VirtualKeyCode::Period => {
+                        playing = false;
+                        playhead = frame_time(playhead, 1);
+                    }
+                    VirtualKeyCode::LBracket => {
+                        playhead = (playhead - (end - start) / 10.0).max(start)
+                    }
+                    VirtualKeyCode::RBracket => {
+                        playhead = (playhead + (end - start) / 10.0).min(end)
+                    }
+                    VirtualKeyCode::R | VirtualKeyCode::Home => playhead = start,
+                    VirtualKeyCode::End => playhead = end,
+                    VirtualKeyCode::Q => *control_flow = ControlFlow::Exit,
+                    _ => (),
+                },
+                Event::MainEventsCleared => {
+                    let width = Self::dyn_width(&self.context) as f64;
+                    let height = Self::dyn_height(&self.context) as f64;
+
+                    // advance the playhead in real time, or move it to the cursor while scrubbing
+                    let elapsed = Instant::now().duration_since(time).as_secs_f64();
+                    time = Instant::now();
+                    if scrubbing {
+                        playhead = start + (cursor_x / width).clamp(0.0, 1.0) * (end - start);
+                    } else if playing {
+                        playhead = (playhead + elapsed).min(end);
+                    }
+                    let frame = recording.frame_at(playhead);
+                    recording.apply(frame, &mut sim);
+
+                    let mut draw_buffer = Self::draw(&self.view, &sim, width, height);
+                    // draw how far through the recording the playhead is
+                    let progress = if end > start {
+                        (playhead - start) / (end - start)
+                    } else {
+                        1.0
+                    };
+                    if let Some(bar) = Rect::from_xywh(
+                        0.0,
+                        height as f32 - PROGRESS_HEIGHT,
+                        (progress * width) as f32,
+                        PROGRESS_HEIGHT,
+                    ) {
+                        let mut style = Paint::default();
+                        style.set_color_rgba8(
+                            PROGRESS_COLOR.0,
+                            PROGRESS_COLOR.1,
+                            PROGRESS_COLOR.2,
+                            PROGRESS_COLOR.3,
+                        );
+                        draw_buffer.fill_rect(bar, &style, Transform::identity(), None);
+                    }
+                    Self::present(&mut self.context, &draw_buffer);
+
+                    self.context.window_mut().set_title(
+                        format!(
+                            "Replay - Frame: {}/{} - Time: {:.3}{}",
+                            frame + 1,
+                            recording.frames.len(),
+                            recording.frames[frame].time,
+                            if playing { "" } else { " - Paused" }
+                        )
+                        .as_str(),
+                    );
+                }
+                _ => (),
+            };
+        });
+    }
+
+    /// Draw the sim's particles (and their ghost images across a periodic boundary) onto a new buffer the size of
+    /// the window.
+    fn draw(view: &View2D, sim: &System, width: f64, height: f64) -> Pixmap {
+        // create buffer
+        let mut draw_buffer = Pixmap::new(width as u32, height as u32).unwrap();
+
+        // create drawing styles
+        let mut style = Paint::default();
+        style.anti_alias = true;
+        let mut stroke = Stroke::default();
+        stroke.width = STROKE * (view.parameterized_zoom() as f32);
+
+        // paint the background
+        draw_buffer.fill(Color::from_rgba8(
+            view.bg_color.0,
+            view.bg_color.1,
+            view.bg_color.2,
+            view.bg_color.3,
+        ));
+
+        // draw the sim's particles
+        for particle in &sim.particles {
+            let (r, g, b, a) = particle.color;
+            for (Vec2 { x, y }, radius) in
+                view.map_images_to_view(particle.pos, particle.radius, &sim.boundary)
+            {
+                let path = {
+                    let mut pb = PathBuilder::new();
+                    // draw the particle using (0,0) to be in the center of the screen
+                    pb.push_circle(
+                        (x + width / 2.0) as f32,
+                        (height / 2.0 - y) as f32,
+                        radius as f32,
+                    );
+                    pb.finish().unwrap()
+                };
+
+                // draw the particle outlines
+                style.set_color_rgba8(
+                    STROKE_COLOR.0,
+                    STROKE_COLOR.1,
+                    STROKE_COLOR.2,
+                    STROKE_COLOR.3,
+                );
+                draw_buffer.stroke_path(&path, &style, &stroke, Transform::identity(), None);
+
+                // fill in the particle outlines
+                style.set_color_rgba8(r, g, b, a);
+                draw_buffer.fill_path(
+                    &path,
+                    &style,
+                    FillRule::Winding,
+                    Transform::identity(),
+                    None,
+                );
+            }
+        }
+
+        draw_buffer
+    }
+
+    /// Show a drawn buffer in the window.
+    fn present(context: &mut GraphicsContext<Window>, draw_buffer: &Pixmap) {
+        // convert the draw_buffer to the format that Softbuffer uses
+        let framebuffer: Vec<u32> = draw_buffer
+            .pixels()
+            .into_iter()
+            .map(|pixel| Renderer::rgb_to_softbuffer((pixel.red(), pixel.green(), pixel.blue())))
+            .collect();
+
+        // write the contents of framebuffer to the window's framebuffer
+        context.set_buffer(
+            &framebuffer,
+            draw_buffer.width() as u16,
+            draw_buffer.height() as u16,
+        );
+    }
+
     /// Render/Bake an animation instead of running real-time in a window.
     pub fn create_animation(self: &Self, _sim: System, _fps: u8, _length: f64) {
         todo!();
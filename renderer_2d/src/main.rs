@@ -1,8 +1,17 @@
 use renderer_2d::Renderer;
 use rusty_particle_physics_2d::prelude::*;
+use rusty_particle_physics_2d::recording::Recording;
 
 fn main() {
     let window = Renderer::new(600, 600);
+
+    // replay a recording (.csv or .frames) instead, if one is given
+    if let Some(path) = std::env::args().nth(1) {
+        let recording = Recording::load(&path).unwrap_or_else(|error| panic!("{path}: {error}"));
+        window.replay(recording, System::new());
+        return;
+    }
+
     let mut sim = System::new();
 
     let gravity = Vec2::new(0.0, -400.0);